@@ -0,0 +1,888 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::Path,
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actor::{
+        character::ClassStats, MeleeDefinition, ProjectileDefinition, RevealDefinition,
+        ShieldDefinition,
+    },
+    core::CoreAction,
+    lobby::{Character, Lobby, LobbyState, PlayerId},
+    world::{CursorWorld, LinkId, Me},
+    ASSET_DIR,
+};
+
+/// File in the asset directory the ability definitions are read from.
+pub const ABILITIES_FILE: &str = "abilities.yaml";
+
+/// Input an ability is activated with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AbilitySlot {
+    /// `atk`, mouse left.
+    Attack,
+    /// `util`, mouse right.
+    Utility,
+    /// `act`, `e`.
+    Act,
+}
+
+impl AbilitySlot {
+    pub const ALL: [AbilitySlot; 3] = [AbilitySlot::Attack, AbilitySlot::Utility, AbilitySlot::Act];
+
+    pub fn action(self) -> CoreAction {
+        match self {
+            AbilitySlot::Attack => CoreAction::Attack,
+            AbilitySlot::Utility => CoreAction::Utility,
+            AbilitySlot::Act => CoreAction::Act,
+        }
+    }
+}
+
+/// What an ability is aimed at.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Targeting {
+    /// The caster itself.
+    #[default]
+    Myself,
+    /// A point in the world within the range.
+    Point { range: f32 },
+    /// A direction on the ground.
+    Direction,
+    /// An actor within the range.
+    Entity { range: f32 },
+    /// A rune drawn on the ground within the range, see [`RuneLibrary`](super::RuneLibrary).
+    Rune { range: f32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AbilityDefinition {
+    pub name: String,
+    pub slot: AbilitySlot,
+    /// Seconds before the ability can be activated again, counted from the activation.
+    pub cooldown: f32,
+    /// [`Energy`] taken on activation.
+    pub cost: f32,
+    /// Seconds between the activation and the effect.
+    pub cast_time: f32,
+    pub targeting: Targeting,
+    /// Characters that can not fight can not use it.
+    pub offensive: bool,
+    /// Shot toward the target when the cast ends.
+    pub projectile: Option<ProjectileDefinition>,
+    /// Swung when the cast ends, the cast time is the wind-up.
+    pub melee: Option<MeleeDefinition>,
+    /// Raised toward the target when the cast ends.
+    pub shield: Option<ShieldDefinition>,
+    /// Toggles [`Sneaking`](crate::actor::Sneaking) on the caster.
+    pub sneak: bool,
+    /// Shares the [`TrueSight`](crate::actor::TrueSight) with the characters around.
+    pub reveal: Option<RevealDefinition>,
+}
+
+impl Default for AbilityDefinition {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            slot: AbilitySlot::Attack,
+            cooldown: 1.,
+            cost: 0.,
+            cast_time: 0.,
+            targeting: Targeting::default(),
+            offensive: false,
+            projectile: None,
+            melee: None,
+            shield: None,
+            sneak: false,
+            reveal: None,
+        }
+    }
+}
+
+/// Definitions of every ability by its id, read from the [`ABILITIES_FILE`].
+#[derive(Resource, Debug, Clone, Default, Deref)]
+pub struct AbilityDefinitions(HashMap<String, AbilityDefinition>);
+
+/// Target of an activation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbilityTarget {
+    Myself,
+    Point(Vec3),
+    Direction(Vec3),
+    Entity(Entity),
+    /// Id of the rune in the [`RuneLibrary`](super::RuneLibrary) and the middle of the drawing.
+    Rune {
+        point: Vec3,
+        rune: String,
+    },
+}
+
+/// [`AbilityTarget`] the peers agree on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum NetworkTarget {
+    Myself,
+    Point(Vec3),
+    Direction(Vec3),
+    Player(PlayerId),
+    Link(LinkId),
+    Rune { point: Vec3, rune: String },
+}
+
+/// Why an activation was refused.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbilityError {
+    /// Nothing is bound to the slot.
+    Empty,
+    Cooldown,
+    Cost,
+    /// Another ability is being cast.
+    Casting,
+    /// The target does not fit the targeting of the ability.
+    Target,
+    Range,
+    /// The character can not fight.
+    Pacifist,
+}
+
+/// Last sequence number of the activations of this peer.
+#[derive(Resource, Debug, Default)]
+pub struct AbilitySeq(u32);
+
+impl AbilitySeq {
+    pub fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(1);
+        self.0
+    }
+}
+
+/// Energy abilities cost.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct Energy {
+    pub current: f32,
+    pub max: f32,
+    /// Energy restored per second.
+    pub regen: f32,
+}
+
+impl Energy {
+    pub fn new(max: f32, regen: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            regen,
+        }
+    }
+}
+
+impl Default for Energy {
+    fn default() -> Self {
+        Self::new(100., 10.)
+    }
+}
+
+/// Ability bound to a slot.
+#[derive(Debug, Clone)]
+pub struct AbilityState {
+    pub id: String,
+    pub cooldown: Timer,
+}
+
+/// Abilities of an actor by their slot.
+#[derive(Component, Debug, Default)]
+pub struct Abilities {
+    slots: HashMap<AbilitySlot, AbilityState>,
+    /// Activations of this peer the host has not confirmed, by their sequence number.
+    predicted: HashMap<u32, AbilitySlot>,
+}
+
+impl Abilities {
+    /// Binds the abilities to their slots, the first one wins a slot.
+    pub fn new(ids: &[String], definitions: &AbilityDefinitions) -> Self {
+        let mut slots = HashMap::new();
+        for id in ids {
+            let Some(definition) = definitions.get(id) else {
+                log::warn!("unknown ability {:?}", id);
+                continue;
+            };
+            let mut cooldown = Timer::from_seconds(definition.cooldown, TimerMode::Once);
+            // ready right away
+            cooldown.tick(cooldown.duration());
+            slots.entry(definition.slot).or_insert(AbilityState {
+                id: id.clone(),
+                cooldown,
+            });
+        }
+        Self {
+            slots,
+            predicted: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, slot: AbilitySlot) -> Option<&AbilityState> {
+        self.slots.get(&slot)
+    }
+
+    /// Forgets a predicted activation the host confirmed.
+    pub fn confirm(&mut self, seq: u32) {
+        self.predicted.remove(&seq);
+    }
+}
+
+/// An ability between its activation and its effect.
+#[derive(Component, Debug)]
+pub struct Casting {
+    pub slot: AbilitySlot,
+    pub target: AbilityTarget,
+    pub seq: u32,
+    /// Host time the caster sees the cast end at, see [`AbilityActivated::time`].
+    pub time: Option<f64>,
+    pub timer: Timer,
+}
+
+/// Asks to activate the ability in a slot.
+#[derive(Event, Debug, Clone)]
+pub struct AbilityRequest {
+    pub caster: Entity,
+    pub slot: AbilitySlot,
+    pub target: AbilityTarget,
+    /// Sequence number of the peer that asked, it matches the answer of the host.
+    pub seq: u32,
+    /// Host time the caster asked at, `None` when it is now.
+    pub time: Option<f64>,
+}
+
+/// Sent when a [`AbilityRequest`] is refused.
+#[derive(Event, Debug, Clone)]
+pub struct AbilityRejected {
+    pub caster: Entity,
+    pub slot: AbilitySlot,
+    pub seq: u32,
+    pub error: AbilityError,
+}
+
+/// Sent when the cast of an ability ends, the abilities apply their effect on it.
+#[derive(Event, Debug, Clone)]
+pub struct AbilityActivated {
+    pub caster: Entity,
+    pub slot: AbilitySlot,
+    pub ability: String,
+    pub target: AbilityTarget,
+    pub seq: u32,
+    /// Host time the caster saw the activation at, `None` when it is now.
+    pub time: Option<f64>,
+    /// Activated by this peer ahead of the host.
+    pub predicted: bool,
+}
+
+pub struct AbilityPlugins;
+
+impl Plugin for AbilityPlugins {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Energy>()
+            .add_event::<AbilityRequest>()
+            .add_event::<AbilityRejected>()
+            .add_event::<AbilityActivated>()
+            .init_resource::<AbilitySeq>()
+            .insert_resource(load_abilities(Path::new(ASSET_DIR).join(ABILITIES_FILE)))
+            .add_systems(
+                Update,
+                (
+                    regen_energy,
+                    tick_cooldowns,
+                    request_abilities,
+                    // clients predict their own activations
+                    start_casts,
+                    rollback_rejected.run_if(in_state(LobbyState::Client)),
+                    finish_casts,
+                )
+                    .chain()
+                    .run_if(not(in_state(LobbyState::None))),
+            );
+    }
+}
+
+fn load_abilities(path: impl AsRef<Path>) -> AbilityDefinitions {
+    let path = path.as_ref();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            log::error!("Failed to open ability definitions ({:?}): {}", path, err);
+            return AbilityDefinitions::default();
+        }
+    };
+    match serde_yaml::from_reader(file) {
+        Ok(definitions) => AbilityDefinitions(definitions),
+        Err(err) => {
+            log::error!("Failed to read ability definitions ({:?}): {}", path, err);
+            AbilityDefinitions::default()
+        }
+    }
+}
+
+fn regen_energy(mut energy_query: Query<&mut Energy>, time: Res<Time>) {
+    for mut energy in energy_query.iter_mut() {
+        if energy.current < energy.max {
+            energy.current = (energy.current + energy.regen * time.delta_seconds()).min(energy.max);
+        }
+    }
+}
+
+fn tick_cooldowns(mut abilities_query: Query<&mut Abilities>, time: Res<Time>) {
+    for mut abilities in abilities_query.iter_mut() {
+        for state in abilities.slots.values_mut() {
+            state.cooldown.tick(time.delta());
+        }
+    }
+}
+
+/// Turns the ability inputs of this peer into requests aimed at the cursor.
+fn request_abilities(
+    me_query: Query<(Entity, &Character, &Transform, &Abilities), With<Me>>,
+    lobby: Option<Res<Lobby>>,
+    definitions: Res<AbilityDefinitions>,
+    targets: AbilityTargets,
+    mut request_events: EventWriter<AbilityRequest>,
+    mut seq: ResMut<AbilitySeq>,
+) {
+    let (Some(lobby), Ok((caster, character, transform, abilities))) =
+        (lobby, me_query.get_single())
+    else {
+        return;
+    };
+    let Some(inputs) = lobby.inputs_of(&character.id, true) else {
+        return;
+    };
+
+    for slot in AbilitySlot::ALL {
+        if !inputs.get_just_pressed(slot.action()).unwrap_or(false) {
+            continue;
+        }
+        let Some(definition) = abilities
+            .get(slot)
+            .and_then(|state| definitions.get(&state.id))
+        else {
+            continue;
+        };
+        let aim = lobby.me.aim;
+        let target = match definition.targeting {
+            Targeting::Myself => Some(AbilityTarget::Myself),
+            Targeting::Point { .. } => aim.map(AbilityTarget::Point),
+            Targeting::Direction => aim
+                .and_then(|aim| {
+                    (aim - transform.translation)
+                        .reject_from(Vec3::Y)
+                        .try_normalize()
+                })
+                .map(AbilityTarget::Direction),
+            Targeting::Entity { .. } => targets.under_cursor().map(AbilityTarget::Entity),
+            // drawn while the input is held
+            Targeting::Rune { .. } => None,
+        };
+        let Some(target) = target else {
+            continue;
+        };
+
+        request_events.send(AbilityRequest {
+            caster,
+            slot,
+            target,
+            seq: seq.next(),
+            time: None,
+        });
+    }
+}
+
+/// Validates the requests and starts the accepted casts.
+#[allow(clippy::type_complexity)]
+fn start_casts(
+    mut commands: Commands,
+    mut request_events: EventReader<AbilityRequest>,
+    mut caster_query: Query<(
+        &Transform,
+        &mut Abilities,
+        Option<&mut Energy>,
+        Option<&ClassStats>,
+        Has<Casting>,
+    )>,
+    position_query: Query<&GlobalTransform>,
+    definitions: Res<AbilityDefinitions>,
+    mut rejected_events: EventWriter<AbilityRejected>,
+    lobby_state: Res<State<LobbyState>>,
+) {
+    let predicting = *lobby_state.get() == LobbyState::Client;
+    // `Casting` is inserted through the commands, the casts started this frame are not in the query
+    let mut started = HashSet::new();
+    for request in request_events.read() {
+        let Ok((transform, mut abilities, mut energy, stats, casting)) =
+            caster_query.get_mut(request.caster)
+        else {
+            continue;
+        };
+
+        let validation = abilities
+            .get(request.slot)
+            .and_then(|state| Some((state, definitions.get(&state.id)?)))
+            .ok_or(AbilityError::Empty)
+            .and_then(|(state, definition)| {
+                let in_range =
+                    |point: Vec3, range: f32| point.distance(transform.translation) <= range;
+                if casting || started.contains(&request.caster) {
+                    Err(AbilityError::Casting)
+                } else if !state.cooldown.finished() {
+                    Err(AbilityError::Cooldown)
+                } else if definition.offensive && stats.is_some_and(|stats| !stats.can_fight) {
+                    Err(AbilityError::Pacifist)
+                } else if energy.as_ref().map_or(definition.cost > 0., |energy| {
+                    energy.current < definition.cost
+                }) {
+                    Err(AbilityError::Cost)
+                } else {
+                    match (definition.targeting, &request.target) {
+                        (Targeting::Myself, AbilityTarget::Myself)
+                        | (Targeting::Direction, AbilityTarget::Direction(_)) => Ok(definition),
+                        (Targeting::Point { range }, AbilityTarget::Point(point))
+                        | (Targeting::Rune { range }, AbilityTarget::Rune { point, .. }) => {
+                            in_range(*point, range)
+                                .then_some(definition)
+                                .ok_or(AbilityError::Range)
+                        }
+                        (Targeting::Entity { range }, AbilityTarget::Entity(entity)) => {
+                            let point = position_query
+                                .get(*entity)
+                                .map_err(|_| AbilityError::Target)?
+                                .translation();
+                            in_range(point, range)
+                                .then_some(definition)
+                                .ok_or(AbilityError::Range)
+                        }
+                        _ => Err(AbilityError::Target),
+                    }
+                }
+            });
+
+        let definition = match validation {
+            Ok(definition) => definition,
+            Err(error) => {
+                log::debug!(
+                    "{:?} {:?} refused: {:?}",
+                    request.caster,
+                    request.slot,
+                    error
+                );
+                rejected_events.send(AbilityRejected {
+                    caster: request.caster,
+                    slot: request.slot,
+                    seq: request.seq,
+                    error,
+                });
+                continue;
+            }
+        };
+
+        if let Some(energy) = energy.as_mut() {
+            energy.current -= definition.cost;
+        }
+        if let Some(state) = abilities.slots.get_mut(&request.slot) {
+            state.cooldown.reset();
+        }
+        if predicting {
+            abilities.predicted.insert(request.seq, request.slot);
+        }
+        started.insert(request.caster);
+        commands.entity(request.caster).insert(Casting {
+            slot: request.slot,
+            target: request.target.clone(),
+            seq: request.seq,
+            time: request.time.map(|time| time + definition.cast_time as f64),
+            timer: Timer::from_seconds(definition.cast_time, TimerMode::Once),
+        });
+    }
+}
+
+/// Undoes the activations of this peer the host refused.
+fn rollback_rejected(
+    mut commands: Commands,
+    mut rejected_events: EventReader<AbilityRejected>,
+    mut caster_query: Query<(&mut Abilities, Option<&mut Energy>, Option<&Casting>)>,
+    definitions: Res<AbilityDefinitions>,
+) {
+    for rejected in rejected_events.read() {
+        let Ok((mut abilities, energy, casting)) = caster_query.get_mut(rejected.caster) else {
+            continue;
+        };
+        // refusals of the local check never started anything
+        if abilities.predicted.remove(&rejected.seq).is_none() {
+            continue;
+        }
+        log::info!("host refused {:?}: {:?}", rejected.slot, rejected.error);
+
+        let Some(state) = abilities.slots.get_mut(&rejected.slot) else {
+            continue;
+        };
+        let duration = state.cooldown.duration();
+        state.cooldown.tick(duration);
+        if let (Some(mut energy), Some(definition)) = (energy, definitions.get(&state.id)) {
+            energy.current = (energy.current + definition.cost).min(energy.max);
+        }
+        if casting.is_some_and(|casting| casting.seq == rejected.seq) {
+            commands.entity(rejected.caster).remove::<Casting>();
+        }
+    }
+}
+
+fn finish_casts(
+    mut commands: Commands,
+    mut casting_query: Query<(Entity, &mut Casting, &Abilities)>,
+    mut activated_events: EventWriter<AbilityActivated>,
+    lobby_state: Res<State<LobbyState>>,
+    time: Res<Time>,
+) {
+    let predicted = *lobby_state.get() == LobbyState::Client;
+    for (entity, mut casting, abilities) in casting_query.iter_mut() {
+        if !casting.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        commands.entity(entity).remove::<Casting>();
+        let Some(state) = abilities.get(casting.slot) else {
+            continue;
+        };
+        activated_events.send(AbilityActivated {
+            caster: entity,
+            slot: casting.slot,
+            ability: state.id.clone(),
+            target: casting.target.clone(),
+            seq: casting.seq,
+            time: casting.time,
+            predicted,
+        });
+    }
+}
+
+/// Converts ability targets between entities and the ids the peers share.
+#[derive(SystemParam)]
+pub struct AbilityTargets<'w, 's> {
+    character_query: Query<'w, 's, (Entity, &'static Character)>,
+    link_query: Query<'w, 's, (Entity, &'static LinkId)>,
+    cursor: Res<'w, CursorWorld>,
+}
+
+impl AbilityTargets<'_, '_> {
+    pub fn to_network(&self, target: &AbilityTarget) -> Option<NetworkTarget> {
+        Some(match target {
+            AbilityTarget::Myself => NetworkTarget::Myself,
+            AbilityTarget::Point(point) => NetworkTarget::Point(*point),
+            AbilityTarget::Direction(direction) => NetworkTarget::Direction(*direction),
+            AbilityTarget::Entity(entity) => {
+                if let Ok((_, character)) = self.character_query.get(*entity) {
+                    NetworkTarget::Player(character.id)
+                } else {
+                    NetworkTarget::Link(self.link_query.get(*entity).ok()?.1.clone())
+                }
+            }
+            AbilityTarget::Rune { point, rune } => NetworkTarget::Rune {
+                point: *point,
+                rune: rune.clone(),
+            },
+        })
+    }
+
+    pub fn from_network(&self, target: &NetworkTarget) -> Option<AbilityTarget> {
+        Some(match target {
+            NetworkTarget::Myself => AbilityTarget::Myself,
+            NetworkTarget::Point(point) => AbilityTarget::Point(*point),
+            NetworkTarget::Direction(direction) => AbilityTarget::Direction(*direction),
+            NetworkTarget::Player(id) => AbilityTarget::Entity(
+                self.character_query
+                    .iter()
+                    .find(|(_, character)| character.id == *id)?
+                    .0,
+            ),
+            NetworkTarget::Link(link_id) => {
+                AbilityTarget::Entity(self.link_query.iter().find(|(_, id)| *id == link_id)?.0)
+            }
+            NetworkTarget::Rune { point, rune } => AbilityTarget::Rune {
+                point: *point,
+                rune: rune.clone(),
+            },
+        })
+    }
+
+    /// Actor under the cursor of this peer, one the peers can refer to.
+    fn under_cursor(&self) -> Option<Entity> {
+        self.cursor.entity.filter(|entity| {
+            self.character_query.contains(*entity) || self.link_query.contains(*entity)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definitions() -> AbilityDefinitions {
+        AbilityDefinitions(HashMap::from([
+            (
+                "strike".to_string(),
+                AbilityDefinition {
+                    slot: AbilitySlot::Attack,
+                    cooldown: 5.,
+                    cost: 30.,
+                    targeting: Targeting::Direction,
+                    offensive: true,
+                    ..default()
+                },
+            ),
+            (
+                "leap".to_string(),
+                AbilityDefinition {
+                    slot: AbilitySlot::Utility,
+                    targeting: Targeting::Point { range: 5. },
+                    ..default()
+                },
+            ),
+            (
+                "mark".to_string(),
+                AbilityDefinition {
+                    slot: AbilitySlot::Act,
+                    targeting: Targeting::Entity { range: 5. },
+                    ..default()
+                },
+            ),
+        ]))
+    }
+
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.init_state::<LobbyState>()
+            .add_event::<AbilityRequest>()
+            .add_event::<AbilityRejected>()
+            .insert_resource(definitions())
+            .add_systems(Update, start_casts);
+        app
+    }
+
+    fn spawn_caster(app: &mut App, ids: &[&str], stats: ClassStats) -> Entity {
+        let ids = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let abilities = Abilities::new(&ids, app.world.resource::<AbilityDefinitions>());
+        app.world
+            .spawn((
+                Transform::default(),
+                abilities,
+                Energy::new(100., 0.),
+                stats,
+            ))
+            .id()
+    }
+
+    fn send(app: &mut App, caster: Entity, slot: AbilitySlot, target: AbilityTarget) {
+        app.world.send_event(AbilityRequest {
+            caster,
+            slot,
+            target,
+            seq: 0,
+            time: None,
+        });
+    }
+
+    /// Runs the requests sent so far, the refusals in their order.
+    fn refusals(app: &mut App) -> Vec<AbilityError> {
+        app.update();
+        app.world
+            .resource_mut::<Events<AbilityRejected>>()
+            .drain()
+            .map(|rejected| rejected.error)
+            .collect()
+    }
+
+    fn request(
+        app: &mut App,
+        caster: Entity,
+        slot: AbilitySlot,
+        target: AbilityTarget,
+    ) -> Result<(), AbilityError> {
+        send(app, caster, slot, target);
+        let refused = refusals(app);
+        // the next request is not held back by this cast
+        app.world.entity_mut(caster).remove::<Casting>();
+        refused.first().map_or(Ok(()), |error| Err(*error))
+    }
+
+    #[test]
+    fn accepted_cast_pays_and_cools_down() {
+        let mut app = test_app();
+        let caster = spawn_caster(&mut app, &["strike"], ClassStats::default());
+
+        let direction = AbilityTarget::Direction(Vec3::X);
+        send(&mut app, caster, AbilitySlot::Attack, direction.clone());
+        assert!(refusals(&mut app).is_empty());
+        assert!(app.world.get::<Casting>(caster).is_some());
+        assert_eq!(app.world.get::<Energy>(caster).unwrap().current, 70.);
+
+        app.world.entity_mut(caster).remove::<Casting>();
+        assert_eq!(
+            request(&mut app, caster, AbilitySlot::Attack, direction),
+            Err(AbilityError::Cooldown)
+        );
+        assert_eq!(app.world.get::<Energy>(caster).unwrap().current, 70.);
+    }
+
+    #[test]
+    fn empty_slot_is_refused() {
+        let mut app = test_app();
+        let caster = spawn_caster(&mut app, &["strike"], ClassStats::default());
+        assert_eq!(
+            request(&mut app, caster, AbilitySlot::Act, AbilityTarget::Myself),
+            Err(AbilityError::Empty)
+        );
+    }
+
+    #[test]
+    fn missing_energy_is_refused() {
+        let mut app = test_app();
+        let caster = spawn_caster(&mut app, &["strike"], ClassStats::default());
+        app.world.get_mut::<Energy>(caster).unwrap().current = 20.;
+        assert_eq!(
+            request(
+                &mut app,
+                caster,
+                AbilitySlot::Attack,
+                AbilityTarget::Direction(Vec3::X)
+            ),
+            Err(AbilityError::Cost)
+        );
+    }
+
+    #[test]
+    fn pacifist_can_not_use_offensive_abilities() {
+        let mut app = test_app();
+        let pacifist = ClassStats {
+            can_fight: false,
+            ..default()
+        };
+        let caster = spawn_caster(&mut app, &["strike", "leap"], pacifist);
+        assert_eq!(
+            request(
+                &mut app,
+                caster,
+                AbilitySlot::Attack,
+                AbilityTarget::Direction(Vec3::X)
+            ),
+            Err(AbilityError::Pacifist)
+        );
+        assert_eq!(
+            request(
+                &mut app,
+                caster,
+                AbilitySlot::Utility,
+                AbilityTarget::Point(Vec3::X)
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn points_and_entities_out_of_range_are_refused() {
+        let mut app = test_app();
+        let caster = spawn_caster(&mut app, &["leap", "mark"], ClassStats::default());
+        let near = app
+            .world
+            .spawn(GlobalTransform::from_translation(Vec3::new(3., 0., 0.)))
+            .id();
+        let far = app
+            .world
+            .spawn(GlobalTransform::from_translation(Vec3::new(10., 0., 0.)))
+            .id();
+
+        let point = |x| AbilityTarget::Point(Vec3::new(x, 0., 0.));
+        assert_eq!(
+            request(&mut app, caster, AbilitySlot::Utility, point(10.)),
+            Err(AbilityError::Range)
+        );
+        assert_eq!(
+            request(&mut app, caster, AbilitySlot::Utility, point(3.)),
+            Ok(())
+        );
+        assert_eq!(
+            request(
+                &mut app,
+                caster,
+                AbilitySlot::Act,
+                AbilityTarget::Entity(far)
+            ),
+            Err(AbilityError::Range)
+        );
+        assert_eq!(
+            request(
+                &mut app,
+                caster,
+                AbilitySlot::Act,
+                AbilityTarget::Entity(near)
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn wrong_target_is_refused() {
+        let mut app = test_app();
+        let caster = spawn_caster(&mut app, &["strike", "mark"], ClassStats::default());
+        let gone = app.world.spawn_empty().id();
+        app.world.despawn(gone);
+
+        assert_eq!(
+            request(&mut app, caster, AbilitySlot::Attack, AbilityTarget::Myself),
+            Err(AbilityError::Target)
+        );
+        assert_eq!(
+            request(
+                &mut app,
+                caster,
+                AbilitySlot::Act,
+                AbilityTarget::Entity(gone)
+            ),
+            Err(AbilityError::Target)
+        );
+    }
+
+    #[test]
+    fn one_cast_at_a_time_within_a_frame() {
+        let mut app = test_app();
+        let caster = spawn_caster(&mut app, &["strike", "leap"], ClassStats::default());
+
+        send(
+            &mut app,
+            caster,
+            AbilitySlot::Attack,
+            AbilityTarget::Direction(Vec3::X),
+        );
+        send(
+            &mut app,
+            caster,
+            AbilitySlot::Utility,
+            AbilityTarget::Point(Vec3::X),
+        );
+        assert_eq!(refusals(&mut app), vec![AbilityError::Casting]);
+        assert_eq!(
+            app.world.get::<Casting>(caster).unwrap().slot,
+            AbilitySlot::Attack
+        );
+
+        // and across frames
+        assert_eq!(
+            request(
+                &mut app,
+                caster,
+                AbilitySlot::Utility,
+                AbilityTarget::Point(Vec3::X)
+            ),
+            Err(AbilityError::Casting)
+        );
+    }
+}
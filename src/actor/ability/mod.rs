@@ -0,0 +1,7 @@
+#![allow(clippy::module_inception)]
+
+mod ability;
+mod rune;
+
+pub use ability::*;
+pub use rune::*;
@@ -0,0 +1,516 @@
+use std::{collections::BTreeMap, fs::File, path::Path};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages},
+};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actor::Actor,
+    component::{Damage, DamageEvent},
+    lobby::{host::DespawnActorEvent, Character, Lobby, LobbyState},
+    util::gesture::{normalize_strokes, PointCloud},
+    world::{CursorWorld, LinkId, MainCamera, Me},
+    ASSET_DIR,
+};
+
+use super::{
+    Abilities, AbilityActivated, AbilityDefinitions, AbilityRequest, AbilitySeq, AbilitySlot,
+    AbilityTarget, Targeting,
+};
+
+/// File in the asset directory the rune templates are read from.
+pub const RUNES_FILE: &str = "runes.yaml";
+
+/// Height of the decal above the ground, keeps it out of the floor.
+const DECAL_OFFSET: f32 = 0.02;
+
+/// Tuning of the rune drawing.
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct RuneSettings {
+    /// Shortest distance between two recorded points of the stroke.
+    pub min_spacing: f32,
+    /// Fewer recorded points are not a drawing.
+    pub min_points: usize,
+    /// Lowest score a drawing is recognized with.
+    pub threshold: f32,
+    /// Width of the decal on the ground.
+    pub decal_size: f32,
+    /// Window with the stroke and the scores of the last drawing.
+    pub debug_overlay: bool,
+}
+
+impl Default for RuneSettings {
+    fn default() -> Self {
+        Self {
+            min_spacing: 0.1,
+            min_points: 8,
+            threshold: 0.6,
+            decal_size: 2.,
+            debug_overlay: false,
+        }
+    }
+}
+
+/// What an inscribed rune does.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RuneEffect {
+    /// Lights the place up.
+    Light { range: f32, intensity: f32 },
+    /// Hurts the first character other than the caster that steps in, then fades.
+    Trap { radius: f32, damage: Damage },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuneTemplate {
+    pub name: String,
+    pub effect: RuneEffect,
+    /// Seconds the decal stays.
+    #[serde(default = "default_lifetime")]
+    pub lifetime: f32,
+    #[serde(default = "default_color")]
+    pub color: Color,
+    /// Strokes of the rune, `y` up.
+    pub strokes: Vec<Vec<Vec2>>,
+}
+
+fn default_lifetime() -> f32 {
+    30.
+}
+
+fn default_color() -> Color {
+    Color::rgb(0.4, 0.8, 1.)
+}
+
+/// A rune template ready for matching.
+#[derive(Debug, Clone)]
+pub struct Rune {
+    pub template: RuneTemplate,
+    pub cloud: PointCloud,
+}
+
+/// Templates of every rune by their id, read from the [`RUNES_FILE`].
+#[derive(Resource, Debug, Clone, Default)]
+pub struct RuneLibrary(BTreeMap<String, Rune>);
+
+impl RuneLibrary {
+    pub fn new(templates: BTreeMap<String, RuneTemplate>) -> Self {
+        Self(
+            templates
+                .into_iter()
+                .filter_map(|(id, template)| {
+                    let Some(cloud) = PointCloud::new(&template.strokes) else {
+                        log::warn!("rune {:?} has no strokes", id);
+                        return None;
+                    };
+                    Some((id, Rune { template, cloud }))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Rune> {
+        self.0.get(id)
+    }
+
+    /// Scores of every rune for the strokes, the best first.
+    pub fn recognize(&self, strokes: &[Vec<Vec2>]) -> Vec<(String, f32)> {
+        let Some(cloud) = PointCloud::new(strokes) else {
+            return Vec::new();
+        };
+        let mut scores: Vec<(String, f32)> = self
+            .0
+            .iter()
+            .map(|(id, rune)| (id.clone(), cloud.score(&rune.cloud)))
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores
+    }
+}
+
+/// Stroke this peer is drawing.
+#[derive(Resource, Debug, Default)]
+pub struct RuneStroke {
+    /// Points on the ground, in the order they were drawn.
+    pub points: Vec<Vec3>,
+    /// The last finished stroke seen from the camera.
+    pub last: Vec<Vec2>,
+    /// Scores of the last finished stroke, the best first.
+    pub scores: Vec<(String, f32)>,
+}
+
+/// An inscribed rune.
+#[derive(Component, Debug)]
+pub struct RuneDecal {
+    pub rune: String,
+    pub caster: Entity,
+    pub lifetime: Timer,
+}
+
+#[derive(Component, Debug)]
+pub struct RuneTrap {
+    pub radius: f32,
+    pub damage: Damage,
+}
+
+/// Inscribing the runes of the Runic Sorcerer.
+pub struct RunePlugins;
+
+impl Plugin for RunePlugins {
+    fn build(&self, app: &mut App) {
+        app.register_type::<RuneSettings>()
+            .init_resource::<RuneSettings>()
+            .init_resource::<RuneStroke>()
+            .insert_resource(load_runes(Path::new(ASSET_DIR).join(RUNES_FILE)))
+            .add_systems(
+                Update,
+                (
+                    draw_rune,
+                    inscribe_runes,
+                    // the host springs the traps and despawns them on every peer
+                    trigger_traps.run_if(not(in_state(LobbyState::Client))),
+                    fade_runes,
+                    rune_debug_overlay.run_if(|settings: Res<RuneSettings>| settings.debug_overlay),
+                )
+                    .run_if(not(in_state(LobbyState::None))),
+            );
+    }
+}
+
+fn load_runes(path: impl AsRef<Path>) -> RuneLibrary {
+    let path = path.as_ref();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            log::error!("Failed to open rune templates ({:?}): {}", path, err);
+            return RuneLibrary::default();
+        }
+    };
+    match serde_yaml::from_reader(file) {
+        Ok(templates) => RuneLibrary::new(templates),
+        Err(err) => {
+            log::error!("Failed to read rune templates ({:?}): {}", path, err);
+            RuneLibrary::default()
+        }
+    }
+}
+
+/// Records the cursor on the ground while the rune input is held, asks for the
+/// recognized rune on release.
+#[allow(clippy::too_many_arguments)]
+fn draw_rune(
+    me_query: Query<(Entity, &Character, &Abilities), With<Me>>,
+    camera_query: Query<&GlobalTransform, With<MainCamera>>,
+    lobby: Option<Res<Lobby>>,
+    definitions: Res<AbilityDefinitions>,
+    library: Res<RuneLibrary>,
+    settings: Res<RuneSettings>,
+    cursor: Res<CursorWorld>,
+    mut stroke: ResMut<RuneStroke>,
+    mut seq: ResMut<AbilitySeq>,
+    mut request_events: EventWriter<AbilityRequest>,
+) {
+    let (Some(lobby), Ok((caster, character, abilities))) = (lobby, me_query.get_single()) else {
+        return;
+    };
+    let Some(slot) = AbilitySlot::ALL.into_iter().find(|slot| {
+        abilities
+            .get(*slot)
+            .and_then(|state| definitions.get(&state.id))
+            .is_some_and(|definition| matches!(definition.targeting, Targeting::Rune { .. }))
+    }) else {
+        return;
+    };
+    let Some(inputs) = lobby.inputs_of(&character.id, true) else {
+        return;
+    };
+
+    if inputs.get_pressed(slot.action()).unwrap_or(false) {
+        if let Some(point) = cursor.point {
+            let far_enough = stroke
+                .points
+                .last()
+                .map_or(true, |last| last.distance(point) >= settings.min_spacing);
+            if far_enough {
+                stroke.points.push(point);
+            }
+        }
+        return;
+    }
+    if stroke.points.is_empty() {
+        return;
+    }
+
+    let points = std::mem::take(&mut stroke.points);
+    if points.len() < settings.min_points {
+        return;
+    }
+    // the drawing is read the way the player sees it
+    let forward = camera_query
+        .get_single()
+        .ok()
+        .and_then(|camera| camera.forward().reject_from(Vec3::Y).try_normalize())
+        .unwrap_or(Vec3::NEG_Z);
+    let right = forward.cross(Vec3::Y);
+    stroke.last = points
+        .iter()
+        .map(|point| Vec2::new(point.dot(right), point.dot(forward)))
+        .collect();
+    stroke.scores = library.recognize(&[stroke.last.clone()]);
+
+    let Some((rune, score)) = stroke.scores.first().cloned() else {
+        return;
+    };
+    if score < settings.threshold {
+        log::debug!("no rune recognized, closest {:?} ({:.2})", rune, score);
+        return;
+    }
+    log::info!("rune {:?} recognized ({:.2})", rune, score);
+    let point = points.iter().sum::<Vec3>() / points.len() as f32;
+    request_events.send(AbilityRequest {
+        caster,
+        slot,
+        target: AbilityTarget::Rune { point, rune },
+        seq: seq.next(),
+        time: None,
+    });
+}
+
+/// Spawns the decals of the activated runes.
+fn inscribe_runes(
+    mut commands: Commands,
+    mut activated_events: EventReader<AbilityActivated>,
+    character_query: Query<&Character>,
+    library: Res<RuneLibrary>,
+    settings: Res<RuneSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for activated in activated_events.read() {
+        let AbilityTarget::Rune { point, rune: id } = &activated.target else {
+            continue;
+        };
+        let Some(rune) = library.get(id) else {
+            log::warn!("unknown rune {:?}", id);
+            continue;
+        };
+        let template = &rune.template;
+
+        let mut decal = commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(decal_mesh(&template.strokes, settings.decal_size)),
+                material: materials.add(StandardMaterial {
+                    base_color: template.color,
+                    emissive: template.color,
+                    unlit: true,
+                    ..default()
+                }),
+                transform: Transform::from_translation(*point + Vec3::Y * DECAL_OFFSET),
+                ..default()
+            },
+            RuneDecal {
+                rune: id.clone(),
+                caster: activated.caster,
+                lifetime: Timer::from_seconds(template.lifetime, TimerMode::Once),
+            },
+            Actor,
+            Name::new(format!("Rune:{}", id)),
+        ));
+        // every peer inscribes its own copy, the seq of the activation is the same on all of them
+        if let Ok(character) = character_query.get(activated.caster) {
+            decal.insert(LinkId::Rune(character.id, activated.seq));
+        }
+        match template.effect {
+            RuneEffect::Light { range, intensity } => {
+                decal.with_children(|parent| {
+                    parent.spawn(PointLightBundle {
+                        point_light: PointLight {
+                            color: template.color,
+                            intensity,
+                            range,
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(0., 1., 0.),
+                        ..default()
+                    });
+                });
+            }
+            RuneEffect::Trap { radius, ref damage } => {
+                decal.insert(RuneTrap {
+                    radius,
+                    damage: damage.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Lines of the rune flat on the ground.
+fn decal_mesh(strokes: &[Vec<Vec2>], size: f32) -> Mesh {
+    let positions: Vec<Vec3> = normalize_strokes(strokes)
+        .iter()
+        .flat_map(|stroke| stroke.windows(2))
+        .flat_map(|pair| [pair[0], pair[1]])
+        .map(|point| Vec3::new(point.x, 0., -point.y) * size)
+        .collect();
+    let normals = vec![Vec3::Y; positions.len()];
+    Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+}
+
+#[allow(clippy::type_complexity)]
+fn trigger_traps(
+    mut commands: Commands,
+    trap_query: Query<(
+        Entity,
+        &GlobalTransform,
+        &RuneTrap,
+        &RuneDecal,
+        Option<&LinkId>,
+    )>,
+    actor_query: Query<(Entity, &GlobalTransform), With<Character>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut despawn_actor_events: EventWriter<DespawnActorEvent>,
+) {
+    for (rune, transform, trap, decal, link_id) in trap_query.iter() {
+        let Some((actor, _)) = actor_query.iter().find(|(actor, actor_transform)| {
+            *actor != decal.caster
+                && actor_transform
+                    .translation()
+                    .distance(transform.translation())
+                    <= trap.radius
+        }) else {
+            continue;
+        };
+        log::info!("rune {:?} triggered by {:?}", decal.rune, actor);
+        damage_events.send(DamageEvent::new(actor, Some(decal.caster), &trap.damage));
+        if let Some(link_id) = link_id {
+            despawn_actor_events.send(DespawnActorEvent(link_id.clone()));
+        }
+        commands.entity(rune).despawn_recursive();
+    }
+}
+
+fn fade_runes(
+    mut commands: Commands,
+    mut decal_query: Query<(Entity, &mut RuneDecal)>,
+    time: Res<Time>,
+) {
+    for (entity, mut decal) in decal_query.iter_mut() {
+        if decal.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Shows the last stroke and how well every rune matched it.
+fn rune_debug_overlay(
+    mut context: EguiContexts,
+    stroke: Res<RuneStroke>,
+    settings: Res<RuneSettings>,
+) {
+    egui::Window::new("Runes").show(context.ctx_mut(), |ui| {
+        let (response, painter) = ui.allocate_painter(egui::vec2(160., 160.), egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_stroke(rect, 0., (1., egui::Color32::DARK_GRAY));
+        if let Some(stroke) = normalize_strokes(&[stroke.last.clone()]).first() {
+            let points: Vec<egui::Pos2> = stroke
+                .iter()
+                .map(|point| rect.center() + egui::vec2(point.x, -point.y) * rect.width() * 0.9)
+                .collect();
+            painter.add(egui::Shape::line(points, (2., egui::Color32::LIGHT_BLUE)));
+        }
+
+        for (rune, score) in stroke.scores.iter() {
+            let color = if *score >= settings.threshold {
+                egui::Color32::LIGHT_GREEN
+            } else {
+                egui::Color32::GRAY
+            };
+            ui.colored_label(color, format!("{:<12} {:.2}", rune, score));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory in the asset directory with recorded drawings, one file per rune.
+    const FIXTURES_DIR: &str = "runes/fixtures";
+
+    #[derive(Deserialize)]
+    struct Recording {
+        strokes: Vec<Vec<Vec2>>,
+    }
+
+    fn library() -> RuneLibrary {
+        let library = load_runes(Path::new(ASSET_DIR).join(RUNES_FILE));
+        assert!(!library.0.is_empty(), "rune templates did not load");
+        library
+    }
+
+    fn recordings(rune: &str) -> Vec<Recording> {
+        let path = Path::new(ASSET_DIR)
+            .join(FIXTURES_DIR)
+            .join(format!("{}.yaml", rune));
+        let file = File::open(&path).unwrap_or_else(|err| panic!("{:?}: {}", path, err));
+        let recordings: Vec<Recording> = serde_yaml::from_reader(file).unwrap();
+        assert!(!recordings.is_empty(), "{:?} has no recordings", path);
+        recordings
+    }
+
+    fn assert_recognized(rune: &str) {
+        let library = library();
+        let threshold = RuneSettings::default().threshold;
+        for (index, recording) in recordings(rune).iter().enumerate() {
+            let scores = library.recognize(&recording.strokes);
+            assert_eq!(scores.len(), library.0.len());
+            let (best, score) = &scores[0];
+            assert_eq!(best, rune, "recording {} of {}: {:?}", index, rune, scores);
+            assert!(
+                *score >= threshold,
+                "recording {} of {}: {:?}",
+                index,
+                rune,
+                scores
+            );
+            for (other, score) in scores.iter().filter(|(id, _)| id != rune) {
+                assert!(
+                    *score < threshold,
+                    "recording {} of {} matches {}: {}",
+                    index,
+                    rune,
+                    other,
+                    score
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_rune_has_fixtures() {
+        for id in library().0.keys() {
+            recordings(id);
+        }
+    }
+
+    #[test]
+    fn recognizes_kenaz() {
+        assert_recognized("kenaz");
+    }
+
+    #[test]
+    fn recognizes_sowilo() {
+        assert_recognized("sowilo");
+    }
+
+    #[test]
+    fn recognizes_thurisaz() {
+        assert_recognized("thurisaz");
+    }
+}
@@ -21,7 +21,11 @@ use {
     std::any::type_name,
 };
 
-use super::TracePlugins;
+use super::{
+    ability::{AbilityPlugins, RunePlugins},
+    character::CharacterPlugins,
+    MeleePlugins, ProjectilePlugins, StealthPlugins, TracePlugins, TrueSightPlugins,
+};
 
 #[derive(Default, Component)]
 pub struct Actor;
@@ -39,7 +43,16 @@ impl Plugin for ActorPlugins {
         #[cfg(feature = "temp-container")]
         app.add_systems(Startup, setup);
         app.add_event::<UnloadActorsEvent>()
-            .add_plugins(TracePlugins)
+            .add_plugins((
+                TracePlugins,
+                ProjectilePlugins,
+                MeleePlugins,
+                StealthPlugins,
+                TrueSightPlugins,
+                CharacterPlugins,
+                AbilityPlugins,
+                RunePlugins,
+            ))
             .add_systems(Update, unload_actors);
     }
 }
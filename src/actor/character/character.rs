@@ -1,9 +1,13 @@
 
 
-use crate::component::{AxisName, DespawnReason, NoclipDuration, Respawn};
+use crate::actor::ability::{Abilities, AbilityDefinitions, Energy};
+use crate::actor::Exposure;
+use crate::component::{AxisName, DespawnReason, Health, NoclipDuration, Resistances, Respawn};
 use crate::extend_commands;
+use crate::level::OUT_OF_WORLD_HEIGHT;
 use crate::lobby::Character;
-use crate::lobby::{LobbyState, PlayerId, PlayerView};
+use crate::lobby::{LobbyState, PlayerId, PlayerView, ViewMode};
+use crate::world::CollisionLayer;
 use crate::world::MainCamera;
 use crate::world::Me;
 use crate::world::SpawnProperty;
@@ -11,50 +15,46 @@ use bevy::{ecs::system::EntityCommands, prelude::*};
 
 use serde::{Deserialize, Serialize};
 
-//pub const PLAYER_MOVE_SPEED: f32 = 0.07;
+use super::{
+    character_physics, CameraOcclusionPlugins, CharacterClass, CharacterClassPlugins,
+    CharacterClasses, CharacterControllerPlugins, TopDownCameraPlugins,
+};
+
 pub const PLAYER_SIZE: f32 = 2.;
 pub const HALPH_PLAYER_SIZE: f32 = PLAYER_SIZE / 2.;
-//const SHIFT_ACCELERATION: f32 = 2.0;
 //const SENSITIVITY: f32 = 0.5;
-//const JUMP_HEIGHT_MULTIPLICATOR: f32 = 1.1;
 
-const DEFAULT_CAMERA_DISTANCE: f32 = 20.;
+pub const DEFAULT_CAMERA_DISTANCE: f32 = 20.;
 
 #[derive(Component, Debug, Serialize, Deserialize)]
 pub struct TiedCamera(Entity);
 
-//#[derive(Component, Debug)]
-//struct JumpHelper {
-//    last_viable_normal: Vec3,
-//}
-
 pub struct CharacterPlugins;
 
 impl Plugin for CharacterPlugins {
     fn build(&self, app: &mut App) {
-        app
-            .add_systems(
-                FixedUpdate,
-                move_characters/*, update_jump_normals*/.run_if(
-                    not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
-                ),
-            )
-            .add_systems(
-                Update,
-                /*jump, */rotate_camera.run_if(
-                    not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
-                ),
-            )
-            //.add_systems(
-            //    Last,
-            //    fire.after(server_update_system).run_if(
-            //        not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
-            //    ),
-            //)
-            .add_systems(
-                PostUpdate,
-                tied_camera_follow.run_if(not(in_state(LobbyState::None))),
-            );
+        app.add_plugins((
+            CharacterClassPlugins,
+            CharacterControllerPlugins,
+            TopDownCameraPlugins,
+            CameraOcclusionPlugins,
+        ))
+        .add_systems(
+            Update,
+            rotate_camera.run_if(
+                not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+            ),
+        )
+        //.add_systems(
+        //    Last,
+        //    fire.after(server_update_system).run_if(
+        //        not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+        //    ),
+        //)
+        .add_systems(
+            PostUpdate,
+            tied_camera_follow.run_if(not(in_state(LobbyState::None))),
+        );
     }
 }
 
@@ -66,8 +66,13 @@ fn tied_camera_follow(
 ) {
     for (TiedCamera(target), children, mut transform) in tied_camera_query.iter_mut() {
         if let Ok(target_transform) = transform_query.get(*target) {
+            let view = view_direction_query.get_single().ok();
+            // the top-down rig moves the camera in its own mode
+            if view.is_some_and(|view| view.mode != ViewMode::Orbit) {
+                continue;
+            }
             transform.translation = target_transform.translation + Vec3::Y * 2.;
-            if let Ok(view) = view_direction_query.get_single() {
+            if let Some(view) = view {
                 transform.rotation = view.direction;
                 if let Some(child) = children.iter().next() {
                     if let Ok(mut camera_transform) = camera_query.get_mut(*child) {
@@ -84,100 +89,18 @@ fn tied_camera_follow(
     }
 }
 
-// TODO jump for rapiera
-//fn update_jump_normals(
-//    mut query: Query<(&mut JumpHelper, Entity, &GlobalTransform)>,
-//    collisions: Res<Collisions>,
-//) {
-//    let mut direction_vector = Vec3::ZERO;
-//    for (mut jump_direction, player_entity, transform) in query.iter_mut() {
-//        for collision in collisions.collisions_with_entity(player_entity) {
-//            for manifold in collision.manifolds.iter() {
-//                for contact in manifold.contacts.iter() {
-//                    direction_vector += transform.translation() - contact.point1;
-//                }
-//            }
-//        }
-//        jump_direction.last_viable_normal = direction_vector.normalize_or_zero();
-//    }
-//}
-
-//fn jump(
-//    mut query: Query<(
-//        &mut LinearVelocity,
-//        &PlayerView,
-//        &mut PlayerInputs,
-//        Entity,
-//        &JumpHelper,
-//    )>, /* , time: Res<Time> */
-//    gravity: Res<Gravity>,
-//    collisions: Res<Collisions>,
-//) {
-//    for (mut linear_velocity, view_direction, mut player_inputs, player_entity, jump_direction) in
-//        query.iter_mut()
-//    {
-//        let jumped = player_inputs.is_input_changed_to_true_and_set_to_false(InputType::Jump);
-//
-//        let dx = (player_inputs.get().right as i8 - player_inputs.get().left as i8) as f32;
-//        let dy = (player_inputs.get().down as i8 - player_inputs.get().up as i8) as f32;
-//
-//        let local_x = view_direction.direction.mul_vec3(Vec3::X);
-//        let local_y = view_direction.direction.mul_vec3(Vec3::Z);
-//
-//        if jumped
-//            && collisions
-//                .collisions_with_entity(player_entity)
-//                .next()
-//                .is_some()
-//        {
-//            **linear_velocity += ((jump_direction.last_viable_normal + local_x * dx + local_y * dy)
-//                    .normalize_or_zero())
-//                    * (-gravity.0.y * 2.0 * PLAYER_SIZE).sqrt() // sqrt(2gh)
-//                    * JUMP_HEIGHT_MULTIPLICATOR;
-//            log::debug!("{:?}", jump_direction.last_viable_normal);
-//        }
-//    }
-//}
-
-fn move_characters(// mut query: Query<(&mut Velocity, &PlayerView, &PlayerInputs)>, /* , time: Res<Time> */
-) {
-    // TODO:
-    //for (mut velocity, view_direction, input) in query.iter_mut() {
-    //    let input = input.get();
-    //    let dx = (input.right as i8 - input.left as i8) as f32;
-    //    let dy = (input.down as i8 - input.up as i8) as f32;
-
-    //    // convert axises to global
-    //    let view_direction_x = view_direction.direction.mul_vec3(Vec3::X);
-    //    let view_direction_y = view_direction.direction.mul_vec3(Vec3::Z);
-
-    //    // never use delta time in fixed update !!!
-    //    let shift_acceleration = SHIFT_ACCELERATION.powf(input.sprint as i32 as f32);
-
-    //    // move by x axis
-    //    velocity.linvel.x += dx * PLAYER_MOVE_SPEED * view_direction_x.x * shift_acceleration;
-    //    velocity.linvel.z += dx * PLAYER_MOVE_SPEED * view_direction_x.z * shift_acceleration;
-
-    //    // move by y axis
-    //    velocity.linvel.x += dy * PLAYER_MOVE_SPEED * view_direction_y.x * shift_acceleration;
-    //    velocity.linvel.z += dy * PLAYER_MOVE_SPEED * view_direction_y.z * shift_acceleration;
-    //}
-}
-
 #[allow(clippy::type_complexity)]
 fn rotate_camera(// TODO:
     //mut query: Query<(
     //    &mut PlayerView,
     //    &Transform,
     //    &PlayerInputs,
-    //    //Option<&mut RayCaster>,
-    //    //Option<&RayHits>,
     //)>,
     //time: Res<Time>,
 ) {
     // TODO:
     //let delta_seconds = time.delta_seconds();
-    //for (mut view, transform, input) in query.iter_mut() {
+    //for (mut view, input) in query.iter_mut() {
     //    let input = input.get();
 
     //    // camera turn
@@ -188,39 +111,15 @@ fn rotate_camera(// TODO:
     //    let rotation = Quat::from_rotation_x(input.turn_vertical * SENSITIVITY * delta_seconds);
     //    // local rotation (!ORDER OF MULTIPLICATION MATTERS!)
     //    view.direction *= rotation;
-
-    //    view.distance = DEFAULT_CAMERA_DISTANCE;
-
-    //    // TODO:
-    //    // let ray_pos = Vec3::new(1.0, 2.0, 3.0);
-    //    // let ray_dir = Vec3::new(0.0, 1.0, 0.0);
-    //    // let max_toi = 4.0;
-    //    // let solid = true;
-    //    // let filter = QueryFilter::default();
-    //    // //rapier_context.cast_ray(ray_pos, ray_dir, max_toi, solid, filter)
-    //    //
-    //    // if let (Some(hits), Some(mut ray)) = (hits, ray) {
-    //    //     let h = transform.rotation.conjugate();
-    //    //     let start_point = h.mul_vec3(Vec3::Y * 2.);
-    //    //     let offset = h
-    //    //         .mul_vec3(view.direction.mul_vec3(DEFAULT_CAMERA_DISTANCE * Vec3::Z))
-    //    //         // need to normalize ray.direction to time_of_impact work correctly
-    //    //         .normalize();
-    //    //     ray.origin = start_point;
-    //    //     ray.direction = offset;
-    //    //
-    //    //     if let Some(firs_hit) = hits.iter_sorted().next() {
-    //    //         if firs_hit.time_of_impact < DEFAULT_CAMERA_DISTANCE {
-    //    //             view.distance = firs_hit.time_of_impact;
-    //    //         }
-    //    //     }
-    //    // }
     //}
 }
 
 extend_commands!(
-  spawn_character(player_id: PlayerId, color: Color, spawn_point: Vec3),
-  |world: &mut World, entity_id: Entity, player_id: PlayerId, color: Color, spawn_point: Vec3| {
+  spawn_character(player_id: PlayerId, color: Color, spawn: Transform, class: CharacterClass),
+  |world: &mut World, entity_id: Entity, player_id: PlayerId, color: Color, spawn: Transform, class: CharacterClass| {
+
+    let definition = world.resource::<CharacterClasses>().get(class).clone();
+    let abilities = Abilities::new(&definition.abilities, world.resource::<AbilityDefinitions>());
 
     let mesh = world
       .resource_mut::<Assets<Mesh>>()
@@ -230,49 +129,52 @@ extend_commands!(
       .resource_mut::<Assets<StandardMaterial>>()
       .add(color);
 
-      // some raycast magic
-    let _start_point = Vec3::Y * 2.;
-    let _offset = Vec3::new(0., 0., DEFAULT_CAMERA_DISTANCE);
-
     world
         .entity_mut(entity_id)
         .insert((
             PbrBundle {
             mesh,
             material,
+            transform: spawn,
             ..Default::default()
             },
-            // TODO: RayCaster::new(start_point, offset),
-            // TODO: JumpHelper{last_viable_normal: Vec3::Y},
-            Respawn::new((
-                DespawnReason::More(200., AxisName::Y),
-                DespawnReason::Less(-10., AxisName::Y),
-                DespawnReason::More(100., AxisName::X),
-                DespawnReason::Less(-100., AxisName::X),
-                DespawnReason::More(100., AxisName::Z),
-                DespawnReason::Less(-100., AxisName::Z)
+            character_physics(HALPH_PLAYER_SIZE),
+            CollisionLayer::actor(),
+            // every level has a kill zone, authored or the fallback floor, the axis bound only
+            // catches what falls past it
+            Respawn::new(
+                DespawnReason::Less(OUT_OF_WORLD_HEIGHT, AxisName::Y),
+                SpawnProperty::from_transform(spawn),
+                NoclipDuration::Timer(10.),
             ),
-            SpawnProperty::new(spawn_point),
-            NoclipDuration::Timer(10.)),
             // TODO: PlayerInputs::default(),
             Character { id: player_id },
-            PlayerView::new(Quat::default(), 325_f32.sqrt()),
+            class,
+            abilities,
+            Energy::new(definition.stats.max_energy, definition.stats.energy_regen),
+            Health::new(definition.stats.max_health),
+            Resistances(definition.resistances.into_iter().collect()),
+            Exposure::default(),
+            definition.stats,
+            PlayerView {
+                mode: definition.camera.mode,
+                ..PlayerView::new(Quat::default(), 325_f32.sqrt())
+            },
             Name::new(format!("Character:{:#?}", player_id)),
             // PhysicsOptimalTrace::new(0.5, 0.05, color, PLAYER_SIZE / 2.),
         ))
-        // TODO:
-        //.insert((
-        //    Friction::new(0.4),
-        //    PhysicsBundle::from_rigid_body(RigidBody::Dynamic),
-        //    //TODO colider
-        //))
         ;
+    insert_class_model(world, entity_id, class);
   }
 );
 
 extend_commands!(
-  spawn_character_shell(player_id: PlayerId, color: Color, spawn_point: Vec3),
-  |world: &mut World, entity_id: Entity, player_id: PlayerId, color: Color, spawn_point: Vec3| {
+  spawn_character_shell(player_id: PlayerId, color: Color, spawn_point: Vec3, class: CharacterClass),
+  |world: &mut World, entity_id: Entity, player_id: PlayerId, color: Color, spawn_point: Vec3, class: CharacterClass| {
+
+    let definition = world.resource::<CharacterClasses>().get(class).clone();
+    // the character of this peer predicts its abilities
+    let abilities = Abilities::new(&definition.abilities, world.resource::<AbilityDefinitions>());
 
     let mesh = world
       .resource_mut::<Assets<Mesh>>()
@@ -297,11 +199,42 @@ extend_commands!(
        },
         // TransformOptimalTrace::new(0.5, 0.05, color, PLAYER_SIZE / 2.),
         // TODO: PlayerInputs::default(),
+        Character { id: player_id },
         Name::new(format!("Character:{:#?}", player_id)),
-        PlayerView::new(Quat::default(), 325_f32.sqrt())));
+        class,
+        abilities,
+        Energy::new(definition.stats.max_energy, definition.stats.energy_regen),
+        // followed from the host
+        Health::new(definition.stats.max_health),
+        Exposure::default(),
+        PlayerView {
+            mode: definition.camera.mode,
+            ..PlayerView::new(Quat::default(), 325_f32.sqrt())
+        }));
+    insert_class_model(world, entity_id, class);
   }
 );
 
+/// Replaces the cube of a character with the model of its class.
+fn insert_class_model(world: &mut World, entity: Entity, class: CharacterClass) {
+    let Some(scene) = world
+        .resource::<CharacterClasses>()
+        .get(class)
+        .model
+        .scene
+        .clone()
+    else {
+        return;
+    };
+    let scene = world.resource::<AssetServer>().load(scene);
+    world
+        .entity_mut(entity)
+        .remove::<Handle<Mesh>>()
+        .with_children(|parent| {
+            parent.spawn(SceneBundle { scene, ..default() });
+        });
+}
+
 extend_commands!(
   spawn_tied_camera(target: Entity),
   |world: &mut World, entity_id: Entity, target: Entity| {
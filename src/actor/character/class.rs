@@ -0,0 +1,171 @@
+use std::{collections::HashMap, fs::File, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::{component::DamageType, lobby::ViewMode, ASSET_DIR};
+
+/// File in the asset directory the class definitions are read from.
+pub const CLASSES_FILE: &str = "classes.yaml";
+
+/// Playable character, each one with its own core mechanic.
+#[derive(
+    Component,
+    Reflect,
+    Serialize,
+    Deserialize,
+    EnumIter,
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+)]
+#[reflect(Component)]
+pub enum CharacterClass {
+    /// Sneaks, runs and maps the dungeon.
+    #[default]
+    Researcher,
+    /// Inscribes runes.
+    RunicSorcerer,
+    /// Sees the invisible, can not fight.
+    ServantOfGod,
+    /// Short sword and shield.
+    Warrior,
+}
+
+impl CharacterClass {
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::iter().find(|class| class.to_byte() == byte)
+    }
+}
+
+/// Numbers a class changes, inserted on the character.
+#[derive(Component, Reflect, Serialize, Deserialize, Debug, Clone)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct ClassStats {
+    pub max_health: f32,
+    /// Multiplier of the walk speed.
+    pub speed: f32,
+    /// Multiplier of the jump height.
+    pub jump: f32,
+    pub can_fight: bool,
+    /// [`Energy`](crate::actor::ability::Energy) the abilities cost.
+    pub max_energy: f32,
+    pub energy_regen: f32,
+    /// Sees the [`HiddenToMortals`](crate::actor::HiddenToMortals).
+    pub true_sight: bool,
+}
+
+impl Default for ClassStats {
+    fn default() -> Self {
+        Self {
+            max_health: 100.,
+            speed: 1.,
+            jump: 1.,
+            can_fight: true,
+            max_energy: 100.,
+            energy_regen: 10.,
+            true_sight: false,
+        }
+    }
+}
+
+/// Look of a class.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ClassModel {
+    /// GLTF scene in the asset directory, a colored cube if unset.
+    pub scene: Option<String>,
+}
+
+/// Camera a class starts with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ClassCamera {
+    pub mode: ViewMode,
+    /// Height of the top-down camera, the [`TopDownCameraSettings`](super::TopDownCameraSettings) one if unset.
+    pub height: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ClassDefinition {
+    pub name: String,
+    pub stats: ClassStats,
+    /// Abilities the class starts with.
+    pub abilities: Vec<String>,
+    /// [`Resistances`](crate::component::Resistances) by the damage type.
+    pub resistances: HashMap<DamageType, f32>,
+    pub model: ClassModel,
+    pub camera: ClassCamera,
+}
+
+/// Definitions of every class, read from the [`CLASSES_FILE`].
+#[derive(Resource, Debug, Clone)]
+pub struct CharacterClasses(HashMap<CharacterClass, ClassDefinition>);
+
+impl Default for CharacterClasses {
+    fn default() -> Self {
+        Self(
+            CharacterClass::iter()
+                .map(|class| {
+                    let definition = ClassDefinition {
+                        name: format!("{:?}", class),
+                        ..default()
+                    };
+                    (class, definition)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl CharacterClasses {
+    pub fn get(&self, class: CharacterClass) -> &ClassDefinition {
+        // every class is filled in on load
+        &self.0[&class]
+    }
+}
+
+/// Class this peer plays, chosen before starting or joining a game.
+#[derive(Resource, Default, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct SelectedClass(pub CharacterClass);
+
+pub struct CharacterClassPlugins;
+
+impl Plugin for CharacterClassPlugins {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CharacterClass>()
+            .register_type::<ClassStats>()
+            .init_resource::<SelectedClass>()
+            .insert_resource(load_classes(Path::new(ASSET_DIR).join(CLASSES_FILE)));
+    }
+}
+
+/// Reads the class definitions, classes missing in the file keep the default one.
+fn load_classes(path: impl AsRef<Path>) -> CharacterClasses {
+    let path = path.as_ref();
+    let mut classes = CharacterClasses::default();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            log::error!("Failed to open class definitions ({:?}): {}", path, err);
+            return classes;
+        }
+    };
+    match serde_yaml::from_reader::<_, HashMap<CharacterClass, ClassDefinition>>(file) {
+        Ok(definitions) => classes.0.extend(definitions),
+        Err(err) => log::error!("Failed to read class definitions ({:?}): {}", path, err),
+    }
+    classes
+}
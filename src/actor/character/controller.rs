@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    actor::{Sneaking, StealthSettings},
+    component::EntityRespawned,
+    core::CoreAction,
+    lobby::{Character, Lobby, LobbyState, PlayerView},
+    world::Me,
+};
+
+use super::ClassStats;
+
+/// Tuning of the character movement.
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct CharacterControllerSettings {
+    pub walk_speed: f32,
+    pub sprint_multiplier: f32,
+    /// How fast the velocity reaches the wished one on the ground, per second.
+    pub acceleration: f32,
+    /// Share of the acceleration available in the air.
+    pub air_control: f32,
+    pub gravity: f32,
+    pub jump_height: f32,
+    /// Steepest slope in degrees the character walks up and jumps from.
+    pub max_slope: f32,
+    /// Highest step the character climbs without jumping.
+    pub step_height: f32,
+    /// Narrowest step the character climbs.
+    pub step_width: f32,
+    /// Distance the character is pulled down to stick to the ground.
+    pub snap_to_ground: f32,
+}
+
+impl Default for CharacterControllerSettings {
+    fn default() -> Self {
+        Self {
+            walk_speed: 6.,
+            sprint_multiplier: 1.8,
+            acceleration: 12.,
+            air_control: 0.3,
+            gravity: 20.,
+            jump_height: 2.2,
+            max_slope: 45.,
+            step_height: 0.5,
+            step_width: 0.2,
+            snap_to_ground: 0.3,
+        }
+    }
+}
+
+impl CharacterControllerSettings {
+    fn apply(&self, controller: &mut KinematicCharacterController) {
+        controller.max_slope_climb_angle = self.max_slope.to_radians();
+        controller.min_slope_slide_angle = self.max_slope.to_radians();
+        controller.autostep = Some(CharacterAutostep {
+            max_height: CharacterLength::Absolute(self.step_height),
+            min_width: CharacterLength::Absolute(self.step_width),
+            include_dynamic_bodies: false,
+        });
+        controller.snap_to_ground = Some(CharacterLength::Absolute(self.snap_to_ground));
+    }
+
+    fn jump_speed(&self) -> f32 {
+        // sqrt(2gh)
+        (2. * self.gravity * self.jump_height).sqrt()
+    }
+}
+
+/// Velocity of a kinematic character, integrated by the [`CharacterControllerPlugins`].
+#[derive(Component, Debug)]
+pub struct CharacterMotion {
+    pub velocity: Vec3,
+    pub grounded: bool,
+    /// Normal of the ground below, the character jumps along it.
+    pub ground_normal: Vec3,
+    /// Seconds the character stays frozen by a hit, see [`CharacterMotion::hit`].
+    pub hit_stop: f32,
+    /// Velocity added on the next movement.
+    pub impulse: Vec3,
+}
+
+impl CharacterMotion {
+    /// Freezes the character for `hit_stop` seconds, then pushes it by `impulse`.
+    pub fn hit(&mut self, impulse: Vec3, hit_stop: f32) {
+        self.impulse += impulse;
+        self.hit_stop = self.hit_stop.max(hit_stop);
+    }
+}
+
+impl Default for CharacterMotion {
+    fn default() -> Self {
+        Self {
+            velocity: Vec3::ZERO,
+            grounded: false,
+            ground_normal: Vec3::Y,
+            hit_stop: 0.,
+            impulse: Vec3::ZERO,
+        }
+    }
+}
+
+/// Components of a character moved by [`CharacterControllerPlugins`].
+pub fn character_physics(half_size: f32) -> impl Bundle {
+    (
+        RigidBody::KinematicPositionBased,
+        Collider::cuboid(half_size, half_size, half_size),
+        KinematicCharacterController {
+            offset: CharacterLength::Absolute(0.05),
+            ..default()
+        },
+        CharacterMotion::default(),
+    )
+}
+
+pub struct CharacterControllerPlugins;
+
+impl Plugin for CharacterControllerPlugins {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CharacterControllerSettings>()
+            .init_resource::<CharacterControllerSettings>()
+            .add_systems(
+                Update,
+                (apply_controller_settings, reset_motion, move_characters)
+                    .chain()
+                    .run_if(
+                        not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+                    ),
+            );
+    }
+}
+
+fn apply_controller_settings(
+    mut controller_query: Query<&mut KinematicCharacterController>,
+    settings: Res<CharacterControllerSettings>,
+) {
+    for mut controller in controller_query.iter_mut() {
+        if settings.is_changed() || controller.is_added() {
+            settings.apply(&mut controller);
+        }
+    }
+}
+
+fn reset_motion(
+    mut respawned_events: EventReader<EntityRespawned>,
+    mut motion_query: Query<&mut CharacterMotion>,
+) {
+    for event in respawned_events.read() {
+        if let Ok(mut motion) = motion_query.get_mut(event.entity) {
+            *motion = CharacterMotion::default();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn move_characters(
+    mut character_query: Query<(
+        &Character,
+        Has<Me>,
+        &PlayerView,
+        &mut KinematicCharacterController,
+        Option<&KinematicCharacterControllerOutput>,
+        &mut CharacterMotion,
+        Option<&CollisionGroups>,
+        Option<&ClassStats>,
+        Has<Sneaking>,
+    )>,
+    lobby: Option<Res<Lobby>>,
+    settings: Res<CharacterControllerSettings>,
+    stealth_settings: Res<StealthSettings>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    let min_ground_y = settings.max_slope.to_radians().cos();
+
+    for (character, me, view, mut controller, output, mut motion, groups, stats, sneaking) in
+        character_query.iter_mut()
+    {
+        let inputs = lobby
+            .as_ref()
+            .and_then(|lobby| lobby.inputs_of(&character.id, me));
+        let pressed = |action| {
+            inputs
+                .and_then(|inputs| inputs.get_pressed(action))
+                .unwrap_or(false)
+        };
+        let just_pressed = |action| {
+            inputs
+                .and_then(|inputs| inputs.get_just_pressed(action))
+                .unwrap_or(false)
+        };
+
+        // ground detection of the last physics step
+        motion.grounded = output.is_some_and(|output| output.grounded);
+        if let Some(output) = output {
+            let normal = output
+                .collisions
+                .iter()
+                .map(|collision| collision.toi.normal2)
+                .filter(|normal| normal.y >= min_ground_y)
+                .sum::<Vec3>();
+            motion.ground_normal = normal.try_normalize().unwrap_or(Vec3::Y);
+
+            // stop rising under a ceiling
+            if motion.velocity.y > 0.
+                && output.effective_translation.y < output.desired_translation.y * 0.5
+            {
+                motion.velocity.y = 0.;
+            }
+        }
+
+        // frozen by a hit, the movement resumes after
+        if motion.hit_stop > 0. {
+            motion.hit_stop = (motion.hit_stop - delta_seconds).max(0.);
+            controller.translation = None;
+            continue;
+        }
+
+        // movement relative to the view, flattened to the ground
+        let forward = (view.direction * Vec3::NEG_Z).reject_from(Vec3::Y);
+        let right = (view.direction * Vec3::X).reject_from(Vec3::Y);
+        let dx = pressed(CoreAction::MoveRight) as i8 - pressed(CoreAction::MoveLeft) as i8;
+        let dy = pressed(CoreAction::MoveForward) as i8 - pressed(CoreAction::MoveBack) as i8;
+        let stats = stats.cloned().unwrap_or_default();
+        let mut speed = settings.walk_speed * stats.speed;
+        if sneaking {
+            speed *= stealth_settings.sneak_factor;
+        } else if pressed(CoreAction::Sprint) {
+            speed *= settings.sprint_multiplier;
+        }
+        let wished = (right * dx as f32 + forward * dy as f32).normalize_or_zero() * speed;
+
+        let control = if motion.grounded {
+            1.
+        } else {
+            settings.air_control
+        };
+        let blend = (settings.acceleration * control * delta_seconds).min(1.);
+        let horizontal = motion.velocity.reject_from(Vec3::Y).lerp(wished, blend);
+        let mut vertical = motion.velocity.y;
+        if motion.grounded && vertical < 0. {
+            vertical = 0.;
+        }
+        vertical -= settings.gravity * delta_seconds;
+        motion.velocity = horizontal + Vec3::Y * vertical;
+
+        // knockback, the acceleration slows it down like any other velocity
+        if motion.impulse != Vec3::ZERO {
+            motion.velocity += motion.impulse;
+            motion.impulse = Vec3::ZERO;
+        }
+
+        if motion.grounded && just_pressed(CoreAction::Jump) {
+            motion.velocity.y = 0.;
+            // the speed grows with the square root of the height
+            motion.velocity += motion.ground_normal * settings.jump_speed() * stats.jump.sqrt();
+        }
+
+        // noclip changes the groups, the movement queries must follow
+        controller.filter_groups = groups.copied();
+        controller.translation = Some(motion.velocity * delta_seconds);
+    }
+}
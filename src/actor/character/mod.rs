@@ -1,4 +1,12 @@
 #![allow(clippy::module_inception)]
 
 mod character;
+mod class;
+mod controller;
+mod occlusion;
+mod top_down;
 pub use character::*;
+pub use class::*;
+pub use controller::*;
+pub use occlusion::*;
+pub use top_down::*;
@@ -0,0 +1,160 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    lobby::{LobbyState, PlayerView, ViewMode},
+    world::{CollisionLayer, MainCamera, Me},
+};
+
+use super::{DEFAULT_CAMERA_DISTANCE, HALPH_PLAYER_SIZE};
+
+/// Radius of the sphere cast for the orbit camera, keeps the near plane out of walls.
+const CAMERA_RADIUS: f32 = 0.4;
+/// Closest the orbit camera comes to the character.
+const MIN_CAMERA_DISTANCE: f32 = 1.;
+/// How fast the camera moves in front of a wall, per second.
+const PULL_IN_SPEED: f32 = 20.;
+/// How fast the camera moves back once the wall is gone, per second.
+const EASE_OUT_SPEED: f32 = 3.;
+
+/// Opacity of walls between the top-down camera and the character.
+const OCCLUDER_ALPHA: f32 = 0.25;
+const FADE_SPEED: f32 = 8.;
+
+/// A level mesh hiding the character, drawn with a translucent copy of its material.
+#[derive(Component)]
+struct Faded {
+    original: Handle<StandardMaterial>,
+    /// Share of the original opacity.
+    alpha: f32,
+}
+
+/// Keeps walls from hiding the character.
+pub struct CameraOcclusionPlugins;
+
+impl Plugin for CameraOcclusionPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (orbit_camera_collision, fade_occluders).run_if(not(in_state(LobbyState::None))),
+        );
+    }
+}
+
+/// Shortens the view distance when a wall is between the character of this peer and the
+/// orbit camera.
+fn orbit_camera_collision(
+    mut me_query: Query<(Entity, &Transform, &mut PlayerView), With<Me>>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let Ok((entity, transform, mut view)) = me_query.get_single_mut() else {
+        return;
+    };
+    if view.mode != ViewMode::Orbit {
+        return;
+    }
+    // same origin the tied camera turns around
+    let origin = transform.translation + Vec3::Y * 2.;
+    let direction = view.direction * Vec3::Z;
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .exclude_collider(entity)
+        .groups(CollisionLayer::scenery());
+
+    let wished = rapier_context
+        .cast_shape(
+            origin,
+            Quat::IDENTITY,
+            direction,
+            &Collider::ball(CAMERA_RADIUS),
+            DEFAULT_CAMERA_DISTANCE,
+            true,
+            filter,
+        )
+        .map_or(DEFAULT_CAMERA_DISTANCE, |(_, hit)| hit.toi)
+        .max(MIN_CAMERA_DISTANCE);
+
+    // in front of a wall at once, back out slowly
+    let speed = if wished < view.distance {
+        PULL_IN_SPEED
+    } else {
+        EASE_OUT_SPEED
+    };
+    let blend = 1. - (-speed * time.delta_seconds()).exp();
+    view.distance += (wished - view.distance) * blend;
+}
+
+/// Makes the walls between the top-down camera and the character of this peer translucent.
+#[allow(clippy::too_many_arguments)]
+fn fade_occluders(
+    mut commands: Commands,
+    me_query: Query<(&Transform, &PlayerView), With<Me>>,
+    camera_query: Query<&GlobalTransform, With<MainCamera>>,
+    mut faded_query: Query<(Entity, &mut Faded, &mut Handle<StandardMaterial>)>,
+    material_query: Query<&Handle<StandardMaterial>, Without<Faded>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let mut occluders = HashSet::new();
+    if let (Ok((transform, view)), Ok(camera)) = (me_query.get_single(), camera_query.get_single())
+    {
+        if view.mode == ViewMode::TopDown {
+            let origin = camera.translation();
+            let to_character = transform.translation - origin;
+            let filter = QueryFilter::new()
+                .exclude_sensors()
+                .groups(CollisionLayer::scenery());
+            rapier_context.intersections_with_ray(
+                origin,
+                to_character.normalize_or_zero(),
+                (to_character.length() - HALPH_PLAYER_SIZE).max(0.),
+                true,
+                filter,
+                |entity, _| {
+                    occluders.insert(entity);
+                    true
+                },
+            );
+        }
+    }
+
+    for &entity in occluders.iter() {
+        let Ok(handle) = material_query.get(entity) else {
+            continue;
+        };
+        // the material is shared with the meshes that do not hide anything
+        let Some(mut faded) = materials.get(handle).cloned() else {
+            continue;
+        };
+        faded.alpha_mode = AlphaMode::Blend;
+        let faded = materials.add(faded);
+        commands.entity(entity).insert((
+            Faded {
+                original: handle.clone(),
+                alpha: 1.,
+            },
+            faded,
+        ));
+    }
+
+    let blend = 1. - (-FADE_SPEED * time.delta_seconds()).exp();
+    for (entity, mut faded, mut handle) in faded_query.iter_mut() {
+        let occluding = occluders.contains(&entity);
+        let wished = if occluding { OCCLUDER_ALPHA } else { 1. };
+        faded.alpha += (wished - faded.alpha) * blend;
+
+        if !occluding && faded.alpha > 0.99 {
+            *handle = faded.original.clone();
+            commands.entity(entity).remove::<Faded>();
+            continue;
+        }
+        let original_alpha = materials
+            .get(&faded.original)
+            .map_or(1., |material| material.base_color.a());
+        if let Some(material) = materials.get_mut(&*handle) {
+            material.base_color.set_a(original_alpha * faded.alpha);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    core::CoreAction,
+    lobby::{Character, Lobby, LobbyState, PlayerView, ViewMode},
+    world::Me,
+};
+
+use super::{CharacterClass, CharacterClasses, TiedCamera};
+
+/// Scroll lines a touchpad pixel is worth.
+const PIXELS_PER_LINE: f32 = 40.;
+
+/// Tuning of the top-down camera.
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct TopDownCameraSettings {
+    /// Angle between the ground and the camera axis, in degrees.
+    pub pitch: f32,
+    /// Height above the character before zooming.
+    pub height: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Height change per scroll line.
+    pub zoom_step: f32,
+    /// How far the camera leads toward the cursor at the window edge.
+    pub lead: f32,
+    /// Distance the character moves without the camera following.
+    pub dead_zone: f32,
+    /// How fast the camera catches up, per second.
+    pub follow_speed: f32,
+}
+
+impl Default for TopDownCameraSettings {
+    fn default() -> Self {
+        Self {
+            pitch: 60.,
+            height: 18.,
+            min_height: 8.,
+            max_height: 32.,
+            zoom_step: 1.5,
+            lead: 4.,
+            dead_zone: 1.,
+            follow_speed: 6.,
+        }
+    }
+}
+
+/// Area the top-down camera keeps its focus in, placed on a level node.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct CameraBounds {
+    pub half_extents: Vec3,
+}
+
+impl Default for CameraBounds {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::splat(50.),
+        }
+    }
+}
+
+/// State of a [`TiedCamera`] in [`ViewMode::TopDown`].
+#[derive(Component, Debug)]
+struct TopDownRig {
+    focus: Vec3,
+    height: f32,
+}
+
+pub struct TopDownCameraPlugins;
+
+impl Plugin for TopDownCameraPlugins {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TopDownCameraSettings>()
+            .init_resource::<TopDownCameraSettings>()
+            .add_systems(
+                Update,
+                toggle_view_mode.run_if(not(in_state(LobbyState::None))),
+            )
+            .add_systems(
+                PostUpdate,
+                (zoom_top_down, follow_top_down)
+                    .chain()
+                    .before(TransformSystem::TransformPropagate)
+                    .run_if(not(in_state(LobbyState::None))),
+            );
+    }
+}
+
+/// Switches the view of the character of this peer between orbit and top-down.
+///
+/// The view is camera state of the peer, every peer toggles its own.
+fn toggle_view_mode(
+    mut me_query: Query<(&Character, &mut PlayerView), With<Me>>,
+    lobby: Option<Res<Lobby>>,
+) {
+    let (Some(lobby), Ok((character, mut view))) = (lobby, me_query.get_single_mut()) else {
+        return;
+    };
+    if lobby
+        .inputs_of(&character.id, true)
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::ToggleView))
+        .unwrap_or(false)
+    {
+        let mode = view.mode.toggle();
+        log::debug!("{:?} view mode {:?}", character.id, mode);
+    }
+}
+
+fn zoom_top_down(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut rig_query: Query<&mut TopDownRig>,
+    settings: Res<TopDownCameraSettings>,
+) {
+    let lines = wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        })
+        .sum::<f32>();
+    for mut rig in rig_query.iter_mut() {
+        rig.height = (rig.height - lines * settings.zoom_step)
+            .clamp(settings.min_height, settings.max_height);
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn follow_top_down(
+    mut commands: Commands,
+    mut tied_camera_query: Query<(
+        Entity,
+        &TiedCamera,
+        &Children,
+        &mut Transform,
+        Option<&mut TopDownRig>,
+    )>,
+    mut camera_query: Query<&mut Transform, (Without<TiedCamera>, With<Camera>)>,
+    view_query: Query<&PlayerView, With<Me>>,
+    target_query: Query<&Transform, (Without<TiedCamera>, Without<Camera>)>,
+    bounds_query: Query<(&CameraBounds, &GlobalTransform)>,
+    class_query: Query<&CharacterClass>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<TopDownCameraSettings>,
+    classes: Res<CharacterClasses>,
+    time: Res<Time>,
+) {
+    if !view_query
+        .get_single()
+        .is_ok_and(|view| view.mode == ViewMode::TopDown)
+    {
+        return;
+    }
+
+    // cursor position from the window center, -1..1 on both axes
+    let cursor = window_query.get_single().ok().and_then(|window| {
+        let position = window.cursor_position()?;
+        let size = Vec2::new(window.width(), window.height());
+        Some((position / size * 2. - Vec2::ONE).clamp(Vec2::NEG_ONE, Vec2::ONE))
+    });
+    let blend = 1. - (-settings.follow_speed * time.delta_seconds()).exp();
+
+    for (entity, TiedCamera(target), children, mut transform, rig) in tied_camera_query.iter_mut() {
+        let Ok(target_transform) = target_query.get(*target) else {
+            continue;
+        };
+        let Some(mut rig) = rig else {
+            let height = class_query
+                .get(*target)
+                .ok()
+                .and_then(|class| classes.get(*class).camera.height)
+                .unwrap_or(settings.height);
+            // start right over the character instead of flying in
+            commands.entity(entity).insert(TopDownRig {
+                focus: target_transform.translation,
+                height,
+            });
+            continue;
+        };
+
+        // the camera does not turn, screen down is world +Z
+        let lead = cursor.map_or(Vec3::ZERO, |cursor| {
+            Vec3::new(cursor.x, 0., cursor.y) * settings.lead
+        });
+        let shift = target_transform.translation + lead - rig.focus;
+        let flat = shift.reject_from(Vec3::Y);
+        let outside = (flat.length() - settings.dead_zone).max(0.);
+        rig.focus += (flat.normalize_or_zero() * outside + Vec3::Y * shift.y) * blend;
+
+        if let Some(focus) = bounded(&bounds_query, rig.focus) {
+            rig.focus = focus;
+        }
+
+        let pitch = settings.pitch.clamp(1., 90.).to_radians();
+        transform.translation = rig.focus;
+        transform.rotation = Quat::from_rotation_x(-pitch);
+        if let Some(child) = children.iter().next() {
+            if let Ok(mut camera_transform) = camera_query.get_mut(*child) {
+                camera_transform.translation = rig.height / pitch.sin() * Vec3::Z;
+            }
+        }
+    }
+}
+
+/// Moves the focus into the closest [`CameraBounds`], `None` without any.
+fn bounded(bounds_query: &Query<(&CameraBounds, &GlobalTransform)>, focus: Vec3) -> Option<Vec3> {
+    bounds_query
+        .iter()
+        .map(|(bounds, transform)| {
+            let (scale, _, center) = transform.to_scale_rotation_translation();
+            let half_extents = bounds.half_extents * scale.abs();
+            focus.clamp(center - half_extents, center + half_extents)
+        })
+        .min_by(|a, b| {
+            a.distance_squared(focus)
+                .total_cmp(&b.distance_squared(focus))
+        })
+}
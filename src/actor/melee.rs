@@ -0,0 +1,392 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    component::{Damage, DamageEvent, Guard, Health},
+    lobby::{Character, LobbyState, PeerClock},
+    world::CollisionLayer,
+};
+
+use super::{
+    ability::{AbilityActivated, AbilityDefinitions, AbilityTarget},
+    character::{CharacterMotion, HALPH_PLAYER_SIZE},
+};
+
+/// Seconds of positions kept beyond the longest rewind.
+const HISTORY_MARGIN: f64 = 0.1;
+
+/// Hitbox an ability swings, see [`AbilityDefinition`](super::ability::AbilityDefinition).
+///
+/// The cast time of the ability is the wind-up, then the hitbox hits for `active` seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MeleeDefinition {
+    /// Distance from the attacker to the middle of the hitbox.
+    pub reach: f32,
+    /// Halves of the width, height and depth of the hitbox.
+    pub half_extents: Vec3,
+    /// Seconds the hitbox hits.
+    pub active: f32,
+    pub damage: Damage,
+    /// Speed the target is pushed away with.
+    pub knockback: f32,
+    /// Seconds the attacker and the target freeze on a hit.
+    pub hit_stop: f32,
+}
+
+impl Default for MeleeDefinition {
+    fn default() -> Self {
+        Self {
+            reach: 1.,
+            half_extents: Vec3::new(0.6, 0.5, 0.5),
+            active: 0.15,
+            damage: Damage::default(),
+            knockback: 4.,
+            hit_stop: 0.06,
+        }
+    }
+}
+
+/// Shield an ability raises toward its target, see [`Guard`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ShieldDefinition {
+    /// Width of the covered front arc in degrees.
+    pub arc: f32,
+    /// Share of the damage and the knockback that is ignored.
+    pub reduction: f32,
+    /// Seconds the shield stays raised.
+    pub duration: f32,
+}
+
+impl Default for ShieldDefinition {
+    fn default() -> Self {
+        Self {
+            arc: 120.,
+            reduction: 0.7,
+            duration: 0.8,
+        }
+    }
+}
+
+/// Sensor attached to the attacker while a swing is active.
+#[derive(Component, Debug)]
+pub struct MeleeHitbox {
+    pub attacker: Entity,
+    pub damage: Damage,
+    pub knockback: f32,
+    pub hit_stop: f32,
+    pub half_extents: Vec3,
+    /// Seconds the host handles the swing after the attacker saw it, the targets are hit
+    /// where it saw them.
+    pub rewind: f32,
+    pub timer: Timer,
+    /// Every target is hit once per swing.
+    pub hit: HashSet<Entity>,
+}
+
+/// Keeps the [`Guard`] of a shield ability up.
+#[derive(Component, Debug)]
+pub struct RaisedShield(pub Timer);
+
+/// Recent positions of a character, the host rewinds the hits of lagging clients with it.
+#[derive(Component, Debug, Default)]
+pub struct PositionHistory(VecDeque<(f64, Vec3)>);
+
+impl PositionHistory {
+    pub fn record(&mut self, time: f64, position: Vec3) {
+        self.0.push_back((time, position));
+        let oldest = time - (PeerClock::MAX_LAG as f64 + HISTORY_MARGIN);
+        let stale = self
+            .0
+            .iter()
+            .take_while(|(recorded, _)| *recorded < oldest)
+            .count();
+        self.0.drain(..stale);
+    }
+
+    /// Position at `time`, between the two closest records.
+    pub fn at(&self, time: f64) -> Option<Vec3> {
+        match self.0.iter().position(|(recorded, _)| *recorded >= time) {
+            Some(0) => self.0.front().map(|(_, position)| *position),
+            Some(index) => {
+                let (before_time, before) = self.0[index - 1];
+                let (after_time, after) = self.0[index];
+                let share = (time - before_time) / (after_time - before_time).max(f64::EPSILON);
+                Some(before.lerp(after, share as f32))
+            }
+            None => self.0.back().map(|(_, position)| *position),
+        }
+    }
+}
+
+/// Melee swings and shields, resolved by the host.
+pub struct MeleePlugins;
+
+impl Plugin for MeleePlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                record_positions,
+                swing_hitboxes,
+                raise_shields,
+                melee_hits,
+                expire_hitboxes,
+                lower_shields,
+            )
+                .chain()
+                .run_if(
+                    not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+                ),
+        );
+    }
+}
+
+/// Ground direction an ability is aimed at, the forward of the caster by default.
+fn aim_direction(transform: &GlobalTransform, target: &AbilityTarget) -> Vec3 {
+    let origin = transform.translation();
+    match target {
+        AbilityTarget::Direction(direction) => Some(*direction),
+        AbilityTarget::Point(point) | AbilityTarget::Rune { point, .. } => Some(*point - origin),
+        AbilityTarget::Entity(_) | AbilityTarget::Myself => None,
+    }
+    .and_then(|direction| direction.reject_from(Vec3::Y).try_normalize())
+    .unwrap_or_else(|| transform.forward())
+}
+
+fn record_positions(
+    mut commands: Commands,
+    mut character_query: Query<
+        (Entity, &GlobalTransform, Option<&mut PositionHistory>),
+        With<Character>,
+    >,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+    for (entity, transform, history) in character_query.iter_mut() {
+        if let Some(mut history) = history {
+            history.record(now, transform.translation());
+        } else {
+            let mut history = PositionHistory::default();
+            history.record(now, transform.translation());
+            commands.entity(entity).insert(history);
+        }
+    }
+}
+
+/// Attaches the hitbox of the activated melee abilities to their caster.
+fn swing_hitboxes(
+    mut commands: Commands,
+    mut activated_events: EventReader<AbilityActivated>,
+    caster_query: Query<&GlobalTransform>,
+    definitions: Res<AbilityDefinitions>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+    for activated in activated_events.read() {
+        let Some(melee) = definitions
+            .get(&activated.ability)
+            .and_then(|definition| definition.melee.clone())
+        else {
+            continue;
+        };
+        let Ok(transform) = caster_query.get(activated.caster) else {
+            continue;
+        };
+        // the history does not go further back than the longest rewind
+        let swung = activated
+            .time
+            .map_or(now, |time| time.clamp(now - PeerClock::MAX_LAG as f64, now));
+        let rewind = (now - swung) as f32;
+
+        // the hitbox follows the caster, its offset is in the space of the caster
+        let (_, rotation, _) = transform.to_scale_rotation_translation();
+        let direction = rotation.inverse() * aim_direction(transform, &activated.target);
+        let half_extents = melee.half_extents;
+        let hitbox = commands
+            .spawn((
+                TransformBundle::from_transform(
+                    Transform::from_translation(direction * melee.reach)
+                        .looking_to(direction, Vec3::Y),
+                ),
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                Sensor,
+                ActiveCollisionTypes::all(),
+                CollisionLayer::hitbox(),
+                MeleeHitbox {
+                    attacker: activated.caster,
+                    damage: melee.damage,
+                    knockback: melee.knockback,
+                    hit_stop: melee.hit_stop,
+                    half_extents,
+                    rewind,
+                    timer: Timer::from_seconds(melee.active, TimerMode::Once),
+                    hit: HashSet::new(),
+                },
+                Name::new(format!("MeleeHitbox:{}", activated.ability)),
+            ))
+            .id();
+        commands.entity(activated.caster).add_child(hitbox);
+    }
+}
+
+/// Raises the shield of the activated shield abilities toward their target.
+fn raise_shields(
+    mut commands: Commands,
+    mut activated_events: EventReader<AbilityActivated>,
+    caster_query: Query<&GlobalTransform>,
+    definitions: Res<AbilityDefinitions>,
+) {
+    for activated in activated_events.read() {
+        let Some(shield) = definitions
+            .get(&activated.ability)
+            .and_then(|definition| definition.shield.clone())
+        else {
+            continue;
+        };
+        let Ok(transform) = caster_query.get(activated.caster) else {
+            continue;
+        };
+        commands.entity(activated.caster).insert((
+            Guard {
+                facing: aim_direction(transform, &activated.target),
+                arc: shield.arc,
+                reduction: shield.reduction,
+            },
+            RaisedShield(Timer::from_seconds(shield.duration, TimerMode::Once)),
+        ));
+    }
+}
+
+/// Hurts and pushes what the active hitboxes touch.
+///
+/// Characters are tested where they were when the attacker swung, the rest by the sensor.
+#[allow(clippy::type_complexity)]
+fn melee_hits(
+    mut hitbox_query: Query<(Entity, &GlobalTransform, &mut MeleeHitbox)>,
+    history_query: Query<(Entity, &PositionHistory)>,
+    position_query: Query<&GlobalTransform>,
+    mut target_query: Query<(Option<&Guard>, Option<&mut CharacterMotion>), With<Health>>,
+    rapier_context: Res<RapierContext>,
+    mut damage_events: EventWriter<DamageEvent>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+    for (entity, transform, mut hitbox) in hitbox_query.iter_mut() {
+        let when = now - hitbox.rewind as f64;
+        let inverse = transform.affine().inverse();
+        let bounds = hitbox.half_extents + Vec3::splat(HALPH_PLAYER_SIZE);
+        let rewound = history_query.iter().filter_map(|(target, history)| {
+            let position = inverse.transform_point3(history.at(when)?);
+            position.abs().cmple(bounds).all().then_some(target)
+        });
+        let touching = rapier_context
+            .intersection_pairs_with(entity)
+            .filter(|(_, _, intersecting)| *intersecting)
+            .map(|(a, b, _)| if a == entity { b } else { a })
+            .filter(|target| !history_query.contains(*target));
+        let targets: Vec<Entity> = rewound
+            .chain(touching)
+            .filter(|target| *target != hitbox.attacker && target_query.contains(*target))
+            .collect();
+
+        let origin = position_query
+            .get(hitbox.attacker)
+            .map_or(transform.translation(), |attacker| attacker.translation());
+        for target in targets {
+            if !hitbox.hit.insert(target) {
+                continue;
+            }
+            let direction = position_query
+                .get(target)
+                .ok()
+                .and_then(|target| {
+                    (target.translation() - origin)
+                        .reject_from(Vec3::Y)
+                        .try_normalize()
+                })
+                .unwrap_or_else(|| transform.forward());
+            damage_events.send(
+                DamageEvent::new(target, Some(hitbox.attacker), &hitbox.damage)
+                    .with_direction(direction),
+            );
+
+            let Ok((guard, motion)) = target_query.get_mut(target) else {
+                continue;
+            };
+            // a shield takes the push as well
+            let share = guard.map_or(1., |guard| guard.apply(direction, 1.));
+            if let Some(mut motion) = motion {
+                motion.hit(direction * hitbox.knockback * share, hitbox.hit_stop);
+            }
+            if let Ok((_, Some(mut motion))) = target_query.get_mut(hitbox.attacker) {
+                motion.hit(Vec3::ZERO, hitbox.hit_stop);
+            }
+        }
+    }
+}
+
+fn expire_hitboxes(
+    mut commands: Commands,
+    mut hitbox_query: Query<(Entity, &mut MeleeHitbox)>,
+    time: Res<Time>,
+) {
+    for (entity, mut hitbox) in hitbox_query.iter_mut() {
+        if hitbox.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn lower_shields(
+    mut commands: Commands,
+    mut shield_query: Query<(Entity, &mut RaisedShield)>,
+    time: Res<Time>,
+) {
+    for (entity, mut shield) in shield_query.iter_mut() {
+        if shield.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<(Guard, RaisedShield)>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> PositionHistory {
+        let mut history = PositionHistory::default();
+        for step in 0..3 {
+            history.record(step as f64 * 0.1, Vec3::X * step as f32);
+        }
+        history
+    }
+
+    #[test]
+    fn history_interpolates_between_records() {
+        let history = history();
+        assert!(history.at(0.05).unwrap().abs_diff_eq(Vec3::X * 0.5, 1e-5));
+        assert!(history.at(0.15).unwrap().abs_diff_eq(Vec3::X * 1.5, 1e-5));
+        assert_eq!(history.at(0.1), Some(Vec3::X));
+    }
+
+    #[test]
+    fn history_clamps_to_the_oldest_and_newest_records() {
+        let history = history();
+        assert_eq!(history.at(-1.), Some(Vec3::ZERO));
+        assert_eq!(history.at(1.), Some(Vec3::X * 2.));
+        assert_eq!(PositionHistory::default().at(0.), None);
+    }
+
+    #[test]
+    fn history_forgets_what_is_older_than_the_longest_rewind() {
+        let mut history = history();
+        history.record(1., Vec3::Y);
+        assert_eq!(history.0.len(), 1);
+        assert_eq!(history.at(0.), Some(Vec3::Y));
+    }
+}
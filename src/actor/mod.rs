@@ -1,9 +1,18 @@
 #![allow(clippy::module_inception)]
 
 mod actor;
+mod melee;
+mod projectile;
+mod stealth;
 mod trace;
+mod true_sight;
 
+pub mod ability;
 pub mod character;
 
 pub use actor::*;
+pub use melee::*;
+pub use projectile::*;
+pub use stealth::*;
 pub use trace::*;
+pub use true_sight::*;
@@ -0,0 +1,217 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    component::{Damage, DamageEvent, Despawn, DespawnReason, DespawnTimer},
+    extend_commands,
+    lobby::{host::SpawnProjectileEvent, LobbyState},
+    world::{CollisionLayer, LinkId, ProjectileIdSeq},
+};
+
+use super::{
+    ability::{AbilityActivated, AbilityDefinitions, AbilityTarget},
+    character::HALPH_PLAYER_SIZE,
+    Actor, Trace,
+};
+
+/// Gap between the shooter and a spawned projectile.
+const MUZZLE_GAP: f32 = 0.1;
+
+/// Trail of tracepoints behind a projectile, see [`Trace`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ProjectileTrace {
+    /// Seconds a tracepoint stays.
+    pub duration: f32,
+    /// Seconds between two tracepoints.
+    pub intensity: f32,
+}
+
+/// What an ability shoots, see [`AbilityDefinition`](super::ability::AbilityDefinition).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ProjectileDefinition {
+    pub speed: f32,
+    pub radius: f32,
+    /// Share of the gravity, 0 flies straight.
+    pub gravity_scale: f32,
+    /// Seconds before it falls apart.
+    pub lifetime: f32,
+    pub damage: Damage,
+    pub trace: Option<ProjectileTrace>,
+}
+
+impl Default for ProjectileDefinition {
+    fn default() -> Self {
+        Self {
+            speed: 30.,
+            radius: 0.15,
+            gravity_scale: 0.,
+            lifetime: 3.,
+            damage: Damage::default(),
+            trace: None,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Projectile {
+    /// Not hit by its own projectiles, unknown to the clients.
+    pub shooter: Option<Entity>,
+    pub damage: Damage,
+    /// Already hit something and falls apart.
+    pub spent: bool,
+}
+
+pub struct ProjectilePlugins;
+
+impl Plugin for ProjectilePlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                fire_projectiles.run_if(
+                    not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+                ),
+                projectile_hits.run_if(not(in_state(LobbyState::None))),
+            ),
+        );
+    }
+}
+
+/// Shoots the projectiles of the activated abilities, the clients get them from the host.
+fn fire_projectiles(
+    mut commands: Commands,
+    mut activated_events: EventReader<AbilityActivated>,
+    mut spawn_projectile_events: EventWriter<SpawnProjectileEvent>,
+    caster_query: Query<(&GlobalTransform, Option<&Handle<StandardMaterial>>)>,
+    materials: Res<Assets<StandardMaterial>>,
+    definitions: Res<AbilityDefinitions>,
+    mut projectile_id_seq: ResMut<ProjectileIdSeq>,
+) {
+    for activated in activated_events.read() {
+        let Some(projectile) = definitions
+            .get(&activated.ability)
+            .and_then(|definition| definition.projectile.clone())
+        else {
+            continue;
+        };
+        let Ok((transform, material)) = caster_query.get(activated.caster) else {
+            continue;
+        };
+        let origin = transform.translation();
+        let toward = |point: Vec3| (point - origin).try_normalize();
+        let direction = match &activated.target {
+            AbilityTarget::Direction(direction) => Some(*direction),
+            AbilityTarget::Point(point) | AbilityTarget::Rune { point, .. } => toward(*point),
+            AbilityTarget::Entity(entity) => caster_query
+                .get(*entity)
+                .ok()
+                .and_then(|(target, _)| toward(target.translation())),
+            AbilityTarget::Myself => None,
+        }
+        .unwrap_or_else(|| transform.forward());
+
+        let color = material
+            .and_then(|material| materials.get(material))
+            .map_or(Color::WHITE, |material| material.base_color);
+        let position = origin + direction * (HALPH_PLAYER_SIZE + projectile.radius + MUZZLE_GAP);
+        let velocity = direction * projectile.speed;
+        let id = projectile_id_seq.shift();
+
+        commands.spawn_projectile(
+            id.clone(),
+            projectile.clone(),
+            position,
+            velocity,
+            color,
+            Some(activated.caster),
+        );
+        spawn_projectile_events.send(SpawnProjectileEvent {
+            id,
+            color,
+            projectile,
+            position,
+            velocity,
+        });
+    }
+}
+
+/// Hurts what a projectile hits and makes it fall apart, only the damage of the host counts.
+fn projectile_hits(
+    mut collision_events: EventReader<CollisionEvent>,
+    mut projectile_query: Query<(&mut Projectile, &mut Despawn, &Velocity)>,
+    sensor_query: Query<(), With<Sensor>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for event in collision_events.read() {
+        let CollisionEvent::Started(a, b, _) = *event else {
+            continue;
+        };
+        for (projectile_entity, target) in [(a, b), (b, a)] {
+            let Ok((mut projectile, mut despawn, velocity)) =
+                projectile_query.get_mut(projectile_entity)
+            else {
+                continue;
+            };
+            // trigger volumes of the level and hitboxes are flown through
+            if projectile.spent
+                || projectile.shooter == Some(target)
+                || sensor_query.contains(target)
+            {
+                continue;
+            }
+            projectile.spent = true;
+            damage_events.send(
+                DamageEvent::new(target, projectile.shooter, &projectile.damage)
+                    .with_direction(velocity.linvel),
+            );
+            despawn.insert_reason(DespawnReason::Forced);
+        }
+    }
+}
+
+extend_commands!(
+  spawn_projectile(id: LinkId, projectile: ProjectileDefinition, position: Vec3, velocity: Vec3, color: Color, shooter: Option<Entity>),
+  |world: &mut World, entity_id: Entity, id: LinkId, projectile: ProjectileDefinition, position: Vec3, velocity: Vec3, color: Color, shooter: Option<Entity>| {
+    let mesh = world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Sphere::new(projectile.radius).mesh().uv(12, 8));
+    let material = world
+        .resource_mut::<Assets<StandardMaterial>>()
+        .add(StandardMaterial {
+            base_color: color,
+            emissive: color,
+            ..default()
+        });
+
+    let mut entity = world.entity_mut(entity_id);
+    entity.insert((
+        PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(position),
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::ball(projectile.radius),
+        Velocity::linear(velocity),
+        GravityScale(projectile.gravity_scale),
+        Ccd::enabled(),
+        ActiveEvents::COLLISION_EVENTS,
+        CollisionLayer::projectile(),
+        Despawn::new(DespawnReason::After(DespawnTimer::new(projectile.lifetime))),
+        Projectile {
+            shooter,
+            damage: projectile.damage,
+            spent: false,
+        },
+        Actor,
+        Name::new(format!("Projectile:{:?}", id)),
+        id,
+    ));
+    if let Some(trace) = projectile.trace {
+        entity.insert(Trace::new(trace.duration, trace.intensity, color));
+    }
+  }
+);
@@ -0,0 +1,411 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    component::DamageEvent,
+    lobby::LobbyState,
+    world::{CollisionLayer, Me},
+};
+
+use super::{
+    ability::{AbilityActivated, AbilityDefinitions},
+    character::CharacterControllerSettings,
+};
+
+/// Tuning of the exposure, the noises and the perception.
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct StealthSettings {
+    /// Illuminance in lux an actor is fully lit at.
+    pub bright_light: f32,
+    /// Light share of an actor in the dark, close watchers still see a shape.
+    pub dark_floor: f32,
+    /// Share of the exposure that comes from the movement, the rest from the light.
+    pub movement_share: f32,
+    /// Multiplier of the exposure and the walk speed while sneaking.
+    pub sneak_factor: f32,
+    /// Meters between two footsteps.
+    pub step_length: f32,
+    /// Radii in meters footsteps are heard within.
+    pub walk_noise: f32,
+    pub run_noise: f32,
+    pub sneak_noise: f32,
+    /// Radius of hits and attacks.
+    pub combat_noise: f32,
+    /// Awareness gained per second watching a fully exposed actor up close.
+    pub awareness_gain: f32,
+    /// Awareness gained by a noise right next to the listener.
+    pub hearing_gain: f32,
+    /// Awareness lost per second without seeing or hearing anything.
+    pub awareness_decay: f32,
+}
+
+impl Default for StealthSettings {
+    fn default() -> Self {
+        Self {
+            bright_light: 40.,
+            dark_floor: 0.05,
+            movement_share: 0.4,
+            sneak_factor: 0.5,
+            step_length: 1.6,
+            walk_noise: 4.,
+            run_noise: 10.,
+            sneak_noise: 0.,
+            combat_noise: 15.,
+            awareness_gain: 1.5,
+            hearing_gain: 0.4,
+            awareness_decay: 0.15,
+        }
+    }
+}
+
+/// How visible an actor is, see [`StealthSettings`].
+#[derive(Component, Reflect, Debug, Clone, Default)]
+#[reflect(Component)]
+pub struct Exposure {
+    /// From 0, invisible, to 1.
+    pub score: f32,
+    /// Light reaching the actor in `0..=1`.
+    pub light: f32,
+    /// Ground speed in meters per second.
+    pub speed: f32,
+    last_position: Option<Vec3>,
+    /// Meters walked since the last footstep.
+    walked: f32,
+}
+
+/// Crouched, slower and harder to see and hear, toggled by the sneak ability.
+#[derive(Component, Reflect, Debug, Clone, Copy, Default)]
+#[reflect(Component)]
+pub struct Sneaking;
+
+/// Something NPCs may hear within the radius.
+#[derive(Event, Debug, Clone)]
+pub struct NoiseEvent {
+    pub source: Entity,
+    pub position: Vec3,
+    pub radius: f32,
+}
+
+#[derive(Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Alertness {
+    #[default]
+    Unaware,
+    /// Saw or heard something, looks around.
+    Suspicious,
+    Alerted,
+}
+
+/// Senses of an NPC, the host fills them from the [`Exposure`] and the [`NoiseEvent`]s.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct Perception {
+    pub sight_range: f32,
+    /// Width of the view cone in degrees.
+    pub fov: f32,
+    /// Multiplier of the noise radii.
+    pub hearing: f32,
+    /// From 0 to 1, sets the [`Alertness`].
+    pub awareness: f32,
+    pub alertness: Alertness,
+    /// Actor that raised the awareness the last.
+    pub target: Option<Entity>,
+    /// Where the last heard noise came from.
+    pub heard: Option<Vec3>,
+}
+
+impl Perception {
+    /// Awareness from which the NPC is [`Alertness::Suspicious`].
+    pub const SUSPICIOUS: f32 = 0.3;
+
+    fn alertness(&self) -> Alertness {
+        if self.awareness >= 1. {
+            Alertness::Alerted
+        } else if self.awareness >= Self::SUSPICIOUS {
+            Alertness::Suspicious
+        } else {
+            Alertness::Unaware
+        }
+    }
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            sight_range: 15.,
+            fov: 110.,
+            hearing: 1.,
+            awareness: 0.,
+            alertness: Alertness::Unaware,
+            target: None,
+            heard: None,
+        }
+    }
+}
+
+/// Sent when the [`Alertness`] of an NPC changes.
+#[derive(Event, Debug, Clone)]
+pub struct AlertnessChanged {
+    pub entity: Entity,
+    pub alertness: Alertness,
+}
+
+pub struct StealthPlugins;
+
+impl Plugin for StealthPlugins {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StealthSettings>()
+            .register_type::<Exposure>()
+            .register_type::<Sneaking>()
+            .init_resource::<StealthSettings>()
+            .add_event::<NoiseEvent>()
+            .add_event::<AlertnessChanged>()
+            .add_systems(
+                Update,
+                (
+                    toggle_sneak,
+                    measure_exposure,
+                    (combat_noise, perceive)
+                        .chain()
+                        .run_if(not(in_state(LobbyState::Client))),
+                    exposure_hud,
+                )
+                    .chain()
+                    .run_if(not(in_state(LobbyState::None))),
+            );
+    }
+}
+
+fn toggle_sneak(
+    mut commands: Commands,
+    mut activated_events: EventReader<AbilityActivated>,
+    sneaking_query: Query<Has<Sneaking>>,
+    definitions: Res<AbilityDefinitions>,
+) {
+    for activated in activated_events.read() {
+        if !definitions
+            .get(&activated.ability)
+            .is_some_and(|definition| definition.sneak)
+        {
+            continue;
+        }
+        match sneaking_query.get(activated.caster) {
+            Ok(true) => {
+                commands.entity(activated.caster).remove::<Sneaking>();
+            }
+            Ok(false) => {
+                commands.entity(activated.caster).insert(Sneaking);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Lights the actors by the point lights in sight and makes their footsteps heard.
+fn measure_exposure(
+    mut exposure_query: Query<(Entity, &GlobalTransform, &mut Exposure, Has<Sneaking>)>,
+    light_query: Query<(&GlobalTransform, &PointLight, &InheritedVisibility)>,
+    mut noise_events: EventWriter<NoiseEvent>,
+    rapier_context: Res<RapierContext>,
+    settings: Res<StealthSettings>,
+    controller_settings: Res<CharacterControllerSettings>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    if delta_seconds <= 0. {
+        return;
+    }
+    let run_speed = controller_settings.walk_speed * controller_settings.sprint_multiplier;
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .groups(CollisionLayer::scenery());
+
+    for (entity, transform, mut exposure, sneaking) in exposure_query.iter_mut() {
+        let position = transform.translation();
+
+        let illuminance: f32 = light_query
+            .iter()
+            .filter(|(_, _, visibility)| visibility.get())
+            .filter_map(|(light_transform, light, _)| {
+                let origin = light_transform.translation();
+                let to_actor = position - origin;
+                let distance = to_actor.length();
+                if distance > light.range {
+                    return None;
+                }
+                let blocked = rapier_context
+                    .cast_ray(origin, to_actor.normalize_or_zero(), distance, true, filter)
+                    .is_some();
+                // lumens spread over the sphere, faded out at the range
+                let fade = 1. - distance / light.range;
+                (!blocked).then(|| light.intensity / (4. * PI * distance.max(1.).powi(2)) * fade)
+            })
+            .sum();
+        exposure.light = (illuminance / settings.bright_light).clamp(settings.dark_floor, 1.);
+
+        let walked = exposure
+            .last_position
+            .map_or(0., |last| (position - last).reject_from(Vec3::Y).length());
+        exposure.last_position = Some(position);
+        exposure.speed = walked / delta_seconds;
+
+        let movement = (exposure.speed / run_speed).min(1.);
+        let mut score =
+            exposure.light * (1. - settings.movement_share) + movement * settings.movement_share;
+        if sneaking {
+            score *= settings.sneak_factor;
+        }
+        exposure.score = score.clamp(0., 1.);
+
+        exposure.walked += walked;
+        if exposure.walked < settings.step_length {
+            continue;
+        }
+        exposure.walked = 0.;
+        let radius = if sneaking {
+            settings.sneak_noise
+        } else if exposure.speed > controller_settings.walk_speed * 1.2 {
+            settings.run_noise
+        } else {
+            settings.walk_noise
+        };
+        if radius > 0. {
+            noise_events.send(NoiseEvent {
+                source: entity,
+                position,
+                radius,
+            });
+        }
+    }
+}
+
+/// Hits and attacks are heard, only the host knows about them.
+fn combat_noise(
+    mut damage_events: EventReader<DamageEvent>,
+    mut activated_events: EventReader<AbilityActivated>,
+    mut noise_events: EventWriter<NoiseEvent>,
+    position_query: Query<&GlobalTransform>,
+    definitions: Res<AbilityDefinitions>,
+    settings: Res<StealthSettings>,
+) {
+    let hits = damage_events.read().map(|event| event.target);
+    let attacks = activated_events
+        .read()
+        .filter(|activated| {
+            definitions
+                .get(&activated.ability)
+                .is_some_and(|definition| definition.offensive)
+        })
+        .map(|activated| activated.caster);
+    for source in hits.chain(attacks) {
+        if let Ok(transform) = position_query.get(source) {
+            noise_events.send(NoiseEvent {
+                source,
+                position: transform.translation(),
+                radius: settings.combat_noise,
+            });
+        }
+    }
+}
+
+/// Raises the awareness of the NPCs by what they see and hear.
+fn perceive(
+    mut perception_query: Query<(Entity, &GlobalTransform, &mut Perception)>,
+    exposure_query: Query<(Entity, &GlobalTransform, &Exposure)>,
+    mut noise_events: EventReader<NoiseEvent>,
+    mut alertness_events: EventWriter<AlertnessChanged>,
+    rapier_context: Res<RapierContext>,
+    settings: Res<StealthSettings>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    let noises: Vec<NoiseEvent> = noise_events.read().cloned().collect();
+
+    for (entity, transform, mut perception) in perception_query.iter_mut() {
+        let eye = transform.translation();
+        let forward = transform.forward();
+        let half_fov = (perception.fov / 2.).to_radians();
+        let filter = QueryFilter::new()
+            .exclude_sensors()
+            .groups(CollisionLayer::scenery());
+
+        let seen = exposure_query
+            .iter()
+            .filter(|(target, ..)| *target != entity)
+            .filter_map(|(target, target_transform, exposure)| {
+                let to_target = target_transform.translation() - eye;
+                let distance = to_target.length();
+                if distance > perception.sight_range || forward.angle_between(to_target) > half_fov
+                {
+                    return None;
+                }
+                let blocked = rapier_context
+                    .cast_ray(eye, to_target.normalize_or_zero(), distance, true, filter)
+                    .is_some();
+                (!blocked).then_some((
+                    target,
+                    exposure.score * (1. - distance / perception.sight_range),
+                ))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let mut gain = 0.;
+        if let Some((target, visibility)) = seen.filter(|(_, visibility)| *visibility > 0.) {
+            gain += visibility * settings.awareness_gain * delta_seconds;
+            perception.target = Some(target);
+        }
+        for noise in noises.iter().filter(|noise| noise.source != entity) {
+            let radius = noise.radius * perception.hearing;
+            let distance = noise.position.distance(eye);
+            if distance < radius {
+                gain += settings.hearing_gain * (1. - distance / radius);
+                perception.heard = Some(noise.position);
+                if perception.target.is_none() {
+                    perception.target = Some(noise.source);
+                }
+            }
+        }
+
+        perception.awareness = if gain > 0. {
+            (perception.awareness + gain).min(1.)
+        } else {
+            (perception.awareness - settings.awareness_decay * delta_seconds).max(0.)
+        };
+        if perception.awareness <= 0. {
+            perception.target = None;
+            perception.heard = None;
+        }
+
+        let alertness = perception.alertness();
+        if alertness != perception.alertness {
+            log::debug!("{:?} is {:?}", entity, alertness);
+            perception.alertness = alertness;
+            alertness_events.send(AlertnessChanged { entity, alertness });
+        }
+    }
+}
+
+/// Shows how exposed the character of this peer is.
+fn exposure_hud(mut context: EguiContexts, me_query: Query<(&Exposure, Has<Sneaking>), With<Me>>) {
+    let Ok((exposure, sneaking)) = me_query.get_single() else {
+        return;
+    };
+    // from a dim gray to gold
+    let level = 60. + 195. * exposure.score;
+    let color = egui::Color32::from_rgb(level as u8, (level * 0.85) as u8, 60);
+    let label = if sneaking { "Sneaking" } else { "Exposure" };
+    egui::Area::new(egui::Id::new("exposure"))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0., -16.])
+        .interactable(false)
+        .show(context.ctx_mut(), |ui| {
+            ui.add(
+                egui::ProgressBar::new(exposure.score)
+                    .desired_width(160.)
+                    .fill(color)
+                    .text(label),
+            );
+        });
+}
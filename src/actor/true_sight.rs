@@ -0,0 +1,399 @@
+use bevy::{
+    ecs::system::{EntityCommands, SystemParam},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    extend_commands,
+    lobby::{Character, LobbyState},
+    world::{LinkId, Me},
+};
+
+use super::{
+    ability::{AbilityActivated, AbilityDefinitions},
+    character::ClassStats,
+    Actor,
+};
+
+/// Color the revealed meshes are drawn with.
+const REVEALED_TINT: Color = Color::rgba(1., 0.9, 0.55, 0.6);
+const REVEALED_GLOW: Color = Color::rgb(0.5, 0.4, 0.15);
+
+/// Invisible to the characters without [`TrueSight`], authored in levels as GLTF extras.
+///
+/// Clients strip them from the scenes they load, the host sends the ones they see as
+/// [`HiddenActor`]s, see [`HiddenReplication`](crate::lobby::host::HiddenReplication).
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct HiddenToMortals;
+
+/// Sees the [`HiddenToMortals`], for good without a timer.
+#[derive(Component, Debug, Default, Clone)]
+pub struct TrueSight(pub Option<Timer>);
+
+impl TrueSight {
+    pub fn is_permanent(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+/// A [`HiddenToMortals`] the character of this peer sees, drawn tinted.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Revealed;
+
+/// A mesh of a revealed actor, drawn with a tinted copy of its material.
+#[derive(Component)]
+pub struct Tinted {
+    original: Handle<StandardMaterial>,
+}
+
+/// A revealed [`HiddenToMortals`] as the host sends it to a client.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HiddenActor {
+    pub id: LinkId,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    pub meshes: Vec<HiddenMesh>,
+}
+
+/// A mesh of a [`HiddenActor`], by the asset paths of the level it is part of.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HiddenMesh {
+    pub mesh: String,
+    pub material: Option<String>,
+    /// Relative to the actor.
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+/// Reads the hidden actors of the host into [`HiddenActor`]s.
+#[derive(SystemParam)]
+pub struct HiddenActors<'w, 's> {
+    hidden_query:
+        Query<'w, 's, (Entity, &'static LinkId, &'static GlobalTransform), With<HiddenToMortals>>,
+    children_query: Query<'w, 's, &'static Children>,
+    mesh_query: Query<
+        'w,
+        's,
+        (
+            &'static GlobalTransform,
+            &'static Handle<Mesh>,
+            Option<&'static Handle<StandardMaterial>>,
+            Option<&'static Tinted>,
+        ),
+    >,
+    asset_server: Res<'w, AssetServer>,
+}
+
+impl HiddenActors<'_, '_> {
+    pub fn ids(&self) -> impl Iterator<Item = &LinkId> {
+        self.hidden_query.iter().map(|(_, id, _)| id)
+    }
+
+    pub fn describe(&self, id: &LinkId) -> Option<HiddenActor> {
+        let (entity, _, transform) = self
+            .hidden_query
+            .iter()
+            .find(|(_, link_id, _)| *link_id == id)?;
+        let meshes = std::iter::once(entity)
+            .chain(self.children_query.iter_descendants(entity))
+            .filter_map(|node| {
+                let (mesh_transform, mesh, material, tinted) = self.mesh_query.get(node).ok()?;
+                // generated meshes have no path, the clients could not load them
+                let Some(mesh) = self.asset_server.get_path(mesh.id()) else {
+                    log::warn!("mesh of hidden actor {:?} has no asset path", id);
+                    return None;
+                };
+                let material = tinted
+                    .map(|tinted| &tinted.original)
+                    .or(material)
+                    .and_then(|material| self.asset_server.get_path(material.id()));
+                let relative = mesh_transform.reparented_to(transform);
+                Some(HiddenMesh {
+                    mesh: mesh.to_string(),
+                    material: material.map(|material| material.to_string()),
+                    translation: relative.translation,
+                    rotation: relative.rotation,
+                    scale: relative.scale,
+                })
+            })
+            .collect();
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        Some(HiddenActor {
+            id: id.clone(),
+            translation,
+            rotation,
+            scale,
+            meshes,
+        })
+    }
+}
+
+/// [`TrueSight`] an ability shares, see [`AbilityDefinition`](super::ability::AbilityDefinition).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RevealDefinition {
+    /// Characters within it around the caster see for a while.
+    pub radius: f32,
+    pub duration: f32,
+}
+
+impl Default for RevealDefinition {
+    fn default() -> Self {
+        Self {
+            radius: 8.,
+            duration: 6.,
+        }
+    }
+}
+
+/// Hides the [`HiddenToMortals`] from the characters without [`TrueSight`].
+pub struct TrueSightPlugins;
+
+impl Plugin for TrueSightPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                strip_hidden.run_if(in_state(LobbyState::Client)),
+                // clients get what they see from the host
+                (
+                    link_hidden,
+                    grant_class_sight,
+                    share_sight,
+                    expire_sight,
+                    reveal_to_me,
+                )
+                    .chain()
+                    .run_if(not(in_state(LobbyState::Client))),
+                hide_unrevealed,
+                tint_revealed,
+                untint_concealed,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Removes the hidden actors of the scenes a client loads, it only has the ones the host
+/// reveals to it.
+fn strip_hidden(
+    mut commands: Commands,
+    hidden_query: Query<Entity, (Added<HiddenToMortals>, Without<Revealed>)>,
+) {
+    for entity in hidden_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Links hidden actors by the path of their scene and the names down to them, so the host
+/// can tell the clients about them.
+///
+/// The names above the scene tell apart the instances of a scene, like the rooms of a dungeon.
+fn link_hidden(
+    mut commands: Commands,
+    hidden_query: Query<Entity, (Added<HiddenToMortals>, Without<LinkId>)>,
+    parent_query: Query<&Parent>,
+    name_query: Query<&Name>,
+    scene_query: Query<&Handle<Scene>>,
+    asset_server: Res<AssetServer>,
+) {
+    for entity in hidden_query.iter() {
+        let mut names = Vec::new();
+        let mut scene = None;
+        for node in std::iter::once(entity).chain(parent_query.iter_ancestors(entity)) {
+            if let Ok(name) = name_query.get(node) {
+                names.push(name.as_str());
+            }
+            if let (None, Ok(handle)) = (&scene, scene_query.get(node)) {
+                scene = asset_server.get_path(handle.id());
+            }
+        }
+        let Some(scene) = scene else {
+            log::warn!(
+                "hidden actor {:?} is not part of a scene and is never revealed",
+                entity
+            );
+            continue;
+        };
+        names.reverse();
+        commands
+            .entity(entity)
+            .insert(LinkId::Node(format!("{}/{}", scene, names.join("/"))));
+    }
+}
+
+fn grant_class_sight(
+    mut commands: Commands,
+    stats_query: Query<(Entity, &ClassStats, Option<&TrueSight>), Changed<ClassStats>>,
+) {
+    for (entity, stats, sight) in stats_query.iter() {
+        if stats.true_sight {
+            commands.entity(entity).insert(TrueSight(None));
+        } else if sight.is_some_and(TrueSight::is_permanent) {
+            commands.entity(entity).remove::<TrueSight>();
+        }
+    }
+}
+
+/// Lends the sight of the activated reveal abilities to the characters around the caster.
+fn share_sight(
+    mut commands: Commands,
+    mut activated_events: EventReader<AbilityActivated>,
+    character_query: Query<(Entity, &GlobalTransform, Option<&TrueSight>), With<Character>>,
+    definitions: Res<AbilityDefinitions>,
+) {
+    for activated in activated_events.read() {
+        let Some(reveal) = definitions
+            .get(&activated.ability)
+            .and_then(|definition| definition.reveal.clone())
+        else {
+            continue;
+        };
+        let Ok((_, caster, _)) = character_query.get(activated.caster) else {
+            continue;
+        };
+        let origin = caster.translation();
+        let lent = TrueSight(Some(Timer::from_seconds(reveal.duration, TimerMode::Once)));
+        for (entity, transform, sight) in character_query.iter() {
+            if transform.translation().distance(origin) > reveal.radius
+                || sight.is_some_and(TrueSight::is_permanent)
+            {
+                continue;
+            }
+            commands.entity(entity).insert(lent.clone());
+        }
+    }
+}
+
+fn expire_sight(
+    mut commands: Commands,
+    mut sight_query: Query<(Entity, &mut TrueSight)>,
+    time: Res<Time>,
+) {
+    for (entity, mut sight) in sight_query.iter_mut() {
+        if sight
+            .0
+            .as_mut()
+            .is_some_and(|timer| timer.tick(time.delta()).finished())
+        {
+            commands.entity(entity).remove::<TrueSight>();
+        }
+    }
+}
+
+/// Reveals the hidden actors while the character of this peer sees them.
+fn reveal_to_me(
+    mut commands: Commands,
+    me_query: Query<Has<TrueSight>, With<Me>>,
+    hidden_query: Query<(Entity, Has<Revealed>), With<HiddenToMortals>>,
+) {
+    let sees = me_query.get_single().unwrap_or(false);
+    for (entity, revealed) in hidden_query.iter() {
+        if sees && !revealed {
+            commands.entity(entity).insert(Revealed);
+        } else if !sees && revealed {
+            commands.entity(entity).remove::<Revealed>();
+        }
+    }
+}
+
+fn hide_unrevealed(
+    mut hidden_query: Query<(&mut Visibility, Has<Revealed>), With<HiddenToMortals>>,
+) {
+    for (mut visibility, revealed) in hidden_query.iter_mut() {
+        visibility.set_if_neq(if revealed {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+fn tint_revealed(
+    mut commands: Commands,
+    revealed_query: Query<Entity, Added<Revealed>>,
+    children_query: Query<&Children>,
+    mut material_query: Query<&mut Handle<StandardMaterial>, Without<Tinted>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for entity in revealed_query.iter() {
+        for mesh in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            let Ok(mut handle) = material_query.get_mut(mesh) else {
+                continue;
+            };
+            // the material may be shared with actors that are not hidden
+            let Some(mut tinted) = materials.get(&*handle).cloned() else {
+                continue;
+            };
+            tinted.alpha_mode = AlphaMode::Blend;
+            tinted.base_color = REVEALED_TINT;
+            tinted.emissive = REVEALED_GLOW;
+            let original = std::mem::replace(&mut *handle, materials.add(tinted));
+            commands.entity(mesh).insert(Tinted { original });
+        }
+    }
+}
+
+fn untint_concealed(
+    mut commands: Commands,
+    mut removed_revealed: RemovedComponents<Revealed>,
+    children_query: Query<&Children>,
+    mut tinted_query: Query<(&Tinted, &mut Handle<StandardMaterial>)>,
+) {
+    for entity in removed_revealed.read() {
+        for mesh in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            if let Ok((tinted, mut handle)) = tinted_query.get_mut(mesh) {
+                *handle = tinted.original.clone();
+                commands.entity(mesh).remove::<Tinted>();
+            }
+        }
+    }
+}
+
+extend_commands!(
+  spawn_revealed(actor: HiddenActor),
+  |world: &mut World, entity_id: Entity, actor: HiddenActor| {
+    let asset_server = world.resource::<AssetServer>().clone();
+    let meshes: Vec<PbrBundle> = actor
+        .meshes
+        .iter()
+        .map(|mesh| PbrBundle {
+            mesh: asset_server.load(mesh.mesh.clone()),
+            material: mesh
+                .material
+                .clone()
+                .map_or_else(default, |material| asset_server.load(material)),
+            transform: Transform {
+                translation: mesh.translation,
+                rotation: mesh.rotation,
+                scale: mesh.scale,
+            },
+            ..default()
+        })
+        .collect();
+
+    world
+        .entity_mut(entity_id)
+        .insert((
+            SpatialBundle::from_transform(Transform {
+                translation: actor.translation,
+                rotation: actor.rotation,
+                scale: actor.scale,
+            }),
+            HiddenToMortals,
+            Revealed,
+            Actor,
+            Name::new(format!("Hidden:{:?}", actor.id)),
+            actor.id,
+        ))
+        .with_children(|parent| {
+            for mesh in meshes {
+                parent.spawn(mesh);
+            }
+        });
+  }
+);
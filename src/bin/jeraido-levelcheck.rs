@@ -0,0 +1,168 @@
+//! Checks a level `.glb` without opening a window.
+//!
+//! ```sh
+//! jeraido-levelcheck asset/level/forest.glb --min-spawn-points 4 --output forest.yaml
+//! ```
+//!
+//! The YAML report is printed to stdout (or written to `--output`),
+//! the exit code is `1` when the level has errors and `2` on invalid arguments.
+
+use std::{
+    env, fmt::Display, fs, path::PathBuf, process::ExitCode, str::FromStr, thread,
+    time::Duration,
+};
+
+use bevy::{
+    log::LogPlugin,
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use jeraido::{LevelCheckPlugins, LevelCheckSettings, LevelReport};
+
+const USAGE: &str = "usage: jeraido-levelcheck <level.glb> [--min-spawn-points N] \
+    [--max-texture-size PX] [--min-height Y] [--output FILE]";
+
+/// Pause between two frames while the level loads.
+const FRAME_DELAY: Duration = Duration::from_millis(10);
+
+struct Args {
+    level: PathBuf,
+    output: Option<PathBuf>,
+    min_spawn_points: Option<usize>,
+    max_texture_size: Option<u32>,
+    min_height: Option<f32>,
+}
+
+fn parse<T: FromStr>(name: &str, value: String) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    value.parse().map_err(|err| format!("{name}: {err}"))
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args().skip(1);
+    let mut level = None;
+    let mut output = None;
+    let mut min_spawn_points = None;
+    let mut max_texture_size = None;
+    let mut min_height = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} expects a value"));
+        match arg.as_str() {
+            "--output" => output = Some(PathBuf::from(value(&arg)?)),
+            "--min-spawn-points" => min_spawn_points = Some(parse(&arg, value(&arg)?)?),
+            "--max-texture-size" => max_texture_size = Some(parse(&arg, value(&arg)?)?),
+            "--min-height" => min_height = Some(parse(&arg, value(&arg)?)?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ if level.is_none() => level = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+
+    Ok(Args {
+        level: level.ok_or("missing level path")?,
+        output,
+        min_spawn_points,
+        max_texture_size,
+        min_height,
+    })
+}
+
+fn main() -> ExitCode {
+    // logs go to stderr, stdout is reserved for the report
+    env_logger::init();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let level = match args.level.canonicalize() {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("{}: {err}", args.level.display());
+            return ExitCode::from(2);
+        }
+    };
+    let (Some(dir), Some(file)) = (level.parent(), level.file_name()) else {
+        eprintln!("{} is not a file", level.display());
+        return ExitCode::from(2);
+    };
+
+    // the level folder is the asset root, so the textures next to it are found
+    let mut settings = LevelCheckSettings::new(file.to_string_lossy());
+    if let Some(min_spawn_points) = args.min_spawn_points {
+        settings.min_spawn_points = min_spawn_points;
+    }
+    if let Some(max_texture_size) = args.max_texture_size {
+        settings.max_texture_size = max_texture_size;
+    }
+    if let Some(min_height) = args.min_height {
+        settings.min_height = min_height;
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins
+            .set(AssetPlugin {
+                file_path: dir.to_string_lossy().into_owned(),
+                ..default()
+            })
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..default()
+                }
+                .into(),
+                ..default()
+            })
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+            })
+            .disable::<WinitPlugin>()
+            .disable::<LogPlugin>(),
+        LevelCheckPlugins,
+    ))
+    .insert_resource(settings);
+
+    app.finish();
+    app.cleanup();
+    let report = loop {
+        app.update();
+        if let Some(report) = app.world.remove_resource::<LevelReport>() {
+            break report;
+        }
+        thread::sleep(FRAME_DELAY);
+    };
+
+    let yaml = match report.to_yaml() {
+        Ok(yaml) => yaml,
+        Err(err) => {
+            eprintln!("failed to serialize the report: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match &args.output {
+        Some(output) => {
+            if let Err(err) = fs::write(output, yaml) {
+                eprintln!("{}: {err}", output.display());
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{yaml}"),
+    }
+
+    if report.passed {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
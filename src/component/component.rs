@@ -1,53 +1,71 @@
-use std::time::Duration;
-
 use bevy::app::{App, PreUpdate, Update};
 use bevy::ecs::entity::Entity;
-use bevy::ecs::event::EventWriter;
-use bevy::ecs::system::{Commands, Query, Res};
+use bevy::ecs::event::{Event, EventWriter};
+use bevy::ecs::query::With;
+use bevy::ecs::system::{Commands, Query, Res, SystemParam};
 use bevy::hierarchy::DespawnRecursiveExt;
-use bevy::prelude::{Component, Deref, DerefMut, Plugin, Vec3};
+use bevy::prelude::{Component, Deref, DerefMut, Name, Plugin, ReflectComponent, Vec3};
 use bevy::reflect::Reflect;
-use bevy::time::{Time, Timer};
+use bevy::time::{Time, Timer, TimerMode};
 use bevy::transform::components::{GlobalTransform, Transform};
+use bevy_rapier3d::prelude::{CollisionGroups, RapierContext, Velocity};
 
-use crate::component::AxisName;
 use crate::lobby::host::DespawnActorEvent;
-use crate::world::{LinkId, SpawnProperty};
+use crate::lobby::Character;
+use crate::world::{CollisionLayer, LinkId, SpawnProperty};
 
-use super::despawn_type::{DespawnReason, IntoDespawnTypeVec};
-use super::SpawnPlugin;
+use super::despawn_type::{
+    CollisionTag, DespawnContext, DespawnReason, DespawnTarget, IntoDespawnTypeVec,
+};
+use super::{GhostPlugin, HealthPlugin, SpawnPlugin, SpawnPoints};
 
 /// A component representing respawn behavior for an entity.
 ///
 /// The [`Respawn`] component is used to control how an entity respawns in a game. It includes information about the respawn reasons,
 /// the spawn point, and a timer value for keeping the entity untouched upon spawn.
-#[derive(Component, Reflect)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct Respawn {
     /// Reasons for respawning.
     reason: Vec<DespawnReason>,
     /// The spawn point for the entity.
     spawn_point: SpawnProperty,
-    /// Duration for keeping the [`CollisionLayers`] into [`noclip`](CollisionLayer::ActorNoclip) [`CollisionLayer`] upon spawn.
+    /// Duration for keeping the [`CollisionGroups`] in the [`noclip`](CollisionLayer::actor_noclip) groups upon spawn.
     noclip: NoclipDuration,
 }
 
-/// An enumeration representing the duration of time an actor will remain [`noclip`](CollisionLayer::ActorNoclip).
+/// An enumeration representing the duration of time an actor will remain [`noclip`](CollisionLayer::actor_noclip).
 ///
-/// The [`NoclipDuration`] enum is used to specify how long an actor should remain [`noclip`](CollisionLayer::ActorNoclip) before some action or event takes place.
-#[derive(PartialEq, Debug, Reflect)]
+/// The [`NoclipDuration`] enum is used to specify how long an actor should remain [`noclip`](CollisionLayer::actor_noclip) before some action or event takes place.
+#[derive(PartialEq, Debug, Default, Reflect)]
 pub enum NoclipDuration {
-    /// Indicates that there is no [`noclip`](CollisionLayer::ActorNoclip) duration, and the actor can be acted upon immediately.
+    /// Indicates that there is no [`noclip`](CollisionLayer::actor_noclip) duration, and the actor can be acted upon immediately.
+    #[default]
     None,
     /// Specifies a timed duration in seconds before the actor can be acted upon.
     Timer(f32),
 }
 
-/// A component representing a timer for a [`noclip`](CollisionLayer::ActorNoclip) mode.
+/// A component representing a timer for a [`noclip`](CollisionLayer::actor_noclip) mode.
 ///
-/// The [`NoclipTimer`] component is used to manage the duration of a [`noclip`](CollisionLayer::ActorNoclip) mode in a game.
+/// The [`NoclipTimer`] component is used to manage the duration of a [`noclip`](CollisionLayer::actor_noclip) mode in a game.
 /// It wraps a [`Timer`] for time tracking and management.
 #[derive(Deref, DerefMut, Component)]
-pub struct NoclipTimer(Timer);
+pub struct NoclipTimer {
+    #[deref]
+    timer: Timer,
+    /// Groups the entity had before the noclip, restored once the timer finishes.
+    restore: Option<CollisionGroups>,
+}
+
+impl NoclipTimer {
+    pub fn new(duration: f32, restore: Option<CollisionGroups>) -> Self {
+        Self {
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+            restore,
+        }
+    }
+}
 
 impl Respawn {
     /// Creates a new `Respawn` instance.
@@ -56,7 +74,7 @@ impl Respawn {
     ///
     /// * `reason` - A container of reasons why the entity might be respawned.
     /// * `spawn_point` - The location where the entity will respawn.
-    /// * `untouched_on_spawn` - Duration for which the entity remains in [`noclip`](CollisionLayer::ActorNoclip) mode upon respawn.
+    /// * `untouched_on_spawn` - Duration for which the entity remains in [`noclip`](CollisionLayer::actor_noclip) mode upon respawn.
     #[allow(dead_code)]
     pub fn new<T: IntoDespawnTypeVec>(
         reason: T,
@@ -117,7 +135,8 @@ impl Respawn {
     }
 }
 
-#[derive(Component, Deref, Reflect)]
+#[derive(Component, Deref, Reflect, Default)]
+#[reflect(Component)]
 pub struct Despawn {
     /// Reasons for respawning.
     reason: Vec<DespawnReason>,
@@ -142,131 +161,219 @@ impl Despawn {
     }
 }
 
+/// Sent when an entity is despawned because of one of its [`Despawn`] reasons.
+///
+/// The entity is gone by the time the event is read.
+#[derive(Event, Debug, Clone)]
+pub struct EntityDespawned {
+    pub entity: Entity,
+    pub reason: DespawnReason,
+}
+
+/// Sent when an entity is moved to a spawn point because of one of its [`Respawn`] reasons.
+///
+/// Clients receive it from the host with a [`DespawnReason::Forced`] reason.
+#[derive(Event, Debug, Clone)]
+pub struct EntityRespawned {
+    pub entity: Entity,
+    pub from: Transform,
+    pub to: Transform,
+    pub reason: DespawnReason,
+}
+
 pub struct ComponentPlugins;
 
 impl Plugin for ComponentPlugins {
     fn build(&self, app: &mut App) {
-        app.add_plugins(SpawnPlugin)
+        app.add_plugins((SpawnPlugin, GhostPlugin, HealthPlugin))
+            .add_event::<EntityDespawned>()
+            .add_event::<EntityRespawned>()
             .add_systems(PreUpdate, (respawn, despawn))
             .add_systems(Update, noclip_timer);
     }
 }
 
-/// Updates entities with a [`NoclipTimer`] component to toggle [`noclip`](CollisionLayer::ActorNoclip) mode temporarily.
+/// Updates entities with a [`NoclipTimer`] component to toggle [`noclip`](CollisionLayer::actor_noclip) mode temporarily.
 ///
 /// The `noclip_timer` function iterates through entities with a [`NoclipTimer`] component and checks if the timer has finished.
-/// If the timer has finished, it adds a specific collision layer to the entity, indicating [`noclip`](CollisionLayer::ActorNoclip) mode, and removes the [`NoclipTimer`] component.
+/// If the timer has finished, it restores the collision groups the entity had before the [`noclip`](CollisionLayer::actor_noclip) mode, and removes the [`NoclipTimer`] component.
 fn noclip_timer(
     mut commands: Commands,
     time: Res<Time>,
     mut query: Query<(Entity, &mut NoclipTimer)>,
 ) {
     for (entity, mut timer) in query.iter_mut() {
-        if timer.0.tick(time.delta()).just_finished() {
-            commands
-                .entity(entity)
-                // TODO:
-                //.insert(CollisionLayers::new(
-                //    [CollisionLayer::Default],
-                //    [CollisionLayer::Default, CollisionLayer::ActorNoclip],
-                //))
-                .remove::<NoclipTimer>();
+        if timer.tick(time.delta()).just_finished() {
+            let mut entity = commands.entity(entity);
+            match timer.restore {
+                Some(groups) => entity.insert(groups),
+                None => entity.remove::<CollisionGroups>(),
+            };
+            entity.remove::<NoclipTimer>();
         }
     }
 }
 
-fn match_reason(
-    reason: &mut [DespawnReason],
-    global_translation: &Vec3,
-    delta_time: &Duration,
-) -> bool {
-    for reason in reason.iter_mut() {
-        if match reason {
-            DespawnReason::Forced => true,
-            DespawnReason::After(ref mut timer) => timer.update(*delta_time).just_finished(),
-            DespawnReason::Less(val, axis) => match axis {
-                AxisName::X => global_translation.x < *val,
-                AxisName::Y => global_translation.y < *val,
-                AxisName::Z => global_translation.z < *val,
-            },
-            DespawnReason::More(val, axis) => match axis {
-                AxisName::X => global_translation.x > *val,
-                AxisName::Y => global_translation.y > *val,
-                AxisName::Z => global_translation.z > *val,
-            },
-        } {
-            return true;
-        }
-    }
+/// Everything the [`DespawnReason`]s look at besides the entity position.
+#[derive(SystemParam)]
+struct ReasonContext<'w, 's> {
+    name_query: Query<'w, 's, (&'static Name, &'static GlobalTransform)>,
+    link_query: Query<'w, 's, (&'static LinkId, &'static GlobalTransform)>,
+    tag_query: Query<'w, 's, &'static CollisionTag>,
+    rapier_context: Res<'w, RapierContext>,
+    time: Res<'w, Time>,
+}
 
-    false
+impl ReasonContext<'_, '_> {
+    /// Returns the first of the reasons that holds.
+    fn match_reason(
+        &self,
+        entity: Entity,
+        global_translation: Vec3,
+        velocity: Option<&Velocity>,
+        reason: &mut [DespawnReason],
+    ) -> Option<DespawnReason> {
+        let position_of = |target: &DespawnTarget| {
+            let transform = match target {
+                DespawnTarget::Name(name) => self
+                    .name_query
+                    .iter()
+                    .find_map(|(other, transform)| (other.as_str() == name).then_some(transform)),
+                DespawnTarget::Link(id) => self
+                    .link_query
+                    .iter()
+                    .find_map(|(other, transform)| (other == id).then_some(transform)),
+            };
+            transform.map(|transform| transform.translation())
+        };
+        let touches = |tag: &str| {
+            let other = |a: Entity, b: Entity| if a == entity { b } else { a };
+            self.rapier_context
+                .contact_pairs_with(entity)
+                .filter(|pair| pair.has_any_active_contacts())
+                .map(|pair| other(pair.collider1(), pair.collider2()))
+                .chain(
+                    self.rapier_context
+                        .intersection_pairs_with(entity)
+                        .filter(|(_, _, intersecting)| *intersecting)
+                        .map(|(a, b, _)| other(a, b)),
+                )
+                .any(|other| self.tag_query.get(other).is_ok_and(|t| t.0 == tag))
+        };
+        let context = DespawnContext {
+            translation: global_translation,
+            velocity: velocity.map(|velocity| velocity.linvel),
+            position_of: &position_of,
+            touches: &touches,
+        };
+
+        let delta = self.time.delta();
+        reason
+            .iter_mut()
+            .find(|reason| reason.holds(&context, delta))
+            .cloned()
+    }
 }
 
 /// Processes a [`Entity`] with [`Respawn`] [`Component`]
 ///
-/// Move actors on respawn position and optionally rest [`LinearVelocity`] and [`AngularVelocity`]
+/// Move actors on respawn position and optionally rest [`Velocity`]
 /// if one of `reason` ([`DespawnReason`]) is true
+#[allow(clippy::type_complexity)]
 fn respawn(
     mut commands: Commands,
-    mut respawn_query: Query<(&mut Respawn, &mut Transform, &GlobalTransform, Entity)>,
-    // TODO: mut velocity_query: Query<(&mut LinearVelocity, &mut AngularVelocity), With<Respawn>>,
-    time: Res<Time>,
+    mut respawn_query: Query<(
+        &mut Respawn,
+        &mut Transform,
+        &GlobalTransform,
+        Option<&mut Velocity>,
+        Option<&CollisionGroups>,
+        Option<&NoclipTimer>,
+        Entity,
+    )>,
+    character_query: Query<(Entity, &GlobalTransform), With<Character>>,
+    mut spawn_points: SpawnPoints,
+    reason_context: ReasonContext,
+    mut respawned_event: EventWriter<EntityRespawned>,
 ) {
-    for (mut respawn, mut transform, global_transform, entity) in respawn_query.iter_mut() {
-        if !match_reason(
+    for (mut respawn, mut transform, global_transform, velocity, groups, noclip, entity) in
+        respawn_query.iter_mut()
+    {
+        let Some(reason) = reason_context.match_reason(
+            entity,
+            global_transform.translation(),
+            velocity.as_deref(),
             &mut respawn.reason,
-            &global_transform.translation(),
-            &time.delta(),
-        ) {
+        ) else {
             continue;
-        }
+        };
+
+        let enemies = character_query
+            .iter()
+            .filter(|(character, _)| *character != entity)
+            .map(|(_, transform)| transform.translation())
+            .collect::<Vec<_>>();
+        let Some(spawn) = spawn_points.select(&respawn.spawn_point, &enemies) else {
+            // the reason holds, retried once the level has spawn points
+            continue;
+        };
 
         if let NoclipDuration::Timer(val) = respawn.noclip {
-            commands
-                .entity(entity)
-                .insert(NoclipTimer(Timer::from_seconds(
-                    val,
-                    bevy::time::TimerMode::Once,
-                )))
-                // TODO:
-                //.insert(CollisionLayers::new(
-                //    [CollisionLayer::ActorNoclip],
-                //    [CollisionLayer::Default],
-                //))
-                ;
+            // respawned again while in noclip, the groups from before are kept
+            let restore = match noclip {
+                Some(noclip) => noclip.restore,
+                None => groups.copied(),
+            };
+            commands.entity(entity).insert((
+                NoclipTimer::new(val, restore),
+                CollisionLayer::actor_noclip(),
+            ));
+        }
+        let from = *transform;
+        transform.translation = spawn.translation;
+        transform.rotation = spawn.rotation;
+        respawned_event.send(EntityRespawned {
+            entity,
+            from,
+            to: *transform,
+            reason,
+        });
+        if let Some(mut velocity) = velocity {
+            *velocity = Velocity::zero();
         }
-        transform.translation = respawn.spawn_point.random_point();
-        // TODO:
-        // if let Ok((mut linear_velocity, mut angular_velocity)) = velocity_query.get_mut(entity) {
-        //     linear_velocity.0 = Vec3::ZERO;
-        //     angular_velocity.0 = Vec3::ZERO;
-        // }
 
-        respawn
-            .reason
-            .retain(|reason| reason != &DespawnReason::Forced);
+        respawn.reason.retain(|reason| !reason.is_forced());
     }
 }
 
 fn despawn(
     mut commands: Commands,
-    mut despawn_query: Query<(&mut Despawn, &GlobalTransform, Option<&LinkId>, Entity)>,
+    mut despawn_query: Query<(
+        &mut Despawn,
+        &GlobalTransform,
+        Option<&Velocity>,
+        Option<&LinkId>,
+        Entity,
+    )>,
     mut despawn_actor_event: EventWriter<DespawnActorEvent>,
-    time: Res<Time>,
+    mut despawned_event: EventWriter<EntityDespawned>,
+    reason_context: ReasonContext,
 ) {
-    for (mut respawn, global_transform, id_option, entity) in despawn_query.iter_mut() {
-        if !match_reason(
+    for (mut respawn, global_transform, velocity, id_option, entity) in despawn_query.iter_mut() {
+        let Some(reason) = reason_context.match_reason(
+            entity,
+            global_transform.translation(),
+            velocity,
             &mut respawn.reason,
-            &global_transform.translation(),
-            &time.delta(),
-        ) {
+        ) else {
             continue;
-        }
+        };
 
         if let Some(id) = id_option {
             despawn_actor_event.send(DespawnActorEvent(id.clone()));
         }
 
+        despawned_event.send(EntityDespawned { entity, reason });
         commands.entity(entity).despawn_recursive();
     }
 }
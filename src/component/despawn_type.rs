@@ -1,29 +1,157 @@
 use std::time::Duration;
 
 use bevy::{
-    reflect::Reflect,
+    ecs::component::Component,
+    math::Vec3,
+    prelude::ReflectComponent,
+    reflect::{Reflect, ReflectDeserialize, ReflectSerialize},
     time::{Timer, TimerMode},
 };
+use serde::{Deserialize, Serialize};
+
+use crate::world::LinkId;
+
+use super::DeathCause;
 
 /// An enumeration representing various reasons for despawning an entity.
 ///
 /// The [`DespawnReason`] enum is used to indicate different conditions or events that lead to the despawning of an entity.
-#[derive(Debug, Clone, PartialEq, Reflect)]
+///
+/// Reflected as a value, so the combinators may nest and levels author it in the serde form,
+/// e.g. `All([Less(-10.0, Y), FasterThan(30.0)])`.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect_value(Debug, PartialEq, Serialize, Deserialize)]
 pub enum DespawnReason {
     /// Indicates that the entity was forcefully despawned. After been removed if object must respawn ([`Respawn`](crate::component::Respawn))
     Forced,
+    /// Specifies that the entity was despawned because its [`Health`](crate::component::Health) ran out, removed like [`DespawnReason::Forced`].
+    #[serde(skip)]
+    Killed(DeathCause),
     /// Specifies that the entity was despawned because it exceeded a certain value along a specific axis.
     More(f32, AxisName),
     /// Specifies that the entity was despawned because it fell below a certain value along a specific axis.
     Less(f32, AxisName),
     /// Specifies that the entity was despawned after timeout.
     After(DespawnTimer),
+    InsideSphere {
+        center: Vec3,
+        radius: f32,
+    },
+    OutsideSphere {
+        center: Vec3,
+        radius: f32,
+    },
+    InsideBox {
+        min: Vec3,
+        max: Vec3,
+    },
+    OutsideBox {
+        min: Vec3,
+        max: Vec3,
+    },
+    /// Closer to the target than the distance.
+    CloserTo(DespawnTarget, f32),
+    /// Farther from the target than the distance, never holds once the target is gone.
+    FartherFrom(DespawnTarget, f32),
+    /// Faster than the speed, never holds without a [`Velocity`](bevy_rapier3d::prelude::Velocity).
+    FasterThan(f32),
+    /// Touches an entity with the [`CollisionTag`].
+    CollidedWith(String),
+    /// Specifies that the entity was despawned because all of the reasons held.
+    All(Vec<DespawnReason>),
+    /// Specifies that the entity was despawned because one of the reasons held.
+    Any(Vec<DespawnReason>),
+    /// Specifies that the entity was despawned because the reason did not hold.
+    Not(Box<DespawnReason>),
+}
+
+impl DespawnReason {
+    /// Returns `true` if the entity described by `context` must be despawned,
+    /// `delta` advances the [`DespawnReason::After`] timers.
+    pub fn holds(&mut self, context: &DespawnContext, delta: Duration) -> bool {
+        let translation = context.translation;
+        let inside_box =
+            |min: &Vec3, max: &Vec3| translation.cmpge(*min).all() && translation.cmple(*max).all();
+        match self {
+            Self::Forced | Self::Killed(_) => true,
+            Self::After(ref mut timer) => timer.update(delta).just_finished(),
+            Self::More(val, axis) => axis.of(translation) > *val,
+            Self::Less(val, axis) => axis.of(translation) < *val,
+            Self::InsideSphere { center, radius } => {
+                translation.distance_squared(*center) <= *radius * *radius
+            }
+            Self::OutsideSphere { center, radius } => {
+                translation.distance_squared(*center) > *radius * *radius
+            }
+            Self::InsideBox { min, max } => inside_box(min, max),
+            Self::OutsideBox { min, max } => !inside_box(min, max),
+            Self::CloserTo(target, distance) => (context.position_of)(target)
+                .is_some_and(|position| position.distance(translation) < *distance),
+            Self::FartherFrom(target, distance) => (context.position_of)(target)
+                .is_some_and(|position| position.distance(translation) > *distance),
+            Self::FasterThan(speed) => context
+                .velocity
+                .is_some_and(|velocity| velocity.length() > *speed),
+            Self::CollidedWith(tag) => (context.touches)(tag),
+            // every nested timer ticks, even once the result is known
+            Self::All(reasons) => reasons
+                .iter_mut()
+                .fold(true, |all, reason| reason.holds(context, delta) && all),
+            Self::Any(reasons) => reasons
+                .iter_mut()
+                .fold(false, |any, reason| reason.holds(context, delta) || any),
+            Self::Not(reason) => !reason.holds(context, delta),
+        }
+    }
+
+    /// Returns `true` for the reasons that hold once and are removed after the respawn.
+    pub fn is_forced(&self) -> bool {
+        matches!(self, Self::Forced | Self::Killed(_))
+    }
 }
 
+/// Entity a [`DespawnReason`] measures the distance to, by what a level can author.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DespawnTarget {
+    /// The first entity with the [`Name`](bevy::core::Name).
+    Name(String),
+    Link(LinkId),
+}
+
+/// State of an entity the [`DespawnReason`]s are checked against.
+pub struct DespawnContext<'a> {
+    pub translation: Vec3,
+    pub velocity: Option<Vec3>,
+    /// Global position of another entity.
+    pub position_of: &'a dyn Fn(&DespawnTarget) -> Option<Vec3>,
+    /// Whether the entity touches one with the [`CollisionTag`].
+    pub touches: &'a dyn Fn(&str) -> bool,
+}
+
+/// Marks an entity for [`DespawnReason::CollidedWith`].
+#[derive(Component, Reflect, Default, Debug, Clone, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct CollisionTag(pub String);
+
 /// A timer used to despawn an entity after a certain amount of time.
-#[derive(Debug, Clone, PartialEq, Reflect)]
+///
+/// Authored as its duration in seconds.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(from = "f32", into = "f32")]
 pub struct DespawnTimer(Timer);
 
+impl From<f32> for DespawnTimer {
+    fn from(duration: f32) -> Self {
+        Self::new(duration)
+    }
+}
+
+impl From<DespawnTimer> for f32 {
+    fn from(timer: DespawnTimer) -> Self {
+        timer.0.duration().as_secs_f32()
+    }
+}
+
 impl DespawnTimer {
     /// Creates a new [`DespawnTimer`] with the specified duration.
     pub fn new(duration: f32) -> Self {
@@ -45,13 +173,24 @@ impl DespawnTimer {
 /// An enumeration representing axis names.
 ///
 /// The [`AxisName`] enum is used to specify the names of different axes in 3D space.
-#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
 pub enum AxisName {
     X,
     Y,
     Z,
 }
 
+impl AxisName {
+    /// Returns the coordinate of `vector` along the axis.
+    pub fn of(self, vector: Vec3) -> f32 {
+        match self {
+            AxisName::X => vector.x,
+            AxisName::Y => vector.y,
+            AxisName::Z => vector.z,
+        }
+    }
+}
+
 pub trait IntoDespawnTypeVec {
     fn into_despawn_type_vec(self) -> Vec<DespawnReason>;
 }
@@ -231,3 +370,208 @@ impl<
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::DamageType;
+
+    const STEP: Duration = Duration::from_millis(100);
+
+    fn beacon_position(target: &DespawnTarget) -> Option<Vec3> {
+        match target {
+            DespawnTarget::Name(name) if name == "Beacon" => Some(Vec3::new(10., 0., 0.)),
+            DespawnTarget::Link(LinkId::Scene(name)) if name == "beacon" => {
+                Some(Vec3::new(10., 0., 0.))
+            }
+            _ => None,
+        }
+    }
+
+    fn touches_lava(tag: &str) -> bool {
+        tag == "Lava"
+    }
+
+    fn at(translation: Vec3) -> DespawnContext<'static> {
+        DespawnContext {
+            translation,
+            velocity: Some(Vec3::new(0., 0., 5.)),
+            position_of: &beacon_position,
+            touches: &touches_lava,
+        }
+    }
+
+    fn holds(mut reason: DespawnReason, translation: Vec3) -> bool {
+        reason.holds(&at(translation), STEP)
+    }
+
+    #[test]
+    fn forced_and_killed_always_hold() {
+        let killed = DespawnReason::Killed(DeathCause {
+            kind: DamageType::default(),
+            source: None,
+        });
+        assert!(holds(DespawnReason::Forced, Vec3::ZERO));
+        assert!(holds(killed.clone(), Vec3::ZERO));
+        assert!(DespawnReason::Forced.is_forced() && killed.is_forced());
+        assert!(!DespawnReason::FasterThan(1.).is_forced());
+    }
+
+    #[test]
+    fn more_and_less() {
+        assert!(holds(DespawnReason::More(1., AxisName::X), Vec3::X * 2.));
+        assert!(!holds(DespawnReason::More(1., AxisName::X), Vec3::Y * 2.));
+        assert!(holds(
+            DespawnReason::Less(-5., AxisName::Y),
+            Vec3::NEG_Y * 6.
+        ));
+        assert!(!holds(
+            DespawnReason::Less(-5., AxisName::Y),
+            Vec3::NEG_Y * 4.
+        ));
+    }
+
+    #[test]
+    fn after() {
+        let mut reason = DespawnReason::After(DespawnTimer::new(0.25));
+        let context = at(Vec3::ZERO);
+        assert!(!reason.holds(&context, STEP));
+        assert!(!reason.holds(&context, STEP));
+        assert!(reason.holds(&context, STEP));
+    }
+
+    #[test]
+    fn spheres() {
+        let center = Vec3::new(0., 0., 3.);
+        let inside = DespawnReason::InsideSphere { center, radius: 1. };
+        let outside = DespawnReason::OutsideSphere { center, radius: 1. };
+        assert!(holds(inside.clone(), Vec3::new(0., 0.5, 3.)));
+        assert!(!holds(inside, Vec3::ZERO));
+        assert!(holds(outside.clone(), Vec3::ZERO));
+        assert!(!holds(outside, Vec3::new(0., 0.5, 3.)));
+    }
+
+    #[test]
+    fn boxes() {
+        let (min, max) = (Vec3::splat(-1.), Vec3::splat(1.));
+        let inside = DespawnReason::InsideBox { min, max };
+        let outside = DespawnReason::OutsideBox { min, max };
+        assert!(holds(inside.clone(), Vec3::splat(0.5)));
+        assert!(!holds(inside, Vec3::new(0., 2., 0.)));
+        assert!(holds(outside.clone(), Vec3::new(0., 2., 0.)));
+        assert!(!holds(outside, Vec3::splat(0.5)));
+    }
+
+    #[test]
+    fn closer_to() {
+        let by_name = DespawnReason::CloserTo(DespawnTarget::Name("Beacon".into()), 2.);
+        let by_link =
+            DespawnReason::CloserTo(DespawnTarget::Link(LinkId::Scene("beacon".into())), 2.);
+        assert!(holds(by_name.clone(), Vec3::new(9., 0., 0.)));
+        assert!(holds(by_link, Vec3::new(9., 0., 0.)));
+        assert!(!holds(by_name, Vec3::ZERO));
+        let gone = DespawnReason::CloserTo(DespawnTarget::Name("Gone".into()), 100.);
+        assert!(!holds(gone, Vec3::ZERO));
+    }
+
+    #[test]
+    fn farther_from() {
+        let by_name = DespawnReason::FartherFrom(DespawnTarget::Name("Beacon".into()), 2.);
+        let by_link =
+            DespawnReason::FartherFrom(DespawnTarget::Link(LinkId::Scene("beacon".into())), 2.);
+        assert!(holds(by_name.clone(), Vec3::ZERO));
+        assert!(holds(by_link, Vec3::ZERO));
+        assert!(!holds(by_name, Vec3::new(9., 0., 0.)));
+        let gone = DespawnReason::FartherFrom(DespawnTarget::Name("Gone".into()), 0.);
+        assert!(!holds(gone, Vec3::ZERO));
+    }
+
+    #[test]
+    fn faster_than() {
+        assert!(holds(DespawnReason::FasterThan(4.), Vec3::ZERO));
+        assert!(!holds(DespawnReason::FasterThan(6.), Vec3::ZERO));
+        let mut reason = DespawnReason::FasterThan(0.);
+        let still = DespawnContext {
+            velocity: None,
+            ..at(Vec3::ZERO)
+        };
+        assert!(!reason.holds(&still, STEP));
+    }
+
+    #[test]
+    fn collided_with() {
+        assert!(holds(
+            DespawnReason::CollidedWith("Lava".into()),
+            Vec3::ZERO
+        ));
+        assert!(!holds(
+            DespawnReason::CollidedWith("Water".into()),
+            Vec3::ZERO
+        ));
+    }
+
+    #[test]
+    fn all() {
+        let above = DespawnReason::More(0., AxisName::Y);
+        let fast = DespawnReason::FasterThan(1.);
+        let slow = DespawnReason::Not(Box::new(fast.clone()));
+        assert!(holds(
+            DespawnReason::All(vec![above.clone(), fast]),
+            Vec3::Y
+        ));
+        assert!(!holds(DespawnReason::All(vec![above, slow]), Vec3::Y));
+        assert!(holds(DespawnReason::All(Vec::new()), Vec3::ZERO));
+    }
+
+    #[test]
+    fn any() {
+        let below = DespawnReason::Less(0., AxisName::Y);
+        let fast = DespawnReason::FasterThan(1.);
+        assert!(holds(
+            DespawnReason::Any(vec![below.clone(), fast]),
+            Vec3::Y
+        ));
+        assert!(!holds(DespawnReason::Any(vec![below]), Vec3::Y));
+        assert!(!holds(DespawnReason::Any(Vec::new()), Vec3::ZERO));
+    }
+
+    #[test]
+    fn not() {
+        let lava = DespawnReason::CollidedWith("Lava".into());
+        let water = DespawnReason::CollidedWith("Water".into());
+        assert!(!holds(DespawnReason::Not(Box::new(lava)), Vec3::ZERO));
+        assert!(holds(DespawnReason::Not(Box::new(water)), Vec3::ZERO));
+    }
+
+    #[test]
+    fn nested_timers_tick_together() {
+        // the timer keeps ticking while the other reason fails
+        let mut reason = DespawnReason::All(vec![
+            DespawnReason::Any(vec![
+                DespawnReason::Less(-1., AxisName::Y),
+                DespawnReason::Not(Box::new(DespawnReason::InsideBox {
+                    min: Vec3::splat(-1.),
+                    max: Vec3::splat(1.),
+                })),
+            ]),
+            DespawnReason::After(DespawnTimer::new(0.25)),
+        ]);
+        assert!(!reason.holds(&at(Vec3::ZERO), STEP));
+        assert!(!reason.holds(&at(Vec3::ZERO), STEP));
+        assert!(reason.holds(&at(Vec3::splat(2.)), STEP));
+    }
+
+    #[test]
+    fn nested_reasons_round_trip() {
+        let reason = DespawnReason::Any(vec![
+            DespawnReason::All(vec![
+                DespawnReason::FartherFrom(DespawnTarget::Name("Beacon".into()), 20.),
+                DespawnReason::After(DespawnTimer::new(3.)),
+            ]),
+            DespawnReason::Not(Box::new(DespawnReason::CollidedWith("Safe".into()))),
+        ]);
+        let authored = serde_yaml::to_string(&reason).unwrap();
+        let parsed: DespawnReason = serde_yaml::from_str(&authored).unwrap();
+        assert_eq!(parsed, reason);
+    }
+}
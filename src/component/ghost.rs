@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+
+use super::NoclipTimer;
+
+/// Opacity of an actor in noclip, it flickers around it.
+const GHOST_ALPHA: f32 = 0.4;
+const GHOST_FLICKER: f32 = 0.15;
+const GHOST_FLICKER_SPEED: f32 = 12.;
+
+/// A mesh of an actor in noclip, drawn with a translucent copy of its material.
+#[derive(Component)]
+struct Ghost {
+    original: Handle<StandardMaterial>,
+}
+
+/// Shows actors in noclip as translucent ghosts.
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (ghost_noclip, flicker_ghosts, unghost).chain());
+    }
+}
+
+fn ghost_noclip(
+    mut commands: Commands,
+    noclip_query: Query<Entity, Added<NoclipTimer>>,
+    children_query: Query<&Children>,
+    mut material_query: Query<&mut Handle<StandardMaterial>, Without<Ghost>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for entity in noclip_query.iter() {
+        for mesh in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            let Ok(mut handle) = material_query.get_mut(mesh) else {
+                continue;
+            };
+            // the material may be shared with actors that are not in noclip
+            let Some(mut ghost) = materials.get(&*handle).cloned() else {
+                continue;
+            };
+            ghost.alpha_mode = AlphaMode::Blend;
+            ghost.base_color.set_a(GHOST_ALPHA);
+            let original = std::mem::replace(&mut *handle, materials.add(ghost));
+            commands.entity(mesh).insert(Ghost { original });
+        }
+    }
+}
+
+fn flicker_ghosts(
+    ghost_query: Query<&Handle<StandardMaterial>, With<Ghost>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time: Res<Time>,
+) {
+    let alpha = GHOST_ALPHA + GHOST_FLICKER * (time.elapsed_seconds() * GHOST_FLICKER_SPEED).sin();
+    for handle in ghost_query.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color.set_a(alpha);
+        }
+    }
+}
+
+fn unghost(
+    mut commands: Commands,
+    mut removed_noclip: RemovedComponents<NoclipTimer>,
+    children_query: Query<&Children>,
+    mut ghost_query: Query<(&Ghost, &mut Handle<StandardMaterial>)>,
+) {
+    for entity in removed_noclip.read() {
+        for mesh in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            if let Ok((ghost, mut handle)) = ghost_query.get_mut(mesh) {
+                *handle = ghost.original.clone();
+                commands.entity(mesh).remove::<Ghost>();
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::lobby::LobbyState;
+
+use super::{Despawn, DespawnReason, EntityRespawned, Respawn};
+
+/// Kind of a [`DamageEvent`], [`Resistances`] are set by it.
+#[derive(Reflect, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Arcane,
+    Holy,
+    /// Ignores the resistances.
+    Pure,
+}
+
+/// Why an actor died, carried by [`DespawnReason::Killed`].
+#[derive(Reflect, Debug, Clone, PartialEq)]
+pub struct DeathCause {
+    pub kind: DamageType,
+    /// Entity that dealt the last damage, if any.
+    pub source: Option<Entity>,
+}
+
+#[derive(Component, Reflect, Debug, Clone, PartialEq)]
+#[reflect(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(100.)
+    }
+}
+
+/// Damage an actor deals to what it hits.
+#[derive(Component, Reflect, Serialize, Deserialize, Debug, Clone, Default)]
+#[reflect(Component)]
+pub struct Damage {
+    pub amount: f32,
+    pub kind: DamageType,
+}
+
+/// Share of the damage of a type that is ignored, a negative one takes more.
+#[derive(Component, Reflect, Debug, Clone, Default, Deref, DerefMut)]
+#[reflect(Component)]
+pub struct Resistances(pub HashMap<DamageType, f32>);
+
+impl Resistances {
+    /// Damage taken from `amount` of the type.
+    pub fn apply(&self, kind: DamageType, amount: f32) -> f32 {
+        if kind == DamageType::Pure {
+            return amount;
+        }
+        let resistance = self.0.get(&kind).copied().unwrap_or(0.).min(1.);
+        amount * (1. - resistance)
+    }
+}
+
+/// Lowers the damage of the hits coming from the front arc, like a raised shield.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct Guard {
+    /// Direction the front arc is centered on.
+    pub facing: Vec3,
+    /// Width of the front arc in degrees.
+    pub arc: f32,
+    /// Share of the damage that is ignored.
+    pub reduction: f32,
+}
+
+impl Guard {
+    /// A hit travelling along `direction` comes from the front arc.
+    pub fn covers(&self, direction: Vec3) -> bool {
+        self.facing.angle_between(-direction) <= (self.arc / 2.).to_radians()
+    }
+
+    /// Damage taken from `amount` of a hit travelling along `direction`.
+    pub fn apply(&self, direction: Vec3, amount: f32) -> f32 {
+        if self.covers(direction) {
+            amount * (1. - self.reduction.clamp(0., 1.))
+        } else {
+            amount
+        }
+    }
+}
+
+/// Hurts the target, only the host applies it.
+#[derive(Event, Debug, Clone)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    pub kind: DamageType,
+    pub amount: f32,
+    /// Direction the hit travels, a [`Guard`] only stops the ones that have it.
+    pub direction: Option<Vec3>,
+}
+
+impl DamageEvent {
+    pub fn new(target: Entity, source: Option<Entity>, damage: &Damage) -> Self {
+        Self {
+            target,
+            source,
+            kind: damage.kind,
+            amount: damage.amount,
+            direction: None,
+        }
+    }
+
+    pub fn with_direction(mut self, direction: Vec3) -> Self {
+        self.direction = direction.try_normalize();
+        self
+    }
+}
+
+/// Health of the actors, the host applies the damage and the clients follow it.
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .register_type::<Guard>()
+            .add_systems(
+                Update,
+                (apply_damage, heal_respawned).run_if(not(in_state(LobbyState::Client))),
+            );
+    }
+}
+
+/// Takes the damage off the health, the dead go through their [`Respawn`] or [`Despawn`].
+fn apply_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    mut health_query: Query<(
+        &mut Health,
+        Option<&Resistances>,
+        Option<&Guard>,
+        Option<&mut Respawn>,
+        Option<&mut Despawn>,
+    )>,
+) {
+    for event in damage_events.read() {
+        let Ok((mut health, resistances, guard, respawn, despawn)) =
+            health_query.get_mut(event.target)
+        else {
+            continue;
+        };
+        // dead until the respawn heals it
+        if health.is_dead() {
+            continue;
+        }
+        let mut amount = event.amount;
+        if let (Some(guard), Some(direction)) = (guard, event.direction) {
+            amount = guard.apply(direction, amount);
+        }
+        if let Some(resistances) = resistances {
+            amount = resistances.apply(event.kind, amount);
+        }
+        health.current = (health.current - amount).clamp(0., health.max);
+        if !health.is_dead() {
+            continue;
+        }
+
+        log::info!(
+            "{:?} killed by {:?} ({:?})",
+            event.target,
+            event.source,
+            event.kind
+        );
+        let reason = DespawnReason::Killed(DeathCause {
+            kind: event.kind,
+            source: event.source,
+        });
+        if let Some(mut respawn) = respawn {
+            respawn.insert_reason(reason);
+        } else if let Some(mut despawn) = despawn {
+            despawn.insert_reason(reason);
+        } else {
+            commands.entity(event.target).insert(Despawn::new(reason));
+        }
+    }
+}
+
+fn heal_respawned(
+    mut respawned_events: EventReader<EntityRespawned>,
+    mut health_query: Query<&mut Health>,
+) {
+    for event in respawned_events.read() {
+        if let Ok(mut health) = health_query.get_mut(event.entity) {
+            health.current = health.max;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> Guard {
+        Guard {
+            facing: Vec3::Z,
+            arc: 90.,
+            reduction: 0.75,
+        }
+    }
+
+    #[test]
+    fn guard_covers_the_front_arc() {
+        let guard = guard();
+        // travelling toward the guard
+        assert!(guard.covers(Vec3::NEG_Z));
+        assert!(guard.covers(Vec3::new(0.5, 0., -1.).normalize()));
+        assert!(!guard.covers(Vec3::new(2., 0., -1.).normalize()));
+        assert!(!guard.covers(Vec3::NEG_X));
+        assert!(!guard.covers(Vec3::Z));
+    }
+
+    #[test]
+    fn guard_reduces_covered_hits_only() {
+        let guard = guard();
+        assert_eq!(guard.apply(Vec3::NEG_Z, 40.), 10.);
+        assert_eq!(guard.apply(Vec3::Z, 40.), 40.);
+
+        let wall = Guard {
+            reduction: 1.5,
+            ..guard
+        };
+        assert_eq!(wall.apply(Vec3::NEG_Z, 40.), 0.);
+    }
+}
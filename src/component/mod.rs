@@ -2,9 +2,13 @@
 
 mod component;
 mod despawn_type;
+mod ghost;
+mod health;
 mod test_component;
 mod spawn;
 pub use component::*;
 pub use despawn_type::*;
+pub use ghost::*;
+pub use health::*;
 pub use test_component::*;
 pub use spawn::*;
@@ -1,33 +1,178 @@
+use std::time::Duration;
+
 use bevy::{
     app::{App, Plugin, Update},
-    ecs::{component::Component, query::With, system::{Query, ResMut, Commands}, entity::Entity},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::Added,
+        system::{Commands, Query, Res, ResMut, SystemParam},
+    },
+    math::Vec3,
+    prelude::ReflectComponent,
     reflect::Reflect,
-    transform::components::GlobalTransform,
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
 };
+use rand::Rng;
 
-use crate::world;
+use crate::world::{SpawnLocation, SpawnProperty, SpawnStrategy};
 
-#[derive(Component, Reflect, Default, Debug)]
-pub struct SpawnPoint;
+/// Who or when a [`SpawnPoint`] is meant for.
+#[derive(Reflect, Debug, Clone, PartialEq, Eq)]
+pub enum SpawnTag {
+    Team(u8),
+    /// A role such as a character class.
+    Role(String),
+    /// The first spawn on a level.
+    Start,
+    Respawn,
+}
+
+/// A place actors spawn at, it stays in the level and may move with it.
+#[derive(Component, Reflect, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct SpawnPoint {
+    /// Points without a tag fit every actor.
+    #[reflect(default)]
+    pub tag: Option<SpawnTag>,
+    /// Direction the spawned actor faces, the node forward if unset.
+    #[reflect(default)]
+    pub facing: Option<Vec3>,
+}
+
+/// How often and when a [`SpawnPoint`] was used.
+#[derive(Component, Default, Debug)]
+pub struct SpawnPointUsage {
+    pub uses: u32,
+    pub last_used: Option<Duration>,
+}
 
 pub struct SpawnPlugin;
 
 impl Plugin for SpawnPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(world::SpawnProperty::empty())
-      // TODO: process_spawn_point in state?
-            .register_type::<SpawnPoint>().add_systems(Update, process_spawn_point);
+        app.insert_resource(SpawnProperty::empty())
+            .add_systems(Update, register_spawn_point);
     }
 }
 
-fn process_spawn_point(
-  mut commands: Commands,
-  query: Query<(Entity, &GlobalTransform), With<SpawnPoint>>,
-  mut resource: ResMut<world::SpawnProperty>,
+/// Adds new spawn points to the level [`SpawnProperty`].
+fn register_spawn_point(
+    mut commands: Commands,
+    query: Query<Entity, Added<SpawnPoint>>,
+    mut resource: ResMut<SpawnProperty>,
 ) {
-    // TODO: spawn point not only like vec3 but like entity (moveble point)
-    for (entity, global_transform) in &query {
-         resource.push(global_transform.translation());
-         commands.entity(entity).despawn(); // TODO: ugly realization
+    for entity in &query {
+        resource.push(SpawnLocation::Point(entity));
+        commands.entity(entity).insert(SpawnPointUsage::default());
+    }
+}
+
+struct Candidate {
+    transform: Transform,
+    tag: Option<SpawnTag>,
+    point: Option<Entity>,
+    last_used: Option<Duration>,
+    uses: u32,
+}
+
+/// Picks the locations of a [`SpawnProperty`].
+#[derive(SystemParam)]
+pub struct SpawnPoints<'w, 's> {
+    point_query: Query<
+        'w,
+        's,
+        (
+            &'static SpawnPoint,
+            &'static GlobalTransform,
+            Option<&'static mut SpawnPointUsage>,
+        ),
+    >,
+    time: Res<'w, Time>,
+}
+
+impl SpawnPoints<'_, '_> {
+    /// Picks a location by the strategy of the property and marks it used.
+    ///
+    /// `enemies` are the positions [`SpawnStrategy::FarthestFromEnemies`] keeps away from.
+    /// Returns `None` if none of the locations exists.
+    pub fn select(&mut self, property: &SpawnProperty, enemies: &[Vec3]) -> Option<Transform> {
+        let candidates = property
+            .locations()
+            .iter()
+            .filter_map(|location| self.candidate(location))
+            .collect::<Vec<_>>();
+
+        let fits = |candidate: &&Candidate| {
+            candidate.tag.is_none()
+                || property.tag().is_none()
+                || candidate.tag.as_ref() == property.tag()
+        };
+        let mut fitting = candidates.iter().filter(fits).collect::<Vec<_>>();
+        if fitting.is_empty() {
+            fitting = candidates.iter().collect();
+        }
+
+        let closest_enemy = |candidate: &Candidate| {
+            enemies
+                .iter()
+                .map(|enemy| enemy.distance_squared(candidate.transform.translation))
+                .fold(f32::INFINITY, f32::min)
+        };
+        let selected = match property.strategy() {
+            SpawnStrategy::Random => None,
+            // ties go to the first point, so the points take turns
+            SpawnStrategy::RoundRobin => fitting.iter().min_by_key(|candidate| candidate.uses),
+            SpawnStrategy::FarthestFromEnemies if enemies.is_empty() => None,
+            SpawnStrategy::FarthestFromEnemies => fitting
+                .iter()
+                .max_by(|a, b| closest_enemy(**a).total_cmp(&closest_enemy(**b))),
+            SpawnStrategy::LeastRecentlyUsed => {
+                fitting.iter().min_by_key(|candidate| candidate.last_used)
+            }
+        }
+        .copied()
+        .or_else(|| {
+            (!fitting.is_empty()).then(|| fitting[rand::thread_rng().gen_range(0..fitting.len())])
+        })?;
+
+        if let Some(entity) = selected.point {
+            if let Ok((_, _, Some(mut usage))) = self.point_query.get_mut(entity) {
+                usage.uses += 1;
+                usage.last_used = Some(self.time.elapsed());
+            }
+        }
+        Some(selected.transform)
+    }
+
+    fn candidate(&self, location: &SpawnLocation) -> Option<Candidate> {
+        match *location {
+            SpawnLocation::Fixed(transform) => Some(Candidate {
+                transform,
+                tag: None,
+                point: None,
+                last_used: None,
+                uses: 0,
+            }),
+            SpawnLocation::Point(entity) => {
+                let (point, global_transform, usage) = self.point_query.get(entity).ok()?;
+                let (_, rotation, translation) = global_transform.to_scale_rotation_translation();
+                // actors stay upright, only the heading is kept
+                let facing = point.facing.unwrap_or(rotation * Vec3::NEG_Z);
+                let heading = Vec3::new(facing.x, 0., facing.z);
+                let mut transform = Transform::from_translation(translation);
+                if heading.length_squared() > f32::EPSILON {
+                    transform = transform.looking_to(heading, Vec3::Y);
+                }
+                Some(Candidate {
+                    transform,
+                    tag: point.tag.clone(),
+                    point: Some(entity),
+                    last_used: usage.and_then(|usage| usage.last_used),
+                    uses: usage.map_or(0, |usage| usage.uses),
+                })
+            }
+        }
     }
 }
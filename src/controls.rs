@@ -4,7 +4,7 @@ use bevy::{
         schedule::{NextState, State},
         system::{Res, ResMut},
     },
-    input::keyboard::KeyCode,
+    input::{keyboard::KeyCode, mouse::MouseButton},
 };
 use bevy_controls::{
     contract::InputsContainer,
@@ -33,6 +33,76 @@ impl Plugin for ControlsPlugins {
                         ))
                         .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
                     )
+                    .with(
+                        CoreAction::MoveForward,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
+                            KeyCode::KeyW,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::MoveBack,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
+                            KeyCode::KeyS,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::MoveLeft,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
+                            KeyCode::KeyA,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::MoveRight,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
+                            KeyCode::KeyD,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::Sprint,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
+                            KeyCode::ShiftLeft,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::Jump,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
+                            KeyCode::Space,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::ToggleView,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
+                            KeyCode::KeyV,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::Attack,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Mouse(
+                            MouseButton::Left,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::Utility,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Mouse(
+                            MouseButton::Right,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
+                    .with(
+                        CoreAction::Act,
+                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
+                            KeyCode::KeyE,
+                        ))
+                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+                    )
                     .build(),
             ),));
     }
@@ -5,10 +5,12 @@ use bevy_asset_loader::prelude::*;
 
 use bevy_controls_derive::{Action, GameState};
 use bevy_kira_audio::AudioSource;
+use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
 
 use crate::{
     controls::ControlsPlugins,
+    level::{level_path, package_path, verify_content_hash, CurrentLevel, LevelPackages},
     lobby::LevelCode,
     world::WorldPlugins,
     ASSET_DIR,
@@ -17,6 +19,16 @@ use crate::{
 #[derive(PartialEq, Eq, Hash, EnumIter, Clone, Copy, Debug, Action)]
 pub enum CoreAction {
     InGameMenu,
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    Jump,
+    ToggleView,
+    Attack,
+    Utility,
+    Act,
 }
 
 #[derive(States, PartialEq, Eq, Clone, Hash, Debug, Default, GameState)]
@@ -25,23 +37,33 @@ pub enum CoreGameState {
     PrimaryLoad,
     Hub,
     LoadCustomLevel,
+    LoadDungeon,
     LoadLobby,
     InGame,
 }
 
-#[derive(PartialEq, Eq, Clone, Hash, Debug)]
+#[derive(PartialEq, Eq, Clone, Hash, Debug, Serialize, Deserialize, Reflect)]
 pub enum KnownLevel {
     Hub,
+    /// Procedurally generated dungeon, the same seed gives the same dungeon on every peer.
+    Dungeon { seed: u64 },
 }
 
 #[derive(Debug, Event, Clone)]
 pub struct LoadLevelEvent {
     pub level_code: LevelCode,
+    /// Hash the level content must match, see [`level_content_hash`](crate::level::level_content_hash).
+    pub content_hash: Option<String>,
 }
 
 impl LoadLevelEvent {
     pub fn new(level_code: LevelCode) -> Self {
-      LoadLevelEvent { level_code }
+      LoadLevelEvent { level_code, content_hash: None }
+    }
+
+    pub fn with_content_hash(mut self, content_hash: Option<String>) -> Self {
+        self.content_hash = content_hash;
+        self
     }
 }
 
@@ -98,44 +120,78 @@ fn change_state_log(core_state: Res<State<CoreGameState>>) {
 fn load_level_event(
     mut load_level_event: EventReader<LoadLevelEvent>,
     mut next_state: ResMut<NextState<CoreGameState>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut packages: ResMut<LevelPackages>,
 ) {
+    // the current level stays until the new one is verified
     if let Some(event) = load_level_event.read().next() {
+        let content_hash = event.content_hash.as_deref();
         match &event.level_code {
             LevelCode::Path(path) => {
                 log::info!("load level: {}", path);
-                let path = Path::new(ASSET_DIR)
-                    .join("level")
-                    .join(format!("{path}.glb"));
-                let path_ron = Path::new(ASSET_DIR).join("dynamic_map.assets.ron");
-
-                if path.exists() {
-                    let mut file = OpenOptions::new()
-                        .write(true)
-                        .truncate(true)
-                        .open(path_ron)
-                        .unwrap();
-
-                    file.write_all(
-                        br#"({
-                       "level": File (
-                          path: "level/Level1.glb",
-                        ),
-                    })
-                    "#,
-                    )
-                    .unwrap();
-                    next_state.set(CoreGameState::LoadCustomLevel);
+                let level_path = level_path(path);
+                let package_path = package_path(path);
+
+                if level_path.exists() {
+                    if let Err(err) = verify_content_hash(&level_path, content_hash) {
+                        log::error!("{:#?}: {}", level_path, err);
+                        return;
+                    }
+                    packages.unmount();
+                    write_dynamic_level(&format!("level/{path}.glb"));
+                } else if package_path.exists() {
+                    // mounting replaces the mounted package only once the new one is verified
+                    match packages.mount(&package_path, content_hash) {
+                        Ok(level) => write_dynamic_level(&level),
+                        Err(err) => {
+                            log::error!("{:#?}: {}", package_path, err);
+                            return;
+                        }
+                    }
                 } else {
-                    log::error!("{:#?} not exist in map folder", path);
+                    log::error!("{:#?} not exist in map folder", level_path);
+                    return;
                 }
+                current_level.0 = event.level_code.clone();
+                next_state.set(CoreGameState::LoadCustomLevel);
+            }
+            LevelCode::Url(url) => {
+                log::info!("load level: {}", url);
+                // the level becomes current once the package is downloaded and verified
+                packages.download(url.clone(), event.content_hash.clone());
             }
-            LevelCode::Url(_url) => todo!(),
             LevelCode::Known(known_level) => {
                 log::info!("load level: {:#?}", known_level);
+                packages.unmount();
+                current_level.0 = event.level_code.clone();
                 match known_level {
                     KnownLevel::Hub => next_state.set(CoreGameState::Hub),
+                    KnownLevel::Dungeon { .. } => next_state.set(CoreGameState::LoadDungeon),
                 }
             }
         }
     }
 }
+
+/// Points the [`GameLevel`] collection at the level `.glb` asset path.
+pub(crate) fn write_dynamic_level(asset_path: &str) {
+    let path_ron = Path::new(ASSET_DIR).join("dynamic_map.assets.ron");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path_ron)
+        .unwrap();
+
+    file.write_all(
+        format!(
+            r#"({{
+                       "level": File (
+                          path: "{asset_path}",
+                        ),
+                    }})
+                    "#
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+}
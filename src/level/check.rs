@@ -0,0 +1,475 @@
+use bevy::{
+    asset::{LoadState, RecursiveDependencyLoadState},
+    gltf::{Gltf, GltfExtras},
+    prelude::*,
+    scene::SceneInstance,
+};
+use bevy_gltf_components::ComponentsFromGltfPlugin;
+use serde::Serialize;
+
+use crate::component::SpawnPoint;
+
+use super::{
+    collider::{collider_from_mesh, ColliderShape},
+    mesh_triangles, register_level_types, OUT_OF_WORLD_HEIGHT,
+};
+
+/// Frames the GLTF extras get to be converted into components after the scene is spawned.
+const SETTLE_FRAMES: u32 = 3;
+
+/// Limits a level is checked against.
+#[derive(Resource, Debug, Clone)]
+pub struct LevelCheckSettings {
+    /// Asset path of the checked `.glb`.
+    pub path: String,
+    pub min_spawn_points: usize,
+    /// Largest allowed texture side in pixels.
+    pub max_texture_size: u32,
+    /// Geometry below it can not be stood on, actors respawn there
+    /// (the [`Respawn`](crate::component::Respawn) axis limit of the characters).
+    pub min_height: f32,
+    /// Frames to wait for the level to load before giving up.
+    pub timeout_frames: u32,
+}
+
+impl LevelCheckSettings {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            min_spawn_points: 1,
+            max_texture_size: 2048,
+            min_height: OUT_OF_WORLD_HEIGHT,
+            timeout_frames: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    LoadFailed,
+    MissingSpawnPoints,
+    /// The extras name a type that is not registered.
+    UnknownComponent,
+    /// The extras name a registered type that is not a component.
+    NotAComponent,
+    MalformedExtras,
+    MissingCollider,
+    OutOfBounds,
+    OversizedTexture,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelIssue {
+    pub severity: Severity,
+    pub kind: IssueKind,
+    /// Node, texture or level the issue is about.
+    pub subject: String,
+    pub message: String,
+}
+
+/// Result of a level check, inserted once the check is done.
+#[derive(Resource, Debug, Clone, Default, Serialize)]
+pub struct LevelReport {
+    pub level: String,
+    pub passed: bool,
+    pub spawn_points: usize,
+    pub issues: Vec<LevelIssue>,
+}
+
+impl LevelReport {
+    fn new(level: &str) -> Self {
+        Self {
+            level: level.to_string(),
+            ..default()
+        }
+    }
+
+    fn push(&mut self, severity: Severity, kind: IssueKind, subject: String, message: String) {
+        self.issues.push(LevelIssue {
+            severity,
+            kind,
+            subject,
+            message,
+        });
+    }
+
+    fn finish(mut self) -> Self {
+        self.passed = self
+            .issues
+            .iter()
+            .all(|issue| issue.severity != Severity::Error);
+        self
+    }
+
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+}
+
+/// Loads the level of [`LevelCheckSettings`] and checks it into a [`LevelReport`].
+///
+/// Knows the components a level may author from [`register_level_types`].
+pub struct LevelCheckPlugins;
+
+impl Plugin for LevelCheckPlugins {
+    fn build(&self, app: &mut App) {
+        register_level_types(app);
+        app.add_plugins(ComponentsFromGltfPlugin::default())
+            .add_systems(Startup, load_checked_level)
+            .add_systems(
+                Update,
+                (spawn_checked_level, check_level)
+                    .chain()
+                    .run_if(not(resource_exists::<LevelReport>)),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct CheckedLevel {
+    gltf: Handle<Gltf>,
+    scene: Option<Entity>,
+    frames: u32,
+    settled_frames: u32,
+}
+
+fn load_checked_level(
+    mut commands: Commands,
+    settings: Res<LevelCheckSettings>,
+    asset_server: Res<AssetServer>,
+) {
+    log::info!("checking {}", settings.path);
+    commands.insert_resource(CheckedLevel {
+        gltf: asset_server.load(settings.path.clone()),
+        scene: None,
+        frames: 0,
+        settled_frames: 0,
+    });
+}
+
+fn spawn_checked_level(
+    mut commands: Commands,
+    mut checked: ResMut<CheckedLevel>,
+    settings: Res<LevelCheckSettings>,
+    asset_server: Res<AssetServer>,
+    gltfs: Res<Assets<Gltf>>,
+) {
+    checked.frames += 1;
+    let mut fail = |message: String| {
+        let mut report = LevelReport::new(&settings.path);
+        report.push(
+            Severity::Error,
+            IssueKind::LoadFailed,
+            settings.path.clone(),
+            message,
+        );
+        commands.insert_resource(report.finish());
+    };
+
+    if checked.frames > settings.timeout_frames {
+        fail(format!("not loaded after {} frames", settings.timeout_frames));
+        return;
+    }
+    if checked.scene.is_some() {
+        return;
+    }
+    if asset_server.load_state(&checked.gltf) == LoadState::Failed
+        || asset_server.recursive_dependency_load_state(&checked.gltf)
+            == RecursiveDependencyLoadState::Failed
+    {
+        fail("failed to load the file or its dependencies".to_string());
+        return;
+    }
+    if !asset_server.is_loaded_with_dependencies(&checked.gltf) {
+        return;
+    }
+
+    let Some(gltf) = gltfs.get(&checked.gltf) else {
+        return;
+    };
+    let Some(scene) = gltf.default_scene.clone().or(gltf.scenes.first().cloned()) else {
+        fail("the file has no scene".to_string());
+        return;
+    };
+    checked.scene = Some(
+        commands
+            .spawn(SceneBundle { scene, ..default() })
+            .id(),
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_level(
+    mut commands: Commands,
+    mut checked: ResMut<CheckedLevel>,
+    settings: Res<LevelCheckSettings>,
+    scene_query: Query<&SceneInstance>,
+    scene_spawner: Res<SceneSpawner>,
+    spawn_point_query: Query<(Entity, &GlobalTransform), With<SpawnPoint>>,
+    extras_query: Query<(Entity, &GltfExtras)>,
+    mesh_query: Query<(Entity, &Handle<Mesh>, &GlobalTransform)>,
+    shape_query: Query<&ColliderShape>,
+    name_query: Query<&Name>,
+    parent_query: Query<&Parent>,
+    meshes: Res<Assets<Mesh>>,
+    images: Res<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    type_registry: Res<AppTypeRegistry>,
+) {
+    let Some(root) = checked.scene else {
+        return;
+    };
+    let ready = scene_query
+        .get(root)
+        .is_ok_and(|instance| scene_spawner.instance_is_ready(**instance));
+    if !ready {
+        return;
+    }
+    // extras are converted into components a frame after the scene is spawned
+    checked.settled_frames += 1;
+    if checked.settled_frames < SETTLE_FRAMES {
+        return;
+    }
+
+    let in_level = |entity: Entity| parent_query.iter_ancestors(entity).any(|e| e == root);
+    let subject = |entity: Entity| {
+        name_query
+            .get(entity)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|_| format!("{entity:?}"))
+    };
+    let mut report = LevelReport::new(&settings.path);
+
+    // spawn points
+    let spawn_points = spawn_point_query
+        .iter()
+        .filter(|(entity, _)| in_level(*entity))
+        .collect::<Vec<_>>();
+    report.spawn_points = spawn_points.len();
+    if spawn_points.len() < settings.min_spawn_points {
+        report.push(
+            Severity::Error,
+            IssueKind::MissingSpawnPoints,
+            settings.path.clone(),
+            format!(
+                "{} spawn points, at least {} required",
+                spawn_points.len(),
+                settings.min_spawn_points
+            ),
+        );
+    }
+    for (entity, transform) in spawn_points {
+        if transform.translation().y < settings.min_height {
+            report.push(
+                Severity::Error,
+                IssueKind::OutOfBounds,
+                subject(entity),
+                format!(
+                    "spawn point at {} is below {}",
+                    transform.translation(),
+                    settings.min_height
+                ),
+            );
+        }
+    }
+
+    // extras
+    let registry = type_registry.read();
+    for (entity, extras) in extras_query.iter().filter(|(entity, _)| in_level(*entity)) {
+        let properties = match serde_yaml::from_str::<serde_yaml::Mapping>(&extras.value) {
+            Ok(properties) => properties,
+            Err(err) => {
+                report.push(
+                    Severity::Error,
+                    IssueKind::MalformedExtras,
+                    subject(entity),
+                    err.to_string(),
+                );
+                continue;
+            }
+        };
+        for key in properties.keys().filter_map(|key| key.as_str()) {
+            let registration = registry
+                .get_with_short_type_path(key)
+                .or_else(|| registry.get_with_type_path(key));
+            match registration {
+                None => report.push(
+                    Severity::Error,
+                    IssueKind::UnknownComponent,
+                    subject(entity),
+                    format!("`{key}` is not a registered type"),
+                ),
+                Some(registration) if registration.data::<ReflectComponent>().is_none() => {
+                    report.push(
+                        Severity::Error,
+                        IssueKind::NotAComponent,
+                        subject(entity),
+                        format!("`{key}` is not reflected as a component"),
+                    )
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    // geometry
+    for (entity, handle, transform) in mesh_query.iter().filter(|(entity, ..)| in_level(*entity)) {
+        let Some(mesh) = meshes.get(handle) else {
+            continue;
+        };
+        // extras land on the node, the mesh is one of its primitives
+        let shape = std::iter::once(entity)
+            .chain(parent_query.get(entity).map(|parent| parent.get()))
+            .find_map(|e| shape_query.get(e).ok())
+            .copied()
+            .unwrap_or_default();
+        if shape != ColliderShape::None && collider_from_mesh(mesh, shape).is_none() {
+            report.push(
+                Severity::Error,
+                IssueKind::MissingCollider,
+                subject(entity),
+                format!("failed to build a {shape:?} collider"),
+            );
+        }
+
+        let lowest = mesh_triangles(mesh, transform)
+            .iter()
+            .flatten()
+            .map(|vertex| vertex.y)
+            .fold(f32::INFINITY, f32::min);
+        if lowest < settings.min_height {
+            report.push(
+                Severity::Error,
+                IssueKind::OutOfBounds,
+                subject(entity),
+                format!("geometry reaches {} below {}", lowest, settings.min_height),
+            );
+        }
+    }
+
+    // textures
+    for (id, image) in images.iter() {
+        let Some(path) = asset_server.get_path(id) else {
+            // built-in images
+            continue;
+        };
+        let size = image.size();
+        if size.max_element() > settings.max_texture_size {
+            report.push(
+                Severity::Warning,
+                IssueKind::OversizedTexture,
+                path.to_string(),
+                format!(
+                    "{}x{} exceeds {}",
+                    size.x, size.y, settings.max_texture_size
+                ),
+            );
+        }
+    }
+
+    commands.insert_resource(report.finish());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use bevy::{
+        log::LogPlugin,
+        render::{settings::WgpuSettings, RenderPlugin},
+        window::ExitCondition,
+        winit::WinitPlugin,
+    };
+
+    use crate::ASSET_DIR;
+
+    use super::*;
+
+    /// Level with a spawn point, an unknown component and a mesh far below the ground.
+    const FIXTURE: &str = "level/fixtures/check.gltf";
+
+    /// Runs the check without a window or a GPU, like the `jeraido-levelcheck` binary.
+    fn check(settings: LevelCheckSettings) -> LevelReport {
+        let mut app = App::new();
+        app.add_plugins((
+            DefaultPlugins
+                .set(AssetPlugin {
+                    file_path: ASSET_DIR.to_string(),
+                    ..default()
+                })
+                .set(RenderPlugin {
+                    render_creation: WgpuSettings {
+                        backends: None,
+                        ..default()
+                    }
+                    .into(),
+                    ..default()
+                })
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    close_when_requested: false,
+                })
+                .disable::<WinitPlugin>()
+                .disable::<LogPlugin>(),
+            LevelCheckPlugins,
+        ))
+        .insert_resource(settings);
+
+        app.finish();
+        app.cleanup();
+        loop {
+            app.update();
+            if let Some(report) = app.world.remove_resource::<LevelReport>() {
+                return report;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn fixture_report() {
+        let report = check(LevelCheckSettings {
+            min_height: -10.,
+            ..LevelCheckSettings::new(FIXTURE)
+        });
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&report.to_yaml().unwrap()).unwrap();
+
+        assert_eq!(yaml["level"], FIXTURE);
+        assert_eq!(yaml["passed"], false);
+        assert_eq!(yaml["spawn_points"], 1);
+        let issues = yaml["issues"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|issue| {
+                (
+                    issue["severity"].as_str().unwrap(),
+                    issue["kind"].as_str().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            issues,
+            vec![("error", "unknown_component"), ("error", "out_of_bounds")],
+            "{yaml:?}"
+        );
+        assert_eq!(yaml["issues"][0]["subject"], "Ghost");
+    }
+
+    #[test]
+    fn missing_file_fails_to_load() {
+        let report = check(LevelCheckSettings::new("level/fixtures/missing.gltf"));
+        assert!(!report.passed);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, IssueKind::LoadFailed);
+    }
+}
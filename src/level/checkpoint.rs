@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    actor::character::PLAYER_SIZE,
+    component::Respawn,
+    core::CoreGameState,
+    lobby::{Character, Lobby, LobbyState, PlayerId},
+    world::{LinkId, SpawnProperty},
+};
+
+use super::trigger::{Checkpoint, TriggerEvent, TriggerPhase};
+
+/// Light intensity of the mark over an activated checkpoint.
+const MARK_INTENSITY: f32 = 2000.;
+const MARK_RANGE: f32 = 8.;
+
+/// Sent on every peer when a player activates a [`Checkpoint`].
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CheckpointActivated {
+    pub player: PlayerId,
+    pub checkpoint: Entity,
+}
+
+/// Last checkpoint every player activated on the current level.
+#[derive(Resource, Default, Debug, Deref)]
+pub struct ActiveCheckpoints(HashMap<PlayerId, Entity>);
+
+/// Light over a checkpoint in the color of the player who activated it.
+#[derive(Component)]
+struct CheckpointMark(PlayerId);
+
+pub struct CheckpointPlugins;
+
+impl Plugin for CheckpointPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CheckpointActivated>()
+            .init_resource::<ActiveCheckpoints>()
+            .add_systems(
+                Update,
+                (
+                    link_checkpoints,
+                    // clients get the activations from the host
+                    activate_checkpoint.run_if(not(in_state(LobbyState::Client))),
+                    mark_checkpoint,
+                )
+                    .chain(),
+            )
+            .add_systems(OnExit(CoreGameState::Hub), reset_checkpoints)
+            .add_systems(OnExit(CoreGameState::InGame), reset_checkpoints);
+    }
+}
+
+/// Links checkpoints by their node name, so peers can refer to the same one.
+fn link_checkpoints(
+    mut commands: Commands,
+    checkpoint_query: Query<(Entity, Option<&Name>), (Added<Checkpoint>, Without<LinkId>)>,
+) {
+    for (entity, name) in checkpoint_query.iter() {
+        match name {
+            Some(name) => {
+                commands
+                    .entity(entity)
+                    .insert(LinkId::Scene(name.to_string()));
+            }
+            None => log::warn!("checkpoint {:?} has no name and is not synced", entity),
+        }
+    }
+}
+
+/// Moves the respawn point of a character entering a checkpoint.
+fn activate_checkpoint(
+    mut checkpoint_events: EventReader<TriggerEvent<Checkpoint>>,
+    mut character_query: Query<(&Character, &mut Respawn)>,
+    checkpoint_query: Query<&GlobalTransform, With<Checkpoint>>,
+    active: Res<ActiveCheckpoints>,
+    mut activated_events: EventWriter<CheckpointActivated>,
+) {
+    for event in checkpoint_events
+        .read()
+        .filter(|event| event.phase == TriggerPhase::Enter)
+    {
+        let Ok((character, mut respawn)) = character_query.get_mut(event.target) else {
+            continue;
+        };
+        if active.get(&character.id) == Some(&event.trigger) {
+            continue;
+        }
+        let Ok(transform) = checkpoint_query.get(event.trigger) else {
+            continue;
+        };
+
+        // the node origin marks the ground, keep the character above it
+        let point = transform.translation() + Vec3::Y * PLAYER_SIZE;
+        log::info!("{:?} activated a checkpoint at {}", character.id, point);
+        respawn.replase_spawn_point(SpawnProperty::new(point));
+        activated_events.send(CheckpointActivated {
+            player: character.id,
+            checkpoint: event.trigger,
+        });
+    }
+}
+
+/// Moves the mark of the player to the activated checkpoint.
+fn mark_checkpoint(
+    mut commands: Commands,
+    mut activated_events: EventReader<CheckpointActivated>,
+    mut active: ResMut<ActiveCheckpoints>,
+    mark_query: Query<(Entity, &CheckpointMark)>,
+    character_query: Query<(&Character, &Handle<StandardMaterial>)>,
+    materials: Res<Assets<StandardMaterial>>,
+    lobby: Option<Res<Lobby>>,
+) {
+    for event in activated_events.read() {
+        active.0.insert(event.player, event.checkpoint);
+
+        for (entity, CheckpointMark(player)) in mark_query.iter() {
+            if *player == event.player {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+
+        // clients only know the color from the lobby
+        let color = character_query
+            .iter()
+            .find(|(character, _)| character.id == event.player)
+            .and_then(|(_, material)| materials.get(material))
+            .map(|material| material.base_color)
+            .or_else(|| {
+                lobby
+                    .as_ref()
+                    .and_then(|lobby| lobby.players.get(&event.player))
+                    .map(|player_data| player_data.color)
+            })
+            .unwrap_or(Color::WHITE);
+
+        let Some(mut checkpoint) = commands.get_entity(event.checkpoint) else {
+            continue;
+        };
+        checkpoint.with_children(|parent| {
+            parent.spawn((
+                PointLightBundle {
+                    point_light: PointLight {
+                        color,
+                        intensity: MARK_INTENSITY,
+                        range: MARK_RANGE,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., PLAYER_SIZE, 0.),
+                    ..default()
+                },
+                CheckpointMark(event.player),
+                Name::new(format!("CheckpointMark:{:?}", event.player)),
+            ));
+        });
+    }
+}
+
+/// Forgets the checkpoints of the left level, the marks go away with their checkpoints.
+fn reset_checkpoints(mut active: ResMut<ActiveCheckpoints>) {
+    active.0.clear();
+}
@@ -0,0 +1,223 @@
+use bevy::{prelude::*, render::primitives::Aabb};
+use bevy_rapier3d::prelude::{
+    ActiveCollisionTypes, ActiveEvents, Collider, CollisionGroups, ComputedColliderShape, Group,
+    Sensor,
+};
+
+use super::{is_level_entity, Affiliation};
+
+/// Node name suffix of an invisible mesh that only provides a collider.
+pub const COLLIDER_ONLY_SUFFIX: &str = "_collider";
+
+/// Shape of the collider generated for a level mesh.
+///
+/// Authored in Blender as GLTF extras on the mesh object,
+/// meshes without it get a [`TriMesh`](ColliderShape::TriMesh).
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub enum ColliderShape {
+    /// Exact static geometry.
+    #[default]
+    TriMesh,
+    ConvexHull,
+    /// Box fitted to the mesh bounds.
+    Box,
+    /// Vertical capsule fitted to the mesh bounds.
+    Capsule,
+    /// The mesh gets no collider.
+    None,
+}
+
+/// Marks an invisible mesh that only provides a collider.
+///
+/// Same as naming the object with the [`COLLIDER_ONLY_SUFFIX`].
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct ColliderOnly;
+
+/// Marks an invisible mesh that becomes a sensor instead of a solid collider.
+///
+/// Sensors default to a [`ConvexHull`](ColliderShape::ConvexHull),
+/// a hollow [`TriMesh`](ColliderShape::TriMesh) does not notice what is inside of it.
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct SensorVolume;
+
+/// Components of a sensor collider that reports collision events.
+#[derive(Bundle)]
+pub struct SensorBundle {
+    pub collider: Collider,
+    pub sensor: Sensor,
+    pub active_events: ActiveEvents,
+    pub active_collision_types: ActiveCollisionTypes,
+}
+
+impl SensorBundle {
+    pub fn new(collider: Collider) -> Self {
+        Self {
+            collider,
+            sensor: Sensor,
+            active_events: ActiveEvents::COLLISION_EVENTS,
+            // kinematic characters never touch the level by themselves
+            active_collision_types: ActiveCollisionTypes::default()
+                | ActiveCollisionTypes::KINEMATIC_STATIC,
+        }
+    }
+}
+
+/// Collision groups of the level colliders.
+///
+/// Applies to every mesh below the entity it is placed on, the nearest one wins.
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct LevelCollisionGroups {
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl Default for LevelCollisionGroups {
+    fn default() -> Self {
+        Self {
+            memberships: Group::ALL.bits(),
+            filters: Group::ALL.bits(),
+        }
+    }
+}
+
+impl From<LevelCollisionGroups> for CollisionGroups {
+    fn from(groups: LevelCollisionGroups) -> Self {
+        CollisionGroups::new(
+            Group::from_bits_truncate(groups.memberships),
+            Group::from_bits_truncate(groups.filters),
+        )
+    }
+}
+
+/// A level mesh waiting for its GLTF extras to be converted into components.
+#[derive(Component)]
+pub(super) struct PendingCollider;
+
+pub struct LevelColliderPlugins;
+
+impl Plugin for LevelColliderPlugins {
+    fn build(&self, app: &mut App) {
+        // extras are converted in `Update`, so a mesh queued in one frame is built in the next one
+        app.add_systems(
+            PostUpdate,
+            (build_level_colliders, queue_level_meshes).chain(),
+        );
+    }
+}
+
+pub(super) fn queue_level_meshes(
+    mut commands: Commands,
+    mesh_query: Query<Entity, (Added<Handle<Mesh>>, Without<Collider>)>,
+    parent_query: Query<&Parent>,
+    affiliation_query: Query<(), With<Affiliation>>,
+) {
+    for entity in mesh_query.iter() {
+        if is_level_entity(entity, &parent_query, &affiliation_query) {
+            commands.entity(entity).insert(PendingCollider);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn build_level_colliders(
+    mut commands: Commands,
+    pending_query: Query<(Entity, &Handle<Mesh>), With<PendingCollider>>,
+    parent_query: Query<&Parent>,
+    shape_query: Query<&ColliderShape>,
+    groups_query: Query<&LevelCollisionGroups>,
+    collider_only_query: Query<(), With<ColliderOnly>>,
+    sensor_query: Query<(), With<SensorVolume>>,
+    name_query: Query<&Name>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (entity, handle) in pending_query.iter() {
+        let Some(mesh) = meshes.get(handle) else {
+            continue;
+        };
+        commands.entity(entity).remove::<PendingCollider>();
+
+        // extras land on the node, the mesh is one of its primitives
+        let node = parent_query.get(entity).map(|parent| parent.get()).ok();
+        let own_or_node = [Some(entity), node];
+
+        let sensor = own_or_node
+            .iter()
+            .flatten()
+            .any(|e| sensor_query.contains(*e));
+        let shape = own_or_node
+            .iter()
+            .flatten()
+            .find_map(|e| shape_query.get(*e).ok())
+            .copied()
+            .unwrap_or(if sensor {
+                ColliderShape::ConvexHull
+            } else {
+                ColliderShape::TriMesh
+            });
+
+        let collider_only = own_or_node.iter().flatten().any(|e| {
+            collider_only_query.contains(*e)
+                || name_query
+                    .get(*e)
+                    .is_ok_and(|name| name.as_str().ends_with(COLLIDER_ONLY_SUFFIX))
+        });
+        if collider_only || sensor {
+            commands.entity(entity).insert(Visibility::Hidden);
+        }
+
+        let Some(collider) = collider_from_mesh(mesh, shape) else {
+            if shape != ColliderShape::None {
+                log::warn!("failed to build {:?} collider for {:?}", shape, entity);
+            }
+            continue;
+        };
+
+        let groups = std::iter::once(entity)
+            .chain(parent_query.iter_ancestors(entity))
+            .find_map(|ancestor| groups_query.get(ancestor).ok())
+            .copied()
+            .unwrap_or_default();
+
+        let mut entity = commands.entity(entity);
+        entity.insert(CollisionGroups::from(groups));
+        if sensor {
+            entity.insert(SensorBundle::new(collider));
+        } else {
+            entity.insert(collider);
+        }
+    }
+}
+
+/// Builds a collider of the given shape in the mesh local space.
+pub fn collider_from_mesh(mesh: &Mesh, shape: ColliderShape) -> Option<Collider> {
+    let bounds = || mesh.compute_aabb().map(|Aabb { center, half_extents }| {
+        (Vec3::from(center), Vec3::from(half_extents))
+    });
+
+    match shape {
+        ColliderShape::TriMesh => Collider::from_bevy_mesh(mesh, &ComputedColliderShape::TriMesh),
+        ColliderShape::ConvexHull => {
+            Collider::from_bevy_mesh(mesh, &ComputedColliderShape::ConvexHull)
+        }
+        ColliderShape::Box => bounds().map(|(center, half_extents)| {
+            Collider::compound(vec![(
+                center,
+                Quat::IDENTITY,
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            )])
+        }),
+        ColliderShape::Capsule => bounds().map(|(center, half_extents)| {
+            let radius = half_extents.x.max(half_extents.z);
+            Collider::compound(vec![(
+                center,
+                Quat::IDENTITY,
+                Collider::capsule_y((half_extents.y - radius).max(0.), radius),
+            )])
+        }),
+        ColliderShape::None => None,
+    }
+}
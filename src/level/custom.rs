@@ -1,44 +1,71 @@
 use bevy::{
-    app::{App, Plugin},
+    app::{App, Plugin, Update},
     asset::{Assets},
     core::Name,
     ecs::{
         component::Component,
+        entity::Entity,
+        query::{With, Without},
         reflect::ReflectComponent,
-        schedule::OnEnter,
+        schedule::{common_conditions::in_state, IntoSystemConfigs, OnEnter},
         system::{Commands, Query, Res},
     },
     reflect::Reflect,
-    scene::SceneBundle,
+    scene::{SceneBundle, SceneInstance, SceneSpawner},
     utils::default,
 };
-use bevy_gltf_components::ComponentsFromGltfPlugin;
+use bevy_gltf_components::{ComponentsFromGltfPlugin, GltfComponentsSet};
 
 
 use crate::{
     component::ComponentsTestPlugin,
-    core::{CoreGameState, GameLevel}, world::SpawnProperty,
+    core::{CoreGameState, GameLevel}, lobby::LevelCode, world::SpawnProperty,
+};
+
+use super::{
+    trigger::{kill_floor, KillZone},
+    Affiliation, CurrentLevel,
 };
 
 #[derive(Component, Reflect, Default, Debug)]
 #[reflect(Component)]
 pub struct LoadedMarker;
 
+/// A loaded level that was checked for a [`KillZone`].
+#[derive(Component)]
+struct KillZoneChecked;
+
 pub struct CustomPlugins;
 
 impl Plugin for CustomPlugins {
     fn build(&self, app: &mut App) {
         app.add_plugins(ComponentsFromGltfPlugin::default(),)
-            .add_systems(OnEnter(CoreGameState::InGame), spawn_level);
+            .register_type::<LoadedMarker>()
+            .add_systems(OnEnter(CoreGameState::InGame), spawn_level)
+            .add_systems(
+                Update,
+                fallback_kill_floor
+                    .after(GltfComponentsSet::Injection)
+                    .run_if(in_state(CoreGameState::InGame)),
+            );
     }
 }
 
 fn spawn_level(
     mut commands: Commands,
     scene_markers: Query<&LoadedMarker>,
-    model_assets: Res<GameLevel>,
+    model_assets: Option<Res<GameLevel>>,
     models: Res<Assets<bevy::gltf::Gltf>>,
+    current_level: Res<CurrentLevel>,
 ) {
+    if let LevelCode::Known(_) = current_level.0 {
+        // known levels are spawned by their own plugins
+        return;
+    }
+    let Some(model_assets) = model_assets else {
+        log::error!("{:?} is not loaded", current_level.0);
+        return;
+    };
     commands.insert_resource(SpawnProperty::empty());
     let gltf = models.get(model_assets.level.clone()).unwrap();
     if scene_markers.is_empty() {
@@ -49,9 +76,34 @@ fn spawn_level(
                 ..default()
             },
             LoadedMarker,
-            Name::new("Level1"),
+            Affiliation(current_level.0.clone()),
+            Name::new(format!("Level:{:?}", current_level.0)),
         ));
     } else {
         log::error!("scene already exist");
     }
 }
+
+/// Places a [`kill_floor`] under a loaded level that has no [`KillZone`] of its own.
+///
+/// Runs once the scene is spawned and its GLTF extras became components.
+fn fallback_kill_floor(
+    mut commands: Commands,
+    level_query: Query<
+        (Entity, &SceneInstance, &Affiliation),
+        (With<LoadedMarker>, Without<KillZoneChecked>),
+    >,
+    kill_zone_query: Query<(), With<KillZone>>,
+    scene_spawner: Res<SceneSpawner>,
+) {
+    for (entity, instance, affiliation) in level_query.iter() {
+        if !scene_spawner.instance_is_ready(**instance) {
+            continue;
+        }
+        commands.entity(entity).insert(KillZoneChecked);
+        if kill_zone_query.is_empty() {
+            log::info!("{:?} has no KillZone, placing a kill floor", affiliation.0);
+            commands.spawn((kill_floor(), Affiliation(affiliation.0.clone())));
+        }
+    }
+}
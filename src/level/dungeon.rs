@@ -0,0 +1,646 @@
+use bevy::{gltf::Gltf, prelude::*, scene::SceneInstance};
+use bevy_gltf_components::GltfComponentsSet;
+use bevy_rapier3d::prelude::{Collider, RigidBody};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::f32::consts::FRAC_PI_2;
+
+use crate::{
+    component::SpawnPoint,
+    core::{CoreGameState, KnownLevel},
+    lobby::LevelCode,
+};
+
+use super::{
+    collider::SensorBundle,
+    trigger::{kill_floor, LevelExit},
+    Affiliation, CurrentLevel,
+};
+
+/// Gap between two rooms (and a room and a corridor) that the layout keeps free.
+const ROOM_MARGIN: f32 = 0.5;
+/// Height of the spawn point above the start room floor.
+const SPAWN_HEIGHT: f32 = 2.;
+/// Half size of the exit volume in the deepest room.
+const EXIT_HALF_EXTENTS: Vec3 = Vec3::new(1.5, 1., 1.5);
+
+/// Marks the root node of a room template scene.
+///
+/// Authored in Blender as GLTF extras, every scene of the rooms file is one template.
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct RoomTemplate {
+    /// Half size of the room footprint on the XZ plane.
+    pub half_extents: Vec2,
+}
+
+/// Marks a doorway of a room template.
+///
+/// The doorway must lie on the edge of the [`RoomTemplate`] footprint,
+/// the side it is placed on defines where a corridor will be attached.
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct RoomConnector;
+
+/// Marks the place where the dungeon is left.
+///
+/// Spawned together with a [`LevelExit`] back to the hub.
+#[derive(Component, Debug)]
+pub struct DungeonExit;
+
+/// Settings of the dungeon generator.
+#[derive(Resource, Debug, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct DungeonConfig {
+    /// GLTF file (relative to the asset folder) with one scene per room template.
+    pub rooms_path: String,
+    /// Template the dungeon starts from (the cave where the `PC` wakes up).
+    pub start_template: usize,
+    /// Amount of rooms the generator tries to place.
+    pub room_count: usize,
+    /// Minimal and maximal corridor length.
+    pub corridor_length: Vec2,
+    /// Corridor width.
+    pub corridor_width: f32,
+    /// Amount of failed placements after which the generator gives up.
+    pub max_attempts: usize,
+}
+
+impl Default for DungeonConfig {
+    fn default() -> Self {
+        Self {
+            rooms_path: "level/dungeon_rooms.glb".into(),
+            start_template: 0,
+            room_count: 8,
+            corridor_length: Vec2::new(2., 8.),
+            corridor_width: 3.,
+            max_attempts: 200,
+        }
+    }
+}
+
+/// Side of a room, ordered so that the next one is a quarter turn around Y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cardinal {
+    PosX = 0,
+    NegZ = 1,
+    NegX = 2,
+    PosZ = 3,
+}
+
+impl Cardinal {
+    const ALL: [Cardinal; 4] = [Cardinal::PosX, Cardinal::NegZ, Cardinal::NegX, Cardinal::PosZ];
+
+    /// Picks the side a point (relative to the room center) lies on.
+    pub fn from_offset(offset: Vec2) -> Self {
+        if offset.x.abs() >= offset.y.abs() {
+            if offset.x >= 0. {
+                Cardinal::PosX
+            } else {
+                Cardinal::NegX
+            }
+        } else if offset.y >= 0. {
+            Cardinal::PosZ
+        } else {
+            Cardinal::NegZ
+        }
+    }
+
+    pub fn rotate(self, quarter_turns: u8) -> Self {
+        Self::ALL[(self as usize + quarter_turns as usize) % 4]
+    }
+
+    pub fn opposite(self) -> Self {
+        self.rotate(2)
+    }
+
+    pub fn vec(self) -> Vec2 {
+        match self {
+            Cardinal::PosX => Vec2::X,
+            Cardinal::NegZ => Vec2::NEG_Y,
+            Cardinal::NegX => Vec2::NEG_X,
+            Cardinal::PosZ => Vec2::Y,
+        }
+    }
+}
+
+/// Rotates a point on the XZ plane (`y` is used as `z`) the same way as [`Quat::from_rotation_y`].
+pub fn rotate_quarter(mut point: Vec2, quarter_turns: u8) -> Vec2 {
+    for _ in 0..quarter_turns % 4 {
+        point = Vec2::new(point.y, -point.x);
+    }
+    point
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectorInfo {
+    /// Position relative to the room center.
+    pub offset: Vec2,
+    pub side: Cardinal,
+}
+
+/// A room template collected from the rooms GLTF.
+#[derive(Debug, Clone)]
+pub struct RoomTemplateInfo {
+    /// Index of the scene in the rooms GLTF.
+    pub scene: usize,
+    /// Room center relative to the scene origin.
+    pub center: Vec3,
+    pub half_extents: Vec2,
+    pub connectors: Vec<ConnectorInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedRoom {
+    /// Index in the template list.
+    pub template: usize,
+    /// Room center on the XZ plane.
+    pub position: Vec2,
+    pub quarter_turns: u8,
+    /// Amount of rooms between this one and the start room.
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Corridor {
+    pub from: Vec2,
+    pub to: Vec2,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DungeonLayout {
+    pub rooms: Vec<PlacedRoom>,
+    pub corridors: Vec<Corridor>,
+    /// Index of the room the dungeon exit is placed in.
+    pub exit_room: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Footprint {
+    min: Vec2,
+    max: Vec2,
+}
+
+impl Footprint {
+    fn from_center(center: Vec2, half_extents: Vec2) -> Self {
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+
+    fn from_segment(from: Vec2, to: Vec2, half_width: f32) -> Self {
+        Self {
+            min: from.min(to) - Vec2::splat(half_width),
+            max: from.max(to) + Vec2::splat(half_width),
+        }
+    }
+
+    fn grow(self, value: f32) -> Self {
+        Self {
+            min: self.min - Vec2::splat(value),
+            max: self.max + Vec2::splat(value),
+        }
+    }
+
+    fn overlaps(&self, other: &Footprint) -> bool {
+        self.min.x < other.max.x
+            && self.max.x > other.min.x
+            && self.min.y < other.max.y
+            && self.max.y > other.min.y
+    }
+}
+
+fn room_rect(template: &RoomTemplateInfo, room: &PlacedRoom) -> Footprint {
+    let half_extents = rotate_quarter(template.half_extents, room.quarter_turns).abs();
+    Footprint::from_center(room.position, half_extents)
+}
+
+fn connector_position(template: &RoomTemplateInfo, room: &PlacedRoom, connector: usize) -> Vec2 {
+    room.position + rotate_quarter(template.connectors[connector].offset, room.quarter_turns)
+}
+
+/// Assembles room templates into a connected layout.
+///
+/// The result depends only on the `seed`, the templates and the config,
+/// so every peer that has the same rooms file builds the same dungeon.
+pub fn generate_layout(
+    seed: u64,
+    templates: &[RoomTemplateInfo],
+    config: &DungeonConfig,
+) -> DungeonLayout {
+    let mut layout = DungeonLayout::default();
+    if templates.is_empty() {
+        return layout;
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let half_width = config.corridor_width / 2.;
+
+    layout.rooms.push(PlacedRoom {
+        template: config.start_template.min(templates.len() - 1),
+        position: Vec2::ZERO,
+        quarter_turns: 0,
+        depth: 0,
+    });
+    let mut rects = vec![room_rect(&templates[layout.rooms[0].template], &layout.rooms[0])];
+    // (room, connector) pairs without a corridor
+    let mut open: Vec<(usize, usize)> = (0..templates[layout.rooms[0].template].connectors.len())
+        .map(|connector| (0, connector))
+        .collect();
+
+    let mut attempts = 0;
+    while layout.rooms.len() < config.room_count && !open.is_empty() && attempts < config.max_attempts {
+        attempts += 1;
+
+        let open_index = rng.gen_range(0..open.len());
+        let (source_index, source_connector) = open[open_index];
+        let source = &layout.rooms[source_index];
+        let source_template = &templates[source.template];
+        let side = source_template.connectors[source_connector]
+            .side
+            .rotate(source.quarter_turns);
+        let from = connector_position(source_template, source, source_connector);
+
+        let template_index = rng.gen_range(0..templates.len());
+        let template = &templates[template_index];
+        if template.connectors.is_empty() {
+            continue;
+        }
+        let connector = rng.gen_range(0..template.connectors.len());
+        // turn the candidate so its doorway faces the source doorway
+        let quarter_turns = (4 + side.opposite() as u8 - template.connectors[connector].side as u8) % 4;
+        let length = rng.gen_range(config.corridor_length.x..=config.corridor_length.y);
+        let to = from + side.vec() * length;
+
+        let mut room = PlacedRoom {
+            template: template_index,
+            position: Vec2::ZERO,
+            quarter_turns,
+            depth: source.depth + 1,
+        };
+        room.position = to - rotate_quarter(template.connectors[connector].offset, quarter_turns);
+
+        let rect = room_rect(template, &room);
+        let corridor_rect = Footprint::from_segment(from, to, half_width);
+        // the corridor only touches the room it leaves, not the other rooms nor corridors
+        let blocked = rects
+            .iter()
+            .any(|other| rect.grow(ROOM_MARGIN).overlaps(other))
+            || rects
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != source_index)
+                .any(|(_, other)| corridor_rect.overlaps(other));
+        if blocked {
+            continue;
+        }
+
+        let room_index = layout.rooms.len();
+        open.swap_remove(open_index);
+        open.extend(
+            (0..template.connectors.len())
+                .filter(|index| *index != connector)
+                .map(|index| (room_index, index)),
+        );
+        layout.rooms.push(room);
+        layout.corridors.push(Corridor { from, to });
+        // corridors are pushed after the rooms, so the room indices stay valid
+        rects.insert(room_index, rect);
+        rects.push(corridor_rect);
+    }
+
+    if layout.rooms.len() < config.room_count {
+        log::warn!(
+            "dungeon ({seed}) has only {} of {} rooms",
+            layout.rooms.len(),
+            config.room_count
+        );
+    }
+
+    layout.exit_room = layout
+        .rooms
+        .iter()
+        .enumerate()
+        .fold(0, |exit, (index, room)| {
+            if room.depth > layout.rooms[exit].depth {
+                index
+            } else {
+                exit
+            }
+        });
+
+    layout
+}
+
+/// Rooms GLTF of the dungeon that is being generated.
+#[derive(Resource, Default)]
+struct DungeonRooms {
+    gltf: Handle<Gltf>,
+    probes_spawned: bool,
+    /// Probes that are not collected yet.
+    pending: usize,
+    /// Scenes without a [`RoomTemplate`] stay `None`.
+    templates: Vec<Option<RoomTemplateInfo>>,
+}
+
+/// Hidden instance of a room scene, spawned only to read its [`RoomConnector`]s.
+#[derive(Component)]
+struct TemplateProbe(usize);
+
+pub struct DungeonPlugins;
+
+impl Plugin for DungeonPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DungeonConfig>()
+            .register_type::<DungeonConfig>()
+            .add_systems(OnEnter(CoreGameState::LoadDungeon), load_rooms)
+            .add_systems(
+                Update,
+                (
+                    spawn_template_probes,
+                    // a ready probe had its GLTF extras turned into components by now
+                    (collect_room_templates, spawn_dungeon)
+                        .chain()
+                        .after(GltfComponentsSet::Injection),
+                )
+                    .run_if(in_state(CoreGameState::LoadDungeon)),
+            );
+    }
+}
+
+fn load_rooms(mut commands: Commands, asset_server: Res<AssetServer>, config: Res<DungeonConfig>) {
+    log::info!("load dungeon rooms: {}", config.rooms_path);
+    commands.insert_resource(DungeonRooms {
+        gltf: asset_server.load(config.rooms_path.clone()),
+        ..default()
+    });
+}
+
+fn spawn_template_probes(
+    mut commands: Commands,
+    mut rooms: ResMut<DungeonRooms>,
+    gltfs: Res<Assets<Gltf>>,
+) {
+    if rooms.probes_spawned {
+        return;
+    }
+    let Some(gltf) = gltfs.get(&rooms.gltf) else {
+        return;
+    };
+
+    for (index, scene) in gltf.scenes.iter().enumerate() {
+        commands.spawn((
+            SceneBundle {
+                scene: scene.clone(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            TemplateProbe(index),
+            Name::new(format!("TemplateProbe:{index}")),
+        ));
+    }
+    rooms.templates = vec![None; gltf.scenes.len()];
+    rooms.pending = gltf.scenes.len();
+    rooms.probes_spawned = true;
+}
+
+fn collect_room_templates(
+    mut commands: Commands,
+    mut rooms: ResMut<DungeonRooms>,
+    probe_query: Query<(Entity, &TemplateProbe, &SceneInstance)>,
+    children_query: Query<&Children>,
+    template_query: Query<(&RoomTemplate, &GlobalTransform)>,
+    connector_query: Query<&GlobalTransform, With<RoomConnector>>,
+    scene_spawner: Res<SceneSpawner>,
+) {
+    for (probe, TemplateProbe(index), instance) in probe_query.iter() {
+        if !scene_spawner.instance_is_ready(**instance) {
+            continue;
+        }
+        let index = *index;
+        // components from GLTF extras are inserted all at once, so connectors come with the template
+        let Some((template, center)) = children_query
+            .iter_descendants(probe)
+            .find_map(|entity| template_query.get(entity).ok())
+        else {
+            log::warn!("room scene {index} has no RoomTemplate, skipped");
+            rooms.pending -= 1;
+            commands.entity(probe).despawn_recursive();
+            continue;
+        };
+
+        let center = center.translation();
+        let connectors = children_query
+            .iter_descendants(probe)
+            .filter_map(|entity| connector_query.get(entity).ok())
+            .map(|global_transform| {
+                let offset = (global_transform.translation() - center).xz();
+                ConnectorInfo {
+                    offset,
+                    side: Cardinal::from_offset(offset),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if connectors.is_empty() {
+            log::warn!("room template {index} has no connectors");
+        }
+
+        rooms.templates[index] = Some(RoomTemplateInfo {
+            scene: index,
+            center,
+            half_extents: template.half_extents,
+            connectors,
+        });
+        rooms.pending -= 1;
+        commands.entity(probe).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_dungeon(
+    mut commands: Commands,
+    rooms: Res<DungeonRooms>,
+    config: Res<DungeonConfig>,
+    current_level: Res<CurrentLevel>,
+    gltfs: Res<Assets<Gltf>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut next_state: ResMut<NextState<CoreGameState>>,
+) {
+    if !rooms.probes_spawned || rooms.pending > 0 {
+        return;
+    }
+    let LevelCode::Known(KnownLevel::Dungeon { seed }) = current_level.0 else {
+        log::error!("{:?} is not a dungeon", current_level.0);
+        return;
+    };
+    let Some(gltf) = gltfs.get(&rooms.gltf) else {
+        return;
+    };
+
+    let templates = rooms.templates.iter().flatten().cloned().collect::<Vec<_>>();
+    let layout = generate_layout(seed, &templates, &config);
+    if layout.rooms.is_empty() {
+        log::error!("{} has no room templates", config.rooms_path);
+        return;
+    }
+    log::info!(
+        "dungeon ({seed}): {} rooms, {} corridors",
+        layout.rooms.len(),
+        layout.corridors.len()
+    );
+
+    let floor_material = materials.add(Color::GRAY);
+    let affiliation = || Affiliation(current_level.0.clone());
+
+    commands
+        .spawn((
+            SpatialBundle::default(),
+            affiliation(),
+            Name::new(format!("Dungeon:{seed}")),
+        ))
+        .with_children(|parent| {
+            for (index, room) in layout.rooms.iter().enumerate() {
+                let template = &templates[room.template];
+                let rotation = Quat::from_rotation_y(room.quarter_turns as f32 * FRAC_PI_2);
+                let position = Vec3::new(room.position.x, 0., room.position.y);
+                parent.spawn((
+                    SceneBundle {
+                        scene: gltf.scenes[template.scene].clone(),
+                        transform: Transform::from_translation(
+                            position - rotation.mul_vec3(Vec3::new(template.center.x, 0., template.center.z)),
+                        )
+                        .with_rotation(rotation),
+                        ..default()
+                    },
+                    Name::new(format!("Room:{index}")),
+                ));
+            }
+
+            for (index, corridor) in layout.corridors.iter().enumerate() {
+                let length = corridor.from.distance(corridor.to);
+                let half_size = Vec3::new(config.corridor_width / 2., 0.1, length / 2.);
+                let center = (corridor.from + corridor.to) / 2.;
+                let direction = (corridor.to - corridor.from).normalize_or_zero();
+                parent.spawn((
+                    PbrBundle {
+                        mesh: meshes.add(Mesh::from(Cuboid { half_size })),
+                        material: floor_material.clone(),
+                        transform: Transform::from_xyz(center.x, -half_size.y, center.y)
+                            .looking_to(Vec3::new(direction.x, 0., direction.y), Vec3::Y),
+                        ..default()
+                    },
+                    RigidBody::Fixed,
+                    Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                    Name::new(format!("Corridor:{index}")),
+                ));
+            }
+        });
+
+    // spawn points and exits are level roots, so their global transform is valid right away
+    let start = &layout.rooms[0];
+    let transform = Transform::from_xyz(start.position.x, SPAWN_HEIGHT, start.position.y);
+    commands.spawn((
+        transform,
+        GlobalTransform::from(transform),
+        SpawnPoint::default(),
+        affiliation(),
+        Name::new("SpawnPoint"),
+    ));
+
+    let exit = &layout.rooms[layout.exit_room];
+    let transform = Transform::from_xyz(exit.position.x, EXIT_HALF_EXTENTS.y, exit.position.y);
+    commands.spawn((
+        transform,
+        GlobalTransform::from(transform),
+        DungeonExit,
+        LevelExit::default(),
+        SensorBundle::new(Collider::cuboid(
+            EXIT_HALF_EXTENTS.x,
+            EXIT_HALF_EXTENTS.y,
+            EXIT_HALF_EXTENTS.z,
+        )),
+        affiliation(),
+        Name::new("DungeonExit"),
+    ));
+    commands.spawn((kill_floor(), affiliation()));
+
+    commands.remove_resource::<DungeonRooms>();
+    next_state.set(CoreGameState::LoadLobby);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Square room with a doorway in the middle of every side.
+    fn templates() -> Vec<RoomTemplateInfo> {
+        [4., 6.]
+            .into_iter()
+            .enumerate()
+            .map(|(scene, half_size)| RoomTemplateInfo {
+                scene,
+                center: Vec3::ZERO,
+                half_extents: Vec2::splat(half_size),
+                connectors: Cardinal::ALL
+                    .into_iter()
+                    .map(|side| ConnectorInfo {
+                        offset: side.vec() * half_size,
+                        side,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn layout(seed: u64) -> DungeonLayout {
+        generate_layout(seed, &templates(), &DungeonConfig::default())
+    }
+
+    #[test]
+    fn same_seed_same_dungeon() {
+        for seed in 0..8 {
+            assert_eq!(layout(seed), layout(seed));
+        }
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let first = layout(0);
+        assert!((1..8).map(layout).any(|other| other != first));
+    }
+
+    #[test]
+    fn rooms_and_corridors_do_not_overlap() {
+        let templates = templates();
+        let half_width = DungeonConfig::default().corridor_width / 2.;
+        for seed in 0..32 {
+            let layout = layout(seed);
+            assert!(layout.rooms.len() > 1, "dungeon {seed} has a single room");
+            assert_eq!(layout.corridors.len(), layout.rooms.len() - 1);
+
+            let rooms: Vec<Footprint> = layout
+                .rooms
+                .iter()
+                .map(|room| room_rect(&templates[room.template], room))
+                .collect();
+            for (index, rect) in rooms.iter().enumerate() {
+                for other in &rooms[index + 1..] {
+                    assert!(!rect.overlaps(other), "rooms overlap in dungeon {seed}");
+                }
+            }
+
+            let corridors: Vec<Footprint> = layout
+                .corridors
+                .iter()
+                .map(|corridor| Footprint::from_segment(corridor.from, corridor.to, half_width))
+                .collect();
+            for (index, rect) in corridors.iter().enumerate() {
+                for other in &corridors[index + 1..] {
+                    assert!(!rect.overlaps(other), "corridors overlap in dungeon {seed}");
+                }
+            }
+        }
+    }
+}
@@ -1,16 +1,234 @@
 use bevy::prelude::*;
+use bevy_kira_audio::{AudioInstance, AudioTween};
 
-use crate::{world::SpawnProperty, lobby::LevelCode};
+use crate::{
+    actor::{character::CameraBounds, HiddenToMortals, Perception},
+    component::{
+        CollisionTag, Damage, Despawn, DespawnReason, Health, Resistances, Respawn, SpawnPoint,
+    },
+    core::{CoreGameState, KnownLevel},
+    lobby::LevelCode,
+    world::SpawnProperty,
+};
 
-use super::{hub::HubPlugins, custom::CustomPlugins};
+use super::{
+    checkpoint::CheckpointPlugins,
+    collider::{
+        ColliderOnly, ColliderShape, LevelColliderPlugins, LevelCollisionGroups, SensorVolume,
+    },
+    custom::CustomPlugins,
+    dungeon::{DungeonPlugins, RoomConnector, RoomTemplate},
+    hub::HubPlugins,
+    navmesh::NavMeshPlugins,
+    trigger::{Checkpoint, KillZone, LevelExit, MusicZone, TriggerPlugins},
+};
 
+/// Marks the root entity of everything owned by a level.
+///
+/// Every entity with this component is despawned recursively when the level is left,
+/// so scene roots, colliders and spawn points attached as children are cleaned up with it.
 #[derive(Component)]
 pub struct Affiliation(pub LevelCode);
 
+/// An audio instance owned by a level.
+///
+/// Must be placed on an entity with [`Affiliation`], the instance is stopped on level unload.
+#[derive(Component)]
+pub struct LevelAudio(pub Handle<AudioInstance>);
+
+/// The level that is currently loaded (or being loaded).
+#[derive(Resource, Debug, Clone)]
+pub struct CurrentLevel(pub LevelCode);
+
+impl Default for CurrentLevel {
+    fn default() -> Self {
+        Self(LevelCode::Known(KnownLevel::Hub))
+    }
+}
+
 pub struct MapPlugins;
 
 impl Plugin for MapPlugins {
     fn build(&self, app: &mut App) {
-        app.init_resource::<SpawnProperty>().add_plugins((HubPlugins, CustomPlugins));
+        register_level_types(app);
+        app.init_resource::<SpawnProperty>()
+            .init_resource::<CurrentLevel>()
+            .add_plugins((
+                HubPlugins,
+                CustomPlugins,
+                DungeonPlugins,
+                LevelColliderPlugins,
+                TriggerPlugins,
+                CheckpointPlugins,
+                NavMeshPlugins,
+            ))
+            .add_systems(OnExit(CoreGameState::Hub), unload_level)
+            .add_systems(OnExit(CoreGameState::InGame), unload_level);
+    }
+}
+
+/// Registers the components a level may author as GLTF extras.
+///
+/// The game and the [`LevelCheckPlugins`](super::LevelCheckPlugins) both call it,
+/// so the check knows exactly the components the game injects.
+pub fn register_level_types(app: &mut App) {
+    app.register_type::<ColliderShape>()
+        .register_type::<ColliderOnly>()
+        .register_type::<SensorVolume>()
+        .register_type::<LevelCollisionGroups>()
+        .register_type::<LevelExit>()
+        .register_type::<KillZone>()
+        .register_type::<Checkpoint>()
+        .register_type::<MusicZone>()
+        .register_type::<RoomTemplate>()
+        .register_type::<RoomConnector>()
+        .register_type::<SpawnPoint>()
+        .register_type::<Respawn>()
+        .register_type::<Despawn>()
+        .register_type::<DespawnReason>()
+        .register_type::<Vec<DespawnReason>>()
+        .register_type::<CollisionTag>()
+        .register_type::<Health>()
+        .register_type::<Damage>()
+        .register_type::<Resistances>()
+        .register_type::<CameraBounds>()
+        .register_type::<HiddenToMortals>()
+        .register_type::<Perception>();
+}
+
+/// Despawns everything owned by the level that is being left.
+///
+/// Stops the level [`LevelAudio`] and clears the collected [`SpawnProperty`],
+/// so the next level starts from scratch.
+fn unload_level(
+    mut commands: Commands,
+    affiliation_query: Query<(Entity, &Affiliation, Option<&LevelAudio>)>,
+    parent_query: Query<&Parent>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    mut spawn_property: ResMut<SpawnProperty>,
+) {
+    for (entity, Affiliation(level_code), audio) in affiliation_query.iter() {
+        log::debug!("unload {:?} from {:?}", entity, level_code);
+        if let Some(LevelAudio(handle)) = audio {
+            if let Some(instance) = audio_instances.get_mut(handle) {
+                instance.stop(AudioTween::default());
+            }
+        }
+        // children are despawned together with the affiliated root
+        let affiliated_parent = parent_query
+            .get(entity)
+            .is_ok_and(|parent| affiliation_query.contains(parent.get()));
+        if !affiliated_parent {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    *spawn_property = SpawnProperty::empty();
+}
+
+/// Whether the entity is owned by a level, by itself or through one of its ancestors.
+pub(super) fn is_level_entity(
+    entity: Entity,
+    parent_query: &Query<&Parent>,
+    affiliation_query: &Query<(), With<Affiliation>>,
+) -> bool {
+    affiliation_query.contains(entity)
+        || parent_query
+            .iter_ancestors(entity)
+            .any(|ancestor| affiliation_query.contains(ancestor))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_rapier3d::prelude::Collider;
+
+    use crate::{component::SpawnPoint, world::SpawnLocation};
+
+    use super::*;
+
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.init_state::<CoreGameState>()
+            .init_resource::<SpawnProperty>()
+            .init_resource::<CurrentLevel>()
+            .init_resource::<Assets<AudioInstance>>()
+            .add_systems(OnExit(CoreGameState::InGame), unload_level);
+        app
+    }
+
+    /// Spawns what a loaded level owns: a scene root with geometry, a spawn point and music.
+    fn load(app: &mut App, level_code: LevelCode) {
+        app.insert_resource(CurrentLevel(level_code.clone()));
+        app.world
+            .resource_mut::<NextState<CoreGameState>>()
+            .set(CoreGameState::InGame);
+        app.update();
+
+        let world = &mut app.world;
+        let root = world.spawn(Affiliation(level_code.clone())).id();
+        let collider = world
+            .spawn(Collider::cuboid(1., 1., 1.))
+            .set_parent(root)
+            .id();
+        world
+            .spawn((SpawnPoint::default(), TransformBundle::default()))
+            .set_parent(collider);
+        let spawn_point = world.spawn(SpawnPoint::default()).set_parent(root).id();
+        world.spawn((Affiliation(level_code), LevelAudio(Handle::default())));
+        world
+            .resource_mut::<SpawnProperty>()
+            .push(SpawnLocation::Point(spawn_point));
+    }
+
+    fn unload(app: &mut App) {
+        app.world
+            .resource_mut::<NextState<CoreGameState>>()
+            .set(CoreGameState::Hub);
+        app.update();
+    }
+
+    fn level_entities(app: &mut App) -> usize {
+        app.world.run_system_once(
+            |entity_query: Query<Entity>,
+             parent_query: Query<&Parent>,
+             affiliation_query: Query<(), With<Affiliation>>| {
+                entity_query
+                    .iter()
+                    .filter(|entity| is_level_entity(*entity, &parent_query, &affiliation_query))
+                    .count()
+            },
+        )
+    }
+
+    fn assert_unloaded(app: &mut App) {
+        assert_eq!(level_entities(app), 0);
+        let world = &mut app.world;
+        assert_eq!(world.query::<&Collider>().iter(world).count(), 0);
+        assert_eq!(world.query::<&SpawnPoint>().iter(world).count(), 0);
+        assert_eq!(world.query::<&LevelAudio>().iter(world).count(), 0);
+        assert!(world.resource::<SpawnProperty>().is_empty());
+    }
+
+    #[test]
+    fn reload_leaves_nothing_behind() {
+        let mut app = test_app();
+        let first = LevelCode::Path("first".into());
+        let second = LevelCode::Path("second".into());
+
+        load(&mut app, first);
+        assert_eq!(level_entities(&mut app), 5);
+        unload(&mut app);
+        assert_unloaded(&mut app);
+
+        load(&mut app, second.clone());
+        assert_eq!(level_entities(&mut app), 5);
+        let world = &mut app.world;
+        assert!(world
+            .query::<&Affiliation>()
+            .iter(world)
+            .all(|Affiliation(level_code)| *level_code == second));
+        unload(&mut app);
+        assert_unloaded(&mut app);
     }
 }
@@ -1,7 +1,33 @@
 #![allow(clippy::module_inception)]
 
+mod check;
+mod checkpoint;
+mod collider;
 mod custom;
+mod dungeon;
 mod hub;
 mod level;
+mod navmesh;
+mod package;
+mod trigger;
+mod voronoi_cells;
 
+pub use check::{
+    IssueKind, LevelCheckPlugins, LevelCheckSettings, LevelIssue, LevelReport, Severity,
+};
+pub use checkpoint::{ActiveCheckpoints, CheckpointActivated};
+pub use collider::{collider_from_mesh, ColliderShape, LevelCollisionGroups, SensorVolume};
+pub use dungeon::{DungeonConfig, DungeonExit};
 pub use level::*;
+pub use navmesh::{mesh_triangles, NavMesh, NavMeshSettings, PathfindingQuery};
+pub use package::{
+    content_hash, level_content_hash, LevelManifest, LevelPackage, LevelPackagePlugins,
+    LevelPackages, PackageDependency, PackageError, MANIFEST_FILE, PACKAGE_EXTENSION,
+    PACKAGE_SOURCE,
+};
+pub(crate) use package::{level_path, package_path, verify_content_hash};
+pub use trigger::{
+    Checkpoint, KillZone, LevelExit, MusicZone, TriggerEvent, TriggerPhase, KILL_FLOOR_HEIGHT,
+    OUT_OF_WORLD_HEIGHT,
+};
+pub use voronoi_cells::{VoronoiCell, VoronoiConfig};
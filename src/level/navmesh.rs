@@ -0,0 +1,709 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::{
+    prelude::*,
+    render::mesh::PrimitiveTopology,
+    scene::SceneInstance,
+    transform::TransformSystem,
+};
+use bevy_rapier3d::prelude::{Collider, Sensor};
+use serde::{Deserialize, Serialize};
+
+use crate::{core::CoreGameState, lobby::LevelCode};
+
+use super::{
+    collider::{build_level_colliders, queue_level_meshes, PendingCollider},
+    is_level_entity,
+    package::{level_path, package_path},
+    Affiliation, CurrentLevel, LevelPackages,
+};
+
+/// Extension of the navmesh cache file placed next to the level `.glb`.
+pub const NAVMESH_EXTENSION: &str = "navmesh";
+
+/// Settings of the navmesh bake.
+///
+/// A cached navmesh is not rebaked when these change, delete the cache file instead.
+#[derive(Resource, Debug, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct NavMeshSettings {
+    /// Steepest walkable slope in degrees.
+    pub max_slope: f32,
+    /// Highest step between two connected surfaces.
+    pub max_step: f32,
+    /// Distance below which two edges are considered touching.
+    pub edge_tolerance: f32,
+    /// Narrowest gap an agent walks through.
+    pub min_portal_width: f32,
+    /// Cell size of the spatial index.
+    pub cell_size: f32,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            max_slope: 45.,
+            max_step: 0.6,
+            edge_tolerance: 0.05,
+            min_portal_width: 0.5,
+            cell_size: 4.,
+        }
+    }
+}
+
+/// Walkable triangles of a level and the portals between them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NavMesh {
+    triangles: Vec<[[f32; 3]; 3]>,
+    portals: Vec<Vec<Portal>>,
+    cell_size: f32,
+    /// Triangles by the cells their bounds touch, rebuilt after loading.
+    #[serde(skip)]
+    grid: HashMap<(i32, i32), Vec<usize>>,
+}
+
+/// Shared segment between two triangles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Portal {
+    to: usize,
+    a: [f32; 3],
+    b: [f32; 3],
+}
+
+impl NavMesh {
+    /// Bakes a navmesh from world space triangles.
+    ///
+    /// Triangles facing up no steeper than [`NavMeshSettings::max_slope`] are kept,
+    /// they are connected where their edges touch within [`NavMeshSettings::max_step`].
+    pub fn bake(
+        triangles: impl IntoIterator<Item = [Vec3; 3]>,
+        settings: &NavMeshSettings,
+    ) -> Self {
+        let min_normal_y = settings.max_slope.to_radians().cos();
+        let walkable = triangles
+            .into_iter()
+            .filter(|[a, b, c]| {
+                let normal = (*b - *a).cross(*c - *a);
+                normal.length_squared() > f32::EPSILON
+                    && normal.normalize().y >= min_normal_y
+            })
+            .collect::<Vec<_>>();
+
+        let mut edge_grid: HashMap<(i32, i32), Vec<(usize, Vec3, Vec3)>> = HashMap::new();
+        for (index, triangle) in walkable.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                for cell in cells(a.min(b), a.max(b), settings.edge_tolerance, settings.cell_size) {
+                    edge_grid.entry(cell).or_default().push((index, a, b));
+                }
+            }
+        }
+
+        let mut portals = vec![Vec::new(); walkable.len()];
+        let mut linked = HashSet::new();
+        for edges in edge_grid.values() {
+            for (i, (from, a, b)) in edges.iter().enumerate() {
+                for (to, c, d) in &edges[i + 1..] {
+                    let pair = (*from.min(to), *from.max(to));
+                    if from == to || linked.contains(&pair) {
+                        continue;
+                    }
+                    if let Some((p, q)) = shared_segment((*a, *b), (*c, *d), settings) {
+                        linked.insert(pair);
+                        portals[*from].push(Portal {
+                            to: *to,
+                            a: p.into(),
+                            b: q.into(),
+                        });
+                        portals[*to].push(Portal {
+                            to: *from,
+                            a: p.into(),
+                            b: q.into(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut navmesh = Self {
+            triangles: walkable
+                .into_iter()
+                .map(|triangle| triangle.map(Into::into))
+                .collect(),
+            portals,
+            cell_size: settings.cell_size,
+            grid: HashMap::new(),
+        };
+        navmesh.build_grid();
+        navmesh
+    }
+
+    /// Loads a navmesh cached with [`NavMesh::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut navmesh: Self = bincode::deserialize(bytes).map_err(io::Error::other)?;
+        navmesh.build_grid();
+        Ok(navmesh)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, bincode::serialize(self).map_err(io::Error::other)?)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Finds the triangle under (or closest to) the point.
+    pub fn locate(&self, point: Vec3) -> Option<usize> {
+        let (x, z) = cell_of(point, self.cell_size);
+        let candidates = self.grid.get(&(x, z)).into_iter().flatten();
+        let containing = candidates
+            .filter_map(|index| {
+                surface_height(self.triangle(*index), point)
+                    .map(|height| (*index, (height - point.y).abs()))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index);
+
+        containing.or_else(|| {
+            (x - 1..=x + 1)
+                .flat_map(|x| (z - 1..=z + 1).map(move |z| (x, z)))
+                .filter_map(|cell| self.grid.get(&cell))
+                .flatten()
+                .min_by(|a, b| {
+                    let a = self.centroid(**a).distance_squared(point);
+                    let b = self.centroid(**b).distance_squared(point);
+                    a.total_cmp(&b)
+                })
+                .copied()
+        })
+    }
+
+    /// Finds a smoothed path between two points, both included.
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Option<Vec<Vec3>> {
+        let start = self.locate(from)?;
+        let goal = self.locate(to)?;
+        let corridor = self.search(start, goal, from, to)?;
+
+        // the funnel walks through the portals, the ends are zero width portals
+        let mut portals = vec![(from, from)];
+        for window in corridor.windows(2) {
+            let (current, next) = (window[0], window[1]);
+            let portal = self.portals[current].iter().find(|p| p.to == next)?;
+            let (a, b) = (Vec3::from(portal.a), Vec3::from(portal.b));
+            let direction = flat(self.centroid(next) - self.centroid(current));
+            if cross(direction, flat(a - self.centroid(current))) > 0. {
+                portals.push((a, b));
+            } else {
+                portals.push((b, a));
+            }
+        }
+        portals.push((to, to));
+
+        Some(funnel(&portals))
+    }
+
+    /// A* over the triangles, returns the visited triangles from start to goal.
+    fn search(&self, start: usize, goal: usize, from: Vec3, to: Vec3) -> Option<Vec<usize>> {
+        let position = |index: usize| {
+            if index == start {
+                from
+            } else if index == goal {
+                to
+            } else {
+                self.centroid(index)
+            }
+        };
+
+        let mut open = BinaryHeap::from([Visit {
+            cost: from.distance(to),
+            triangle: start,
+        }]);
+        let mut came_from = HashMap::new();
+        let mut best = HashMap::from([(start, 0_f32)]);
+
+        while let Some(Visit { triangle, .. }) = open.pop() {
+            if triangle == goal {
+                let mut corridor = vec![goal];
+                while let Some(previous) = came_from.get(corridor.last().unwrap()) {
+                    corridor.push(*previous);
+                }
+                corridor.reverse();
+                return Some(corridor);
+            }
+
+            let cost = best[&triangle];
+            for portal in &self.portals[triangle] {
+                let next_cost = cost + position(triangle).distance(position(portal.to));
+                if best.get(&portal.to).is_some_and(|known| *known <= next_cost) {
+                    continue;
+                }
+                best.insert(portal.to, next_cost);
+                came_from.insert(portal.to, triangle);
+                open.push(Visit {
+                    cost: next_cost + position(portal.to).distance(to),
+                    triangle: portal.to,
+                });
+            }
+        }
+
+        None
+    }
+
+    fn triangle(&self, index: usize) -> [Vec3; 3] {
+        self.triangles[index].map(Vec3::from)
+    }
+
+    fn centroid(&self, index: usize) -> Vec3 {
+        let [a, b, c] = self.triangle(index);
+        (a + b + c) / 3.
+    }
+
+    fn build_grid(&mut self) {
+        self.grid.clear();
+        for index in 0..self.triangles.len() {
+            let [a, b, c] = self.triangle(index);
+            for cell in cells(a.min(b).min(c), a.max(b).max(c), 0., self.cell_size) {
+                self.grid.entry(cell).or_default().push(index);
+            }
+        }
+    }
+}
+
+/// A* frontier entry, ordered so that the cheapest one is popped first.
+#[derive(PartialEq)]
+struct Visit {
+    cost: f32,
+    triangle: usize,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn flat(v: Vec3) -> Vec2 {
+    Vec2::new(v.x, v.z)
+}
+
+/// Positive when `b` is on the left of `a`.
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn cell_of(point: Vec3, cell_size: f32) -> (i32, i32) {
+    (
+        (point.x / cell_size).floor() as i32,
+        (point.z / cell_size).floor() as i32,
+    )
+}
+
+/// Cells touched by the XZ bounds.
+fn cells(min: Vec3, max: Vec3, margin: f32, cell_size: f32) -> impl Iterator<Item = (i32, i32)> {
+    let (min_x, min_z) = cell_of(min - Vec3::splat(margin), cell_size);
+    let (max_x, max_z) = cell_of(max + Vec3::splat(margin), cell_size);
+    (min_x..=max_x).flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+}
+
+/// Height of the triangle at the XZ position of the point, if the point is above or below it.
+fn surface_height([a, b, c]: [Vec3; 3], point: Vec3) -> Option<f32> {
+    let (a2, b2, c2, p) = (flat(a), flat(b), flat(c), flat(point));
+    let area = cross(b2 - a2, c2 - a2);
+    if area.abs() < f32::EPSILON {
+        return None;
+    }
+    let u = cross(c2 - b2, p - b2) / area;
+    let v = cross(a2 - c2, p - c2) / area;
+    let w = 1. - u - v;
+    (u >= 0. && v >= 0. && w >= 0.).then(|| a.y * u + b.y * v + c.y * w)
+}
+
+/// Overlapping part of two collinear edges, if it is wide enough to walk through.
+fn shared_segment(
+    (a, b): (Vec3, Vec3),
+    (c, d): (Vec3, Vec3),
+    settings: &NavMeshSettings,
+) -> Option<(Vec3, Vec3)> {
+    let length = flat(a).distance(flat(b));
+    let other_length = flat(c).distance(flat(d));
+    if length < f32::EPSILON || other_length < f32::EPSILON {
+        return None;
+    }
+    let direction = (flat(b) - flat(a)) / length;
+    let off_line = |point: Vec3| cross(direction, flat(point) - flat(a)).abs();
+    if off_line(c) > settings.edge_tolerance || off_line(d) > settings.edge_tolerance {
+        return None;
+    }
+
+    let along = |point: Vec3| direction.dot(flat(point) - flat(a));
+    let (tc, td) = (along(c), along(d));
+    let start = tc.min(td).max(0.);
+    let end = tc.max(td).min(length);
+    let min_width = settings.min_portal_width.min(length).min(other_length);
+    if end - start < min_width - settings.edge_tolerance {
+        return None;
+    }
+
+    // heights of both edges at the ends of the overlap
+    let on_edge = |t: f32| a.lerp(b, t / length);
+    let on_other = |t: f32| c.lerp(d, ((t - tc) / (td - tc)).clamp(0., 1.));
+    let (p, q) = (on_edge(start), on_edge(end));
+    let (p_other, q_other) = (on_other(start), on_other(end));
+    if (p.y - p_other.y).abs() > settings.max_step || (q.y - q_other.y).abs() > settings.max_step {
+        return None;
+    }
+
+    let raise = |point: Vec3, other: Vec3| Vec3::new(point.x, point.y.max(other.y), point.z);
+    Some((raise(p, p_other), raise(q, q_other)))
+}
+
+/// Simple stupid funnel over `(left, right)` portals, the first and last ones are the path ends.
+fn funnel(portals: &[(Vec3, Vec3)]) -> Vec<Vec3> {
+    let (mut apex, mut left, mut right) = (portals[0].0, portals[0].0, portals[0].1);
+    let (mut apex_index, mut left_index, mut right_index) = (0, 0, 0);
+    let mut path = vec![apex];
+
+    let mut i = 1;
+    while i < portals.len() {
+        let (next_left, next_right) = portals[i];
+        let apex_flat = flat(apex);
+        let to = |point: Vec3| flat(point) - apex_flat;
+
+        // tighten the right side
+        if cross(to(right), to(next_right)) >= 0. {
+            if apex == right || cross(to(left), to(next_right)) <= 0. {
+                right = next_right;
+                right_index = i;
+            } else {
+                // the right side crossed the left one, the left corner is on the path
+                path.push(left);
+                apex = left;
+                apex_index = left_index;
+                right = apex;
+                right_index = apex_index;
+                i = apex_index + 1;
+                continue;
+            }
+        }
+
+        // tighten the left side
+        if cross(to(left), to(next_left)) <= 0. {
+            if apex == left || cross(to(right), to(next_left)) >= 0. {
+                left = next_left;
+                left_index = i;
+            } else {
+                path.push(right);
+                apex = right;
+                apex_index = right_index;
+                left = apex;
+                left_index = apex_index;
+                i = apex_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    let end = portals[portals.len() - 1].0;
+    if path.last() != Some(&end) {
+        path.push(end);
+    }
+    path
+}
+
+/// Paths over the navmesh of the current level.
+///
+/// Empty until the level is baked (or loaded from the cache).
+#[derive(Resource, Default)]
+pub struct PathfindingQuery {
+    navmesh: Option<NavMesh>,
+}
+
+impl PathfindingQuery {
+    pub fn is_ready(&self) -> bool {
+        self.navmesh.is_some()
+    }
+
+    pub fn navmesh(&self) -> Option<&NavMesh> {
+        self.navmesh.as_ref()
+    }
+
+    /// Finds a smoothed path between two points, both included.
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Option<Vec<Vec3>> {
+        self.navmesh.as_ref()?.find_path(from, to)
+    }
+}
+
+/// The current level waits for its colliders to be baked.
+#[derive(Resource)]
+struct PendingBake {
+    /// Where the result is cached, only levels loaded from a file are cached.
+    cache: Option<PathBuf>,
+}
+
+pub struct NavMeshPlugins;
+
+impl Plugin for NavMeshPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavMeshSettings>()
+            .register_type::<NavMeshSettings>()
+            .init_resource::<PathfindingQuery>()
+            .add_systems(OnEnter(CoreGameState::Hub), request_bake)
+            .add_systems(OnEnter(CoreGameState::InGame), request_bake)
+            .add_systems(OnExit(CoreGameState::Hub), clear_navmesh)
+            .add_systems(OnExit(CoreGameState::InGame), clear_navmesh)
+            .add_systems(
+                PostUpdate,
+                bake_navmesh
+                    .run_if(resource_exists::<PendingBake>)
+                    .after(build_level_colliders)
+                    .after(queue_level_meshes)
+                    .after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn cache_path(level_code: &LevelCode) -> Option<(PathBuf, PathBuf)> {
+    match level_code {
+        LevelCode::Path(path) => {
+            let level = [level_path(path), package_path(path)]
+                .into_iter()
+                .find(|level| level.exists())?;
+            Some((level.with_extension(NAVMESH_EXTENSION), level))
+        }
+        LevelCode::Url(_) | LevelCode::Known(_) => None,
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Loads the cached navmesh of the level, or schedules a bake.
+fn request_bake(
+    mut commands: Commands,
+    current_level: Res<CurrentLevel>,
+    packages: Res<LevelPackages>,
+    mut pathfinding: ResMut<PathfindingQuery>,
+) {
+    // a package ships its navmesh
+    let packaged = packages
+        .manifest()
+        .and_then(|manifest| manifest.navmesh)
+        .and_then(|navmesh| packages.file(&navmesh));
+    if let Some(bytes) = packaged {
+        match NavMesh::from_bytes(&bytes) {
+            Ok(navmesh) => {
+                log::info!("navmesh loaded from the level package");
+                pathfinding.navmesh = Some(navmesh);
+                return;
+            }
+            Err(err) => log::warn!("failed to load the packaged navmesh: {}", err),
+        }
+    }
+
+    let cache = cache_path(&current_level.0);
+    if let Some((cache, level)) = &cache {
+        let fresh = modified(cache)
+            .zip(modified(level))
+            .is_some_and(|(cache, level)| cache >= level);
+        if fresh {
+            match NavMesh::load(cache) {
+                Ok(navmesh) => {
+                    log::info!("navmesh loaded from {:?}", cache);
+                    pathfinding.navmesh = Some(navmesh);
+                    return;
+                }
+                Err(err) => log::warn!("failed to load navmesh {:?}: {}", cache, err),
+            }
+        }
+    }
+
+    commands.insert_resource(PendingBake {
+        cache: cache.map(|(cache, _)| cache),
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn bake_navmesh(
+    mut commands: Commands,
+    pending: Res<PendingBake>,
+    pending_collider_query: Query<(), With<PendingCollider>>,
+    scene_query: Query<(Entity, Option<&SceneInstance>), With<Handle<Scene>>>,
+    mesh_query: Query<(Entity, &Handle<Mesh>, &GlobalTransform), (With<Collider>, Without<Sensor>)>,
+    parent_query: Query<&Parent>,
+    affiliation_query: Query<(), With<Affiliation>>,
+    scene_spawner: Res<SceneSpawner>,
+    meshes: Res<Assets<Mesh>>,
+    settings: Res<NavMeshSettings>,
+    mut pathfinding: ResMut<PathfindingQuery>,
+) {
+    // the level colliders are built over several frames after its scenes spawn
+    let scenes_ready = scene_query
+        .iter()
+        .filter(|(entity, _)| is_level_entity(*entity, &parent_query, &affiliation_query))
+        .all(|(_, instance)| {
+            instance.is_some_and(|instance| scene_spawner.instance_is_ready(**instance))
+        });
+    if !scenes_ready || !pending_collider_query.is_empty() {
+        return;
+    }
+    commands.remove_resource::<PendingBake>();
+
+    let triangles = mesh_query
+        .iter()
+        .filter(|(entity, ..)| is_level_entity(*entity, &parent_query, &affiliation_query))
+        .filter_map(|(_, handle, transform)| {
+            Some(mesh_triangles(meshes.get(handle)?, transform))
+        })
+        .flatten();
+    let navmesh = NavMesh::bake(triangles, &settings);
+    log::info!("navmesh baked: {} triangles", navmesh.triangle_count());
+
+    if let Some(cache) = &pending.cache {
+        if let Err(err) = navmesh.save(cache) {
+            log::warn!("failed to cache navmesh {:?}: {}", cache, err);
+        }
+    }
+    pathfinding.navmesh = Some(navmesh);
+}
+
+fn clear_navmesh(mut commands: Commands, mut pathfinding: ResMut<PathfindingQuery>) {
+    commands.remove_resource::<PendingBake>();
+    pathfinding.navmesh = None;
+}
+
+/// World space triangles of a triangle list mesh.
+pub fn mesh_triangles(mesh: &Mesh, transform: &GlobalTransform) -> Vec<[Vec3; 3]> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Vec::new();
+    }
+    let Some(positions) = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(|positions| positions.as_float3())
+    else {
+        return Vec::new();
+    };
+    let positions = positions
+        .iter()
+        .map(|position| transform.transform_point(Vec3::from(*position)))
+        .collect::<Vec<_>>();
+    let indices = match mesh.indices() {
+        Some(indices) => indices.iter().collect::<Vec<_>>(),
+        None => (0..positions.len()).collect(),
+    };
+
+    indices
+        .chunks_exact(3)
+        .filter_map(|triangle| {
+            Some([
+                *positions.get(triangle[0])?,
+                *positions.get(triangle[1])?,
+                *positions.get(triangle[2])?,
+            ])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two triangles of a flat square with its corner at `(x, z)`, facing up.
+    fn square(x: f32, z: f32, size: f32) -> [[Vec3; 3]; 2] {
+        let a = Vec3::new(x, 0., z);
+        let b = Vec3::new(x, 0., z + size);
+        let c = Vec3::new(x + size, 0., z);
+        let d = Vec3::new(x + size, 0., z + size);
+        [[a, b, c], [c, b, d]]
+    }
+
+    fn bake(squares: &[[[Vec3; 3]; 2]]) -> NavMesh {
+        NavMesh::bake(
+            squares.iter().flatten().copied(),
+            &NavMeshSettings::default(),
+        )
+    }
+
+    fn assert_path(path: &[Vec3], expected: &[Vec3]) {
+        assert_eq!(path.len(), expected.len(), "{:?}", path);
+        for (point, expected) in path.iter().zip(expected) {
+            assert!(point.distance(*expected) < 1e-3, "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn path_across_two_triangles_is_straight() {
+        let navmesh = bake(&[square(0., 0., 4.)]);
+        assert_eq!(navmesh.triangle_count(), 2);
+
+        let (from, to) = (Vec3::new(0.5, 0., 3.5), Vec3::new(3.5, 0., 0.5));
+        assert_path(&navmesh.find_path(from, to).unwrap(), &[from, to]);
+    }
+
+    #[test]
+    fn path_turns_around_the_inner_corner() {
+        let navmesh = bake(&[square(0., 0., 4.), square(4., 0., 4.), square(0., 4., 4.)]);
+        let (from, to) = (Vec3::new(7., 0., 2.), Vec3::new(2., 0., 7.));
+        let corner = Vec3::new(4., 0., 4.);
+
+        assert_path(&navmesh.find_path(from, to).unwrap(), &[from, corner, to]);
+        assert_path(&navmesh.find_path(to, from).unwrap(), &[to, corner, from]);
+    }
+
+    #[test]
+    fn steep_triangles_are_not_walkable() {
+        let [flat_a, flat_b] = square(0., 0., 4.);
+        let steep = [Vec3::ZERO, Vec3::new(0., 3., 1.), Vec3::X];
+        let navmesh = NavMesh::bake([flat_a, flat_b, steep], &NavMeshSettings::default());
+        assert_eq!(navmesh.triangle_count(), 2);
+    }
+
+    #[test]
+    fn unreachable_goal_has_no_path() {
+        let navmesh = bake(&[square(0., 0., 4.), square(20., 20., 4.)]);
+        assert!(navmesh.locate(Vec3::new(21., 0., 21.)).is_some());
+        assert!(navmesh
+            .find_path(Vec3::new(1., 0., 1.), Vec3::new(21., 0., 21.))
+            .is_none());
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let navmesh = bake(&[square(0., 0., 4.), square(4., 0., 4.), square(0., 4., 4.)]);
+        let path = std::env::temp_dir().join(format!(
+            "navmesh-test-{}.{}",
+            std::process::id(),
+            NAVMESH_EXTENSION
+        ));
+        navmesh.save(&path).unwrap();
+        let loaded = NavMesh::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.triangle_count(), navmesh.triangle_count());
+        let (from, to) = (Vec3::new(7., 0., 2.), Vec3::new(2., 0., 7.));
+        assert_eq!(loaded.find_path(from, to), navmesh.find_path(from, to));
+        assert!(NavMesh::from_bytes(b"not a navmesh").is_err());
+    }
+}
@@ -0,0 +1,642 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use bevy::{
+    asset::io::{AssetReader, AssetReaderError, AssetSource, PathStream, Reader, VecReader},
+    prelude::*,
+    tasks::{block_on, futures_lite::future, futures_lite::stream, AsyncComputeTaskPool, Task},
+    utils::BoxedFuture,
+};
+use bevy_kira_audio::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::ZipArchive;
+
+use crate::{
+    core::{write_dynamic_level, CoreGameState},
+    lobby::LevelCode,
+    ASSET_DIR,
+};
+
+use super::{Affiliation, CurrentLevel, LevelAudio};
+
+/// Extension of a level package.
+pub const PACKAGE_EXTENSION: &str = "jlvl";
+/// Asset source the mounted package is read from, e.g. `level-package://<hash>/music/theme.ogg`.
+///
+/// Paths start with the [`content_hash`] of the package, so the assets of another package
+/// are never served from the cache.
+pub const PACKAGE_SOURCE: &str = "level-package";
+/// Manifest file at the root of a package.
+pub const MANIFEST_FILE: &str = "manifest.yaml";
+
+/// Longest a package download may take, connecting included.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+/// Largest package that is downloaded, in bytes.
+const MAX_PACKAGE_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Description of a level package, stored as [`MANIFEST_FILE`].
+///
+/// Paths are relative to the package root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelManifest {
+    pub name: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub version: u32,
+    /// The level `.glb`.
+    pub level: String,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    /// Background music, the first track is played when the level starts.
+    #[serde(default)]
+    pub music: Vec<String>,
+    /// Prebaked navmesh, the level is baked on load without it.
+    #[serde(default)]
+    pub navmesh: Option<String>,
+    /// Level scripts, kept for the scripting hooks.
+    #[serde(default)]
+    pub scripts: Vec<String>,
+    /// Packages the level takes assets from, they are mounted together with it.
+    #[serde(default)]
+    pub dependencies: Vec<PackageDependency>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageDependency {
+    /// Package path relative to the level folder, without the extension.
+    pub package: String,
+    /// Expected [`content_hash`] of the package.
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum PackageError {
+    Io(io::Error),
+    Archive(zip::result::ZipError),
+    Manifest(serde_yaml::Error),
+    Download(String),
+    MissingFile(String),
+    MissingDependency(String, Box<PackageError>),
+    HashMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageError::Io(err) => write!(f, "{err}"),
+            PackageError::Archive(err) => write!(f, "broken archive: {err}"),
+            PackageError::Manifest(err) => write!(f, "broken {MANIFEST_FILE}: {err}"),
+            PackageError::Download(err) => write!(f, "download failed: {err}"),
+            PackageError::MissingFile(file) => write!(f, "{file} is not in the package"),
+            PackageError::MissingDependency(package, err) => {
+                write!(f, "dependency {package}: {err}")
+            }
+            PackageError::HashMismatch { expected, actual } => {
+                write!(f, "content hash {actual} does not match {expected}")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for PackageError {
+    fn from(err: io::Error) -> Self {
+        PackageError::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for PackageError {
+    fn from(err: zip::result::ZipError) -> Self {
+        PackageError::Archive(err)
+    }
+}
+
+/// Hex encoded SHA-256 of the content.
+pub fn content_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Hash the host sends with a map change, peers refuse a level with another hash.
+///
+/// [`None`] for known levels, they are built into the game.
+pub fn level_content_hash(level_code: &LevelCode) -> Option<String> {
+    let path = match level_code {
+        LevelCode::Path(path) => [level_path(path), package_path(path)]
+            .into_iter()
+            .find(|path| path.exists())?,
+        LevelCode::Url(url) => download_path(url),
+        LevelCode::Known(_) => return None,
+    };
+    fs::read(path).ok().map(|bytes| content_hash(&bytes))
+}
+
+pub(crate) fn level_path(path: &str) -> PathBuf {
+    Path::new(ASSET_DIR).join("level").join(format!("{path}.glb"))
+}
+
+pub(crate) fn package_path(path: &str) -> PathBuf {
+    Path::new(ASSET_DIR)
+        .join("level")
+        .join(format!("{path}.{PACKAGE_EXTENSION}"))
+}
+
+/// Where a package from the url is downloaded to.
+fn download_path(url: &str) -> PathBuf {
+    Path::new(ASSET_DIR)
+        .join("level")
+        .join("download")
+        .join(format!("{}.{PACKAGE_EXTENSION}", &content_hash(url.as_bytes())[..16]))
+}
+
+/// Checks the content hash of the file if one is expected.
+pub(crate) fn verify_content_hash(
+    path: &Path,
+    expected: Option<&str>,
+) -> Result<(), PackageError> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let actual = content_hash(&fs::read(path)?);
+    if actual != expected {
+        return Err(PackageError::HashMismatch {
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// An unpacked level package.
+pub struct LevelPackage {
+    pub manifest: LevelManifest,
+    /// [`content_hash`] of the archive.
+    pub hash: String,
+    files: HashMap<PathBuf, Arc<[u8]>>,
+}
+
+impl LevelPackage {
+    pub fn open(path: &Path) -> Result<Self, PackageError> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, PackageError> {
+        let hash = content_hash(&bytes);
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+
+        let mut files = HashMap::new();
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            let Some(name) = file.enclosed_name().map(Path::to_path_buf) else {
+                log::warn!("skipping unsafe package path {}", file.name());
+                continue;
+            };
+            if file.is_dir() {
+                continue;
+            }
+            let mut content = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut content)?;
+            files.insert(name, Arc::from(content));
+        }
+
+        let manifest: LevelManifest = serde_yaml::from_slice(
+            files
+                .get(Path::new(MANIFEST_FILE))
+                .ok_or_else(|| PackageError::MissingFile(MANIFEST_FILE.to_string()))?,
+        )
+        .map_err(PackageError::Manifest)?;
+        if !files.contains_key(Path::new(&manifest.level)) {
+            return Err(PackageError::MissingFile(manifest.level));
+        }
+
+        Ok(Self {
+            manifest,
+            hash,
+            files,
+        })
+    }
+
+    pub fn file(&self, path: &Path) -> Option<Arc<[u8]>> {
+        self.files.get(path).cloned()
+    }
+}
+
+/// The mounted package followed by its dependencies, shared with the asset reader.
+#[derive(Clone, Default)]
+struct MountedPackages(Arc<RwLock<Vec<LevelPackage>>>);
+
+impl MountedPackages {
+    /// File of an asset path, only while the package of its hash is mounted.
+    fn asset_file(&self, path: &Path) -> Option<Arc<[u8]>> {
+        let mut components = path.components();
+        let hash = components.next()?.as_os_str();
+        let mounted = self
+            .0
+            .read()
+            .unwrap()
+            .first()
+            .is_some_and(|package| hash == package.hash.as_str());
+        if !mounted {
+            return None;
+        }
+        self.file(components.as_path())
+    }
+
+    fn file(&self, path: &Path) -> Option<Arc<[u8]>> {
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .find_map(|package| package.file(path))
+    }
+
+    /// Asset paths of the files.
+    fn paths(&self) -> Vec<PathBuf> {
+        let packages = self.0.read().unwrap();
+        let Some(hash) = packages.first().map(|package| Path::new(&package.hash)) else {
+            return Vec::new();
+        };
+        packages
+            .iter()
+            .flat_map(|package| package.files.keys().map(|file| hash.join(file)))
+            .collect()
+    }
+}
+
+/// Serves the [`PACKAGE_SOURCE`] assets from the mounted packages.
+struct PackageReader(MountedPackages);
+
+impl AssetReader for PackageReader {
+    fn read<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+        Box::pin(async move {
+            let bytes = self
+                .0
+                .asset_file(path)
+                .ok_or_else(|| AssetReaderError::NotFound(path.to_path_buf()))?;
+            let reader: Box<Reader> = Box::new(VecReader::new(bytes.to_vec()));
+            Ok(reader)
+        })
+    }
+
+    fn read_meta<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+        // packages carry no meta files, the default loader settings are used
+        Box::pin(async move { Err(AssetReaderError::NotFound(path.to_path_buf())) })
+    }
+
+    fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<PathStream>, AssetReaderError>> {
+        Box::pin(async move {
+            let entries = self
+                .0
+                .paths()
+                .into_iter()
+                .filter(|file| file.parent() == Some(path))
+                .collect::<Vec<_>>();
+            let stream: Box<PathStream> = Box::new(stream::iter(entries));
+            Ok(stream)
+        })
+    }
+
+    fn is_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<bool, AssetReaderError>> {
+        Box::pin(async move {
+            Ok(self
+                .0
+                .paths()
+                .iter()
+                .any(|file| file != path && file.starts_with(path)))
+        })
+    }
+}
+
+/// Level packages mounted as the [`PACKAGE_SOURCE`] asset source.
+#[derive(Resource, Default)]
+pub struct LevelPackages {
+    mounted: MountedPackages,
+    download: Option<PackageDownload>,
+}
+
+struct PackageDownload {
+    url: String,
+    expected_hash: Option<String>,
+    task: Task<Result<PathBuf, PackageError>>,
+}
+
+impl LevelPackages {
+    /// Mounts the package with its dependencies and returns the asset path of its level.
+    pub fn mount(
+        &mut self,
+        path: &Path,
+        expected_hash: Option<&str>,
+    ) -> Result<String, PackageError> {
+        let package = LevelPackage::open(path)?;
+        if let Some(expected) = expected_hash {
+            if expected != package.hash {
+                return Err(PackageError::HashMismatch {
+                    expected: expected.to_string(),
+                    actual: package.hash,
+                });
+            }
+        }
+
+        // dependencies of the dependencies are not followed
+        let mut packages = Vec::with_capacity(package.manifest.dependencies.len() + 1);
+        for dependency in &package.manifest.dependencies {
+            let mount_dependency = || {
+                let path = package_path(&dependency.package);
+                verify_content_hash(&path, dependency.hash.as_deref())?;
+                LevelPackage::open(&path)
+            };
+            packages.push(mount_dependency().map_err(|err| {
+                PackageError::MissingDependency(dependency.package.clone(), Box::new(err))
+            })?);
+        }
+
+        log::info!(
+            "mounted {} ({}) from {:?}",
+            package.manifest.name,
+            package.hash,
+            path
+        );
+        let level = package_asset_path(&package.hash, &package.manifest.level);
+        packages.insert(0, package);
+        *self.mounted.0.write().unwrap() = packages;
+        Ok(level)
+    }
+
+    pub fn unmount(&mut self) {
+        self.mounted.0.write().unwrap().clear();
+        self.download = None;
+    }
+
+    /// Downloads the package and mounts it once it is done, the level is loaded afterwards.
+    ///
+    /// A package downloaded before is reused if its hash matches.
+    pub fn download(&mut self, url: String, expected_hash: Option<String>) {
+        let path = download_path(&url);
+        let cached = path.exists() && verify_content_hash(&path, expected_hash.as_deref()).is_ok();
+        // the request blocks its thread, the IO pool is left to the asset loading
+        let task = AsyncComputeTaskPool::get().spawn({
+            let url = url.clone();
+            async move {
+                if !cached {
+                    log::info!("downloading {}", url);
+                    let mut bytes = Vec::new();
+                    ureq::AgentBuilder::new()
+                        .timeout(DOWNLOAD_TIMEOUT)
+                        .build()
+                        .get(&url)
+                        .call()
+                        .map_err(|err| PackageError::Download(err.to_string()))?
+                        .into_reader()
+                        .take(MAX_PACKAGE_SIZE + 1)
+                        .read_to_end(&mut bytes)?;
+                    if bytes.len() as u64 > MAX_PACKAGE_SIZE {
+                        return Err(PackageError::Download(format!(
+                            "the package is larger than {MAX_PACKAGE_SIZE} bytes"
+                        )));
+                    }
+                    fs::create_dir_all(path.parent().unwrap())?;
+                    fs::write(&path, bytes)?;
+                }
+                Ok::<_, PackageError>(path)
+            }
+        });
+        self.download = Some(PackageDownload {
+            url,
+            expected_hash,
+            task,
+        });
+    }
+
+    /// Manifest of the mounted package.
+    pub fn manifest(&self) -> Option<LevelManifest> {
+        self.mounted
+            .0
+            .read()
+            .unwrap()
+            .first()
+            .map(|package| package.manifest.clone())
+    }
+
+    /// File of the mounted packages.
+    pub fn file(&self, path: &str) -> Option<Arc<[u8]>> {
+        self.mounted.file(Path::new(path))
+    }
+
+    /// Asset path of a file of the mounted packages.
+    pub fn asset_path(&self, path: &str) -> Option<String> {
+        self.mounted
+            .0
+            .read()
+            .unwrap()
+            .first()
+            .map(|package| package_asset_path(&package.hash, path))
+    }
+}
+
+fn package_asset_path(hash: &str, path: &str) -> String {
+    format!("{PACKAGE_SOURCE}://{hash}/{path}")
+}
+
+/// Mounts level packages as an asset source.
+///
+/// Must be added before the `DefaultPlugins`, asset sources can not be added later.
+pub struct LevelPackagePlugins;
+
+impl Plugin for LevelPackagePlugins {
+    fn build(&self, app: &mut App) {
+        let packages = LevelPackages::default();
+        let mounted = packages.mounted.clone();
+        app.register_asset_source(
+            PACKAGE_SOURCE,
+            AssetSource::build().with_reader(move || Box::new(PackageReader(mounted.clone()))),
+        )
+        .insert_resource(packages)
+        .add_systems(Update, finish_download)
+        .add_systems(OnEnter(CoreGameState::InGame), play_package_music);
+    }
+}
+
+fn finish_download(
+    mut packages: ResMut<LevelPackages>,
+    mut next_state: ResMut<NextState<CoreGameState>>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    let Some(download) = &mut packages.download else {
+        return;
+    };
+    let Some(result) = block_on(future::poll_once(&mut download.task)) else {
+        return;
+    };
+    let expected_hash = download.expected_hash.take();
+    let url = std::mem::take(&mut download.url);
+    packages.download = None;
+
+    // the current level stays until the package is verified
+    match result.and_then(|path| packages.mount(&path, expected_hash.as_deref())) {
+        Ok(level) => {
+            current_level.0 = LevelCode::Url(url);
+            write_dynamic_level(&level);
+            next_state.set(CoreGameState::LoadCustomLevel);
+        }
+        Err(err) => log::error!("failed to load the level package: {}", err),
+    }
+}
+
+fn play_package_music(
+    mut commands: Commands,
+    packages: Res<LevelPackages>,
+    current_level: Res<CurrentLevel>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+) {
+    let Some(track) = packages
+        .manifest()
+        .and_then(|manifest| manifest.music.first().cloned())
+    else {
+        return;
+    };
+    let Some(path) = packages.asset_path(&track) else {
+        return;
+    };
+    let handle = audio.play(asset_server.load(path)).looped().handle();
+    commands.spawn((
+        Name::new(format!("LevelMusic:{}", track)),
+        LevelAudio(handle),
+        Affiliation(current_level.0.clone()),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    const MANIFEST: &str = "
+name: Test level
+level: level.glb
+music:
+  - music/theme.ogg
+dependencies:
+  - package: shared/props
+    hash: abc
+";
+
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn package_bytes() -> Vec<u8> {
+        archive(&[
+            (MANIFEST_FILE, MANIFEST.as_bytes()),
+            ("level.glb", b"glTF"),
+            ("music/theme.ogg", b"OggS"),
+        ])
+    }
+
+    #[test]
+    fn content_hash_is_hex_sha256() {
+        assert_eq!(
+            content_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(content_hash(b"a"), content_hash(b"b"));
+    }
+
+    #[test]
+    fn package_reads_the_manifest_and_files() {
+        let bytes = package_bytes();
+        let package = LevelPackage::from_bytes(bytes.clone()).unwrap();
+
+        assert_eq!(package.hash, content_hash(&bytes));
+        let manifest = &package.manifest;
+        assert_eq!(manifest.name, "Test level");
+        assert_eq!(manifest.level, "level.glb");
+        assert_eq!(manifest.music, vec!["music/theme.ogg".to_string()]);
+        assert_eq!(manifest.author, None);
+        assert_eq!(manifest.version, 0);
+        assert_eq!(manifest.dependencies.len(), 1);
+        assert_eq!(manifest.dependencies[0].package, "shared/props");
+        assert_eq!(manifest.dependencies[0].hash.as_deref(), Some("abc"));
+
+        assert_eq!(
+            package.file(Path::new("music/theme.ogg")).as_deref(),
+            Some(&b"OggS"[..])
+        );
+        assert!(package.file(Path::new("missing.ogg")).is_none());
+    }
+
+    #[test]
+    fn package_needs_a_manifest_and_its_level() {
+        let without_manifest = archive(&[("level.glb", b"glTF")]);
+        assert!(matches!(
+            LevelPackage::from_bytes(without_manifest),
+            Err(PackageError::MissingFile(file)) if file == MANIFEST_FILE
+        ));
+
+        let without_level = archive(&[(MANIFEST_FILE, MANIFEST.as_bytes())]);
+        assert!(matches!(
+            LevelPackage::from_bytes(without_level),
+            Err(PackageError::MissingFile(file)) if file == "level.glb"
+        ));
+
+        let broken_manifest = archive(&[(MANIFEST_FILE, b"name: [")]);
+        assert!(matches!(
+            LevelPackage::from_bytes(broken_manifest),
+            Err(PackageError::Manifest(_))
+        ));
+    }
+
+    #[test]
+    fn content_hash_is_verified() {
+        let bytes = package_bytes();
+        let hash = content_hash(&bytes);
+        let path = std::env::temp_dir().join(format!(
+            "package-test-{}.{}",
+            std::process::id(),
+            PACKAGE_EXTENSION
+        ));
+        fs::write(&path, &bytes).unwrap();
+
+        let unchecked = verify_content_hash(&path, None);
+        let matching = verify_content_hash(&path, Some(&hash));
+        let mismatch = verify_content_hash(&path, Some("0000"));
+        fs::remove_file(&path).unwrap();
+
+        assert!(unchecked.is_ok());
+        assert!(matching.is_ok());
+        match mismatch {
+            Err(PackageError::HashMismatch { expected, actual }) => {
+                assert_eq!(expected, "0000");
+                assert_eq!(actual, hash);
+            }
+            other => panic!("expected a hash mismatch, got {other:?}"),
+        }
+    }
+}
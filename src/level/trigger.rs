@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+use bevy_rapier3d::prelude::{Collider, CollisionEvent};
+
+use crate::{
+    component::{Despawn, DespawnReason, Respawn},
+    core::{KnownLevel, LoadLevelEvent},
+    lobby::{ChangeMapLobbyEvent, Character, LevelCode, LobbyState},
+    world::Me,
+};
+
+use super::{
+    collider::{build_level_colliders, SensorBundle, SensorVolume},
+    Affiliation, CurrentLevel, LevelAudio,
+};
+
+/// Height of the [`KillZone`] floor placed under generated levels.
+pub const KILL_FLOOR_HEIGHT: f32 = -20.;
+/// Height below which actors respawn even if the level has no [`KillZone`].
+pub const OUT_OF_WORLD_HEIGHT: f32 = KILL_FLOOR_HEIGHT * 5.;
+
+/// Whether a collider entered or left a trigger volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerPhase {
+    Enter,
+    Exit,
+}
+
+/// Fired when a collider enters or leaves a trigger volume with the `T` component.
+#[derive(Debug, Clone)]
+pub struct TriggerEvent<T> {
+    /// Entity with the trigger component (the GLTF node).
+    pub trigger: Entity,
+    /// Collider that entered or left the volume.
+    pub target: Entity,
+    pub phase: TriggerPhase,
+    /// Copy of the trigger component.
+    pub data: T,
+}
+
+impl<T: Component> Event for TriggerEvent<T> {}
+
+/// Leaves the current level for another one when a character enters it.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct LevelExit(pub LevelCode);
+
+impl Default for LevelExit {
+    fn default() -> Self {
+        Self(LevelCode::Known(KnownLevel::Hub))
+    }
+}
+
+/// Forces whatever enters it to respawn (or despawn if it can not).
+#[derive(Component, Reflect, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct KillZone;
+
+/// A place the players return to instead of the level spawn points.
+#[derive(Component, Reflect, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct Checkpoint;
+
+/// Switches the level music when the local player enters it.
+///
+/// Holds the music path relative to the asset folder.
+#[derive(Component, Reflect, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct MusicZone(pub String);
+
+/// Music started by a [`MusicZone`].
+#[derive(Component)]
+struct ZoneMusic(String);
+
+trait AppTriggerExt {
+    /// Adds the [`TriggerEvent`] of the GLTF trigger component `T`,
+    /// the type is registered by [`register_level_types`](super::register_level_types).
+    fn add_trigger<T: Component + Clone>(&mut self) -> &mut Self;
+}
+
+impl AppTriggerExt for App {
+    fn add_trigger<T: Component + Clone>(&mut self) -> &mut Self {
+        self.add_event::<TriggerEvent<T>>()
+            .add_systems(
+                PostUpdate,
+                mark_sensor_volume::<T>.before(build_level_colliders),
+            )
+            .add_systems(Update, fire_trigger_events::<T>)
+    }
+}
+
+pub struct TriggerPlugins;
+
+impl Plugin for TriggerPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_trigger::<LevelExit>()
+            .add_trigger::<KillZone>()
+            .add_trigger::<Checkpoint>()
+            .add_trigger::<MusicZone>()
+            .add_systems(
+                Update,
+                (
+                    kill_zone,
+                    music_zone,
+                    // clients follow the map change of the host
+                    level_exit.run_if(not(in_state(LobbyState::Client))),
+                ),
+            );
+    }
+}
+
+/// Components of a [`KillZone`] spanning below the whole level.
+pub fn kill_floor() -> impl Bundle {
+    (
+        KillZone,
+        SensorBundle::new(Collider::cuboid(1000., 1., 1000.)),
+        TransformBundle::from_transform(Transform::from_xyz(0., KILL_FLOOR_HEIGHT, 0.)),
+        Name::new("KillFloor"),
+    )
+}
+
+/// Turns the meshes of a trigger node into sensors.
+fn mark_sensor_volume<T: Component>(
+    mut commands: Commands,
+    trigger_query: Query<Entity, Added<T>>,
+) {
+    for entity in trigger_query.iter() {
+        commands.entity(entity).insert(SensorVolume);
+    }
+}
+
+fn fire_trigger_events<T: Component + Clone>(
+    mut collision_events: EventReader<CollisionEvent>,
+    trigger_query: Query<&T>,
+    parent_query: Query<&Parent>,
+    mut trigger_events: EventWriter<TriggerEvent<T>>,
+) {
+    // the sensor collider is the trigger itself or one of its mesh primitives
+    let find_trigger = |entity: Entity| {
+        std::iter::once(entity)
+            .chain(parent_query.iter_ancestors(entity))
+            .find_map(|e| trigger_query.get(e).ok().map(|data| (e, data)))
+    };
+
+    for event in collision_events.read() {
+        let (a, b, phase) = match *event {
+            CollisionEvent::Started(a, b, _) => (a, b, TriggerPhase::Enter),
+            CollisionEvent::Stopped(a, b, _) => (a, b, TriggerPhase::Exit),
+        };
+        for (sensor, target) in [(a, b), (b, a)] {
+            if let Some((trigger, data)) = find_trigger(sensor) {
+                trigger_events.send(TriggerEvent {
+                    trigger,
+                    target,
+                    phase,
+                    data: data.clone(),
+                });
+            }
+        }
+    }
+}
+
+fn kill_zone(
+    mut kill_zone_events: EventReader<TriggerEvent<KillZone>>,
+    mut respawn_query: Query<&mut Respawn>,
+    mut despawn_query: Query<&mut Despawn>,
+) {
+    for event in kill_zone_events.read() {
+        if event.phase != TriggerPhase::Enter {
+            continue;
+        }
+        if let Ok(mut respawn) = respawn_query.get_mut(event.target) {
+            respawn.insert_reason(DespawnReason::Forced);
+        } else if let Ok(mut despawn) = despawn_query.get_mut(event.target) {
+            despawn.insert_reason(DespawnReason::Forced);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn music_zone(
+    mut commands: Commands,
+    mut music_zone_events: EventReader<TriggerEvent<MusicZone>>,
+    me_query: Query<(), With<Me>>,
+    zone_music_query: Query<(Entity, &ZoneMusic, &LevelAudio)>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    current_level: Res<CurrentLevel>,
+) {
+    let Some(MusicZone(path)) = music_zone_events
+        .read()
+        .filter(|event| event.phase == TriggerPhase::Enter && me_query.contains(event.target))
+        .last()
+        .map(|event| event.data.clone())
+    else {
+        return;
+    };
+
+    if zone_music_query
+        .iter()
+        .any(|(_, ZoneMusic(playing), _)| *playing == path)
+    {
+        return;
+    }
+    for (entity, _, LevelAudio(handle)) in zone_music_query.iter() {
+        if let Some(instance) = audio_instances.get_mut(handle) {
+            instance.stop(AudioTween::default());
+        }
+        commands.entity(entity).despawn();
+    }
+
+    log::info!("music zone: {}", path);
+    let handle = audio.play(asset_server.load(path.clone())).looped().handle();
+    commands.spawn((
+        Name::new(format!("ZoneMusic:{}", path)),
+        ZoneMusic(path),
+        LevelAudio(handle),
+        Affiliation(current_level.0.clone()),
+    ));
+}
+
+fn level_exit(
+    mut level_exit_events: EventReader<TriggerEvent<LevelExit>>,
+    character_query: Query<(), With<Character>>,
+    mut load_level_event: EventWriter<LoadLevelEvent>,
+    mut change_map_event: EventWriter<ChangeMapLobbyEvent>,
+) {
+    if let Some(LevelExit(level_code)) = level_exit_events
+        .read()
+        .filter(|event| {
+            event.phase == TriggerPhase::Enter && character_query.contains(event.target)
+        })
+        .last()
+        .map(|event| event.data.clone())
+    {
+        log::info!("level exit to {:?}", level_code);
+        change_map_event.send(ChangeMapLobbyEvent(level_code.clone()));
+        load_level_event.send(LoadLevelEvent::new(level_code));
+    }
+}
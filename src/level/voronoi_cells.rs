@@ -0,0 +1,380 @@
+use bevy::{
+    math::DVec2,
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+};
+use bevy_rapier3d::prelude::Collider;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use voronoi::{make_polygons, voronoi, Point};
+
+/// Settings of the Voronoi cell generator.
+#[derive(Resource, Debug, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct VoronoiConfig {
+    /// Seed of the cell sites, the same seed gives the same cells.
+    pub seed: u64,
+    /// Amount of cell sites.
+    pub point_count: usize,
+    /// Width and depth of the rectangle (starting at the origin) the cells fill.
+    pub box_size: Vec2,
+    /// Base extrusion height of a cell.
+    pub height: f32,
+    /// Maximal deviation from the base height.
+    pub height_variation: f32,
+    /// Colors the cells are painted with, picked per cell.
+    pub materials: Vec<Color>,
+}
+
+impl Default for VoronoiConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            point_count: 12,
+            box_size: Vec2::splat(40.),
+            height: 1.,
+            height_variation: 0.5,
+            materials: vec![Color::GRAY, Color::DARK_GRAY, Color::SILVER],
+        }
+    }
+}
+
+/// A single extruded cell.
+pub struct VoronoiCell {
+    pub mesh: Mesh,
+    pub collider: Option<Collider>,
+    /// Index in [`VoronoiConfig::materials`].
+    pub material: usize,
+    pub height: f32,
+}
+
+/// Generates extruded Voronoi cells with their colliders.
+///
+/// A config edited into an empty box gives no cells, a negative variation counts as none.
+pub fn generate_cells(config: &VoronoiConfig) -> Vec<VoronoiCell> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+
+    let extent = config.box_size.as_dvec2();
+    if !(extent.min_element() > 0. && extent.is_finite()) {
+        log::warn!("voronoi box size {} is empty", config.box_size);
+        return Vec::new();
+    }
+    // `max` also turns a NaN into no variation
+    let variation = config.height_variation.max(0.);
+    let sites = (0..config.point_count)
+        .map(|_| Point::new(rng.gen_range(0.0..extent.x), rng.gen_range(0.0..extent.y)))
+        .collect::<Vec<_>>();
+    // the diagram is always square, the cells are cut down to the rectangle
+    let diagram = voronoi(sites, extent.max_element());
+
+    make_polygons(&diagram)
+        .into_iter()
+        .map(|polygon| clip_to_extent(polygon, extent))
+        .filter(|polygon| polygon.len() >= 3)
+        .map(|polygon| {
+            let height = config.height + rng.gen_range(-variation..=variation);
+            let material = rng.gen_range(0..config.materials.len().max(1));
+            let mesh = extrude_to_mesh(polygon, height.max(f32::EPSILON));
+            // cells are convex, so the hull is exactly the cell
+            let collider = mesh
+                .attribute(Mesh::ATTRIBUTE_POSITION)
+                .and_then(|positions| positions.as_float3())
+                .and_then(|positions| {
+                    Collider::convex_hull(
+                        &positions.iter().map(|p| Vec3::from(*p)).collect::<Vec<_>>(),
+                    )
+                });
+            if collider.is_none() {
+                log::warn!("failed to build a collider for a voronoi cell");
+            }
+
+            VoronoiCell {
+                mesh,
+                collider,
+                material,
+                height,
+            }
+        })
+        .collect()
+}
+
+/// Cuts a convex polygon down to the rectangle from the origin to `extent`.
+fn clip_to_extent(polygon: Vec<Point>, extent: DVec2) -> Vec<Point> {
+    let edges: [(DVec2, f64); 4] = [
+        (DVec2::X, 0.),
+        (DVec2::Y, 0.),
+        (DVec2::NEG_X, -extent.x),
+        (DVec2::NEG_Y, -extent.y),
+    ];
+    let mut points = polygon
+        .into_iter()
+        .map(|point| DVec2::new(point.x.0, point.y.0))
+        .collect::<Vec<_>>();
+    // keeps the side of every edge where `normal.dot(point) >= offset`
+    for (normal, offset) in edges {
+        let distance = |point: DVec2| normal.dot(point) - offset;
+        let mut clipped = Vec::with_capacity(points.len() + 1);
+        for (i, &point) in points.iter().enumerate() {
+            let next = points[(i + 1) % points.len()];
+            let (from, to) = (distance(point), distance(next));
+            if from >= 0. {
+                clipped.push(point);
+            }
+            if from * to < 0. {
+                clipped.push(point.lerp(next, from / (from - to)));
+            }
+        }
+        points = clipped;
+    }
+    points
+        .into_iter()
+        .map(|point| Point::new(point.x, point.y))
+        .collect()
+}
+
+fn find_min_max(points: &[Vec3]) -> (f32, f32, f32, f32) {
+    let mut min_x = f32::MAX;
+    let mut max_x = f32::MIN;
+    let mut min_z = f32::MAX;
+    let mut max_z = f32::MIN;
+
+    for p in points {
+        if p.x < min_x {
+            min_x = p.x;
+        }
+        if p.x > max_x {
+            max_x = p.x;
+        }
+        if p.z < min_z {
+            min_z = p.z;
+        }
+        if p.z > max_z {
+            max_z = p.z;
+        }
+    }
+
+    (min_x, max_x, min_z, max_z)
+}
+
+fn calculate_normal(v0: Vec3, v1: Vec3, v2: Vec3) -> Vec3 {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    edge1.cross(edge2).normalize()
+}
+
+/// Doubled signed area of a polygon on the XZ plane, positive for counter clockwise order.
+fn signed_area(points: &[Vec3]) -> f32 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a.x * b.z - b.x * a.z
+        })
+        .sum()
+}
+
+/// Extrudes a convex polygon upwards into a closed prism.
+///
+/// Every face has its own vertices, so normals are flat and face outward.
+pub fn extrude_to_mesh(polygon: Vec<Point>, extrusion_depth: f32) -> Mesh {
+    // Given data: Vec<Point>
+    let mut points: Vec<Vec3> = polygon.into_iter().map(|e| {
+        Vec3 {x: e.x.0 as f32, y: 0., z: e.y.0 as f32}
+    }).collect();
+
+    // Ensure you have at least 3 points to form a plane
+    if points.len() < 3 {
+        panic!("Expected at least 3 points to form a plane");
+    }
+
+    // the winding below expects counter clockwise order
+    if signed_area(&points) < 0. {
+        points.reverse();
+    }
+    let num_points = points.len();
+
+    // Create the top face by offsetting the points
+    let top_face: Vec<Vec3> = points.iter().map(|p| *p + Vec3::new(0.0, extrusion_depth, 0.0)).collect();
+
+    let (min_x, max_x, min_z, max_z) = find_min_max(&points);
+    let planar_uv = |p: &Vec3| {
+        Vec2::new(
+            (p.x - min_x) / (max_x - min_x), // Normalize x to [0, 1]
+            (p.z - min_z) / (max_z - min_z), // Normalize z to [0, 1]
+        )
+    };
+
+    let mut vertices: Vec<Vec3> = Vec::new();
+    let mut vertex_normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    // Top face (CCW from above)
+    vertices.extend(top_face.iter());
+    vertex_normals.extend(vec![Vec3::Y; num_points]);
+    uvs.extend(top_face.iter().map(planar_uv));
+    for i in 1..num_points - 1 {
+        indices.extend([0, (i + 1) as u32, i as u32]);
+    }
+
+    // Bottom face (CW from above)
+    let base = vertices.len() as u32;
+    vertices.extend(points.iter());
+    vertex_normals.extend(vec![Vec3::NEG_Y; num_points]);
+    uvs.extend(points.iter().map(planar_uv));
+    for i in 1..num_points - 1 {
+        indices.extend([base, base + i as u32, base + i as u32 + 1]);
+    }
+
+    // Side faces
+    for i in 0..num_points {
+        let next_i = (i + 1) % num_points;
+        let quad = [points[i], top_face[i], points[next_i], top_face[next_i]];
+        let side_normal = calculate_normal(quad[0], quad[1], quad[2]);
+        let width = points[i].distance(points[next_i]);
+
+        let base = vertices.len() as u32;
+        vertices.extend(quad);
+        vertex_normals.extend([side_normal; 4]);
+        uvs.extend([
+            Vec2::new(0., 0.),
+            Vec2::new(0., extrusion_depth),
+            Vec2::new(width, 0.),
+            Vec2::new(width, extrusion_depth),
+        ]);
+        indices.extend([base, base + 1, base + 2, base + 2, base + 1, base + 3]);
+    }
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vertex_normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_indices(Indices::U32(indices));
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{render::mesh::VertexAttributeValues, utils::HashMap};
+
+    use super::*;
+
+    /// Position rounded so the copies of a vertex on different faces compare equal.
+    fn key(position: Vec3) -> [i64; 3] {
+        (position * 1000.).round().as_i64vec3().to_array()
+    }
+
+    fn triangles(mesh: &Mesh) -> Vec<[(Vec3, Vec3); 3]> {
+        let positions = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(|positions| positions.as_float3())
+            .unwrap();
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("no normals");
+        };
+        let indices = mesh.indices().unwrap().iter().collect::<Vec<_>>();
+        indices
+            .chunks(3)
+            .map(|triangle| {
+                [0, 1, 2].map(|corner| {
+                    let index = triangle[corner];
+                    (Vec3::from(positions[index]), Vec3::from(normals[index]))
+                })
+            })
+            .collect()
+    }
+
+    fn hexagon() -> Vec<Point> {
+        (0..6)
+            .map(|i| {
+                let angle = i as f64 * std::f64::consts::TAU / 6.;
+                Point::new(5. + 2. * angle.cos(), 5. + 2. * angle.sin())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn extruded_mesh_is_watertight() {
+        for polygon in [hexagon(), hexagon().into_iter().rev().collect()] {
+            let mesh = extrude_to_mesh(polygon, 1.5);
+            // uses of every edge, and +1 one way and -1 the other
+            let mut edges = HashMap::<([i64; 3], [i64; 3]), (usize, i32)>::new();
+            for triangle in triangles(&mesh) {
+                for corner in 0..3 {
+                    let from = key(triangle[corner].0);
+                    let to = key(triangle[(corner + 1) % 3].0);
+                    let edge = edges.entry((from.min(to), from.max(to))).or_default();
+                    edge.0 += 1;
+                    edge.1 += if from < to { 1 } else { -1 };
+                }
+            }
+            for (uses, direction) in edges.values() {
+                assert_eq!(*uses, 2, "edge not shared by two triangles");
+                assert_eq!(*direction, 0, "neighbouring triangles wound apart");
+            }
+        }
+    }
+
+    #[test]
+    fn extruded_mesh_faces_outward() {
+        for polygon in [hexagon(), hexagon().into_iter().rev().collect()] {
+            let mesh = extrude_to_mesh(polygon, 1.5);
+            let center = Vec3::new(5., 0.75, 5.);
+            for [(a, normal), (b, _), (c, _)] in triangles(&mesh) {
+                let winding = (b - a).cross(c - a);
+                let outward = (a + b + c) / 3. - center;
+                assert!(winding.dot(outward) > 0., "triangle winds inward");
+                assert!(normal.dot(outward) > 0., "normal points inward");
+            }
+        }
+    }
+
+    #[test]
+    fn cells_stay_in_a_rectangle() {
+        let config = VoronoiConfig {
+            box_size: Vec2::new(30., 10.),
+            ..default()
+        };
+        let cells = generate_cells(&config);
+        assert!(!cells.is_empty());
+        for cell in cells {
+            let positions = cell
+                .mesh
+                .attribute(Mesh::ATTRIBUTE_POSITION)
+                .and_then(|positions| positions.as_float3())
+                .unwrap();
+            for [x, _, z] in positions {
+                assert!((-0.001..=30.001).contains(x) && (-0.001..=10.001).contains(z));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_config_does_not_panic() {
+        for box_size in [
+            Vec2::ZERO,
+            Vec2::new(10., 0.),
+            Vec2::new(-10., 10.),
+            Vec2::NAN,
+        ] {
+            let config = VoronoiConfig {
+                box_size,
+                ..default()
+            };
+            assert!(generate_cells(&config).is_empty());
+        }
+
+        let config = VoronoiConfig {
+            height_variation: -1.,
+            ..default()
+        };
+        let cells = generate_cells(&config);
+        assert!(!cells.is_empty());
+        assert!(cells.iter().all(|cell| cell.height == config.height));
+    }
+}
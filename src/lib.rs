@@ -15,6 +15,11 @@ mod world;
 pub mod editor;
 pub mod core;
 
+pub use level::{
+    IssueKind, LevelCheckPlugins, LevelCheckSettings, LevelIssue, LevelPackagePlugins, LevelReport,
+    Severity,
+};
+
 pub const ASSET_DIR: &str = "asset";
 
 #[cfg(feature = "devtools")]
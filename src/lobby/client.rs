@@ -1,19 +1,31 @@
+use std::collections::{HashMap, HashSet};
 use std::net::UdpSocket;
 use std::time::SystemTime;
 
-use crate::actor::character::{spawn_character_shell, spawn_tied_camera, TiedCamera};
-use crate::actor::UnloadActorsEvent;
+use crate::actor::ability::{
+    Abilities, AbilityActivated, AbilityRejected, AbilityRequest, AbilityTargets,
+};
+use crate::actor::character::{
+    spawn_character_shell, spawn_tied_camera, SelectedClass, TiedCamera,
+};
+use crate::actor::{
+    spawn_projectile, spawn_revealed, HiddenActor, HiddenToMortals, UnloadActorsEvent,
+};
+use crate::component::{DespawnReason, EntityRespawned, Health};
+use crate::core::LoadLevelEvent;
+use crate::level::{CheckpointActivated, CurrentLevel};
 use crate::lobby::{LobbyState, PlayerId};
 use crate::world::{LinkId, Me};
 use bevy::app::{App, Plugin, Update};
 use bevy::ecs::entity::Entity;
-use bevy::ecs::event::EventWriter;
+use bevy::ecs::event::{EventReader, EventWriter};
 use bevy::ecs::query::With;
 use bevy::ecs::schedule::{Condition, OnExit};
 use bevy::ecs::system::{Query, Res, ResMut, Resource};
 use bevy::hierarchy::DespawnRecursiveExt;
 use bevy::math::Vec3;
 use bevy::prelude::{in_state, Commands, IntoSystemConfigs, OnEnter};
+use bevy::time::Time;
 use bevy::transform::components::Transform;
 use bevy_renet::transport::NetcodeClientPlugin;
 use bevy_renet::RenetClientPlugin;
@@ -23,8 +35,13 @@ use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetClient};
 #[derive(Default, Debug, Resource)]
 pub struct OwnId(Option<ClientId>);
 
+/// Hidden actors the host revealed to this client, the client has no others.
+#[derive(Default, Debug, Resource)]
+pub struct RevealedActors(HashMap<LinkId, HiddenActor>);
+
 use super::{
-    ClientResource, Lobby, PlayerData, ServerMessages, TransportDataResource, Username, PROTOCOL_ID,
+    ClientMessages, ClientResource, Lobby, PlayerData, ServerMessages, TransportDataResource,
+    Username, PROTOCOL_ID,
 };
 
 pub struct ClientLobbyPlugins;
@@ -35,14 +52,23 @@ impl Plugin for ClientLobbyPlugins {
             .add_systems(OnEnter(LobbyState::Client), (setup, new_renet_client))
             .add_systems(
                 Update,
-                client_sync_players
+                (
+                    client_sync_players,
+                    client_send_aim,
+                    client_send_abilities,
+                    client_reveal_hidden,
+                )
                     .run_if(in_state(LobbyState::Client).and_then(bevy_renet::client_connected)),
             )
             .add_systems(OnExit(LobbyState::Client), teardown);
     }
 }
 
-pub fn new_renet_client(settings: Res<ClientResource>, mut commands: Commands) {
+pub fn new_renet_client(
+    settings: Res<ClientResource>,
+    selected_class: Res<SelectedClass>,
+    mut commands: Commands,
+) {
     commands.insert_resource(RenetClient::new(ConnectionConfig::default()));
     let server_addr = settings.address.clone().unwrap().parse().unwrap();
     let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
@@ -51,11 +77,11 @@ pub fn new_renet_client(settings: Res<ClientResource>, mut commands: Commands) {
         .unwrap();
     let client_id = current_time.as_millis() as u64;
 
-    let username_netcode =
-        match Username(settings.username.clone().unwrap().clone()).to_netcode_data() {
-            Ok(bytes) => Some(bytes),
-            Err(_) => None,
-        };
+    let username = Username(settings.username.clone().unwrap().clone());
+    let username_netcode = match username.to_netcode_data(**selected_class) {
+        Ok(bytes) => Some(bytes),
+        Err(_) => None,
+    };
 
     let authentication = ClientAuthentication::Unsecure {
         client_id,
@@ -80,6 +106,62 @@ pub fn new_renet_client(settings: Res<ClientResource>, mut commands: Commands) {
 //    }
 //}
 
+/// Sends the aim of this peer, a lost message is replaced by the next one.
+fn client_send_aim(lobby: Res<Lobby>, mut client: ResMut<RenetClient>) {
+    let message = bincode::serialize(&ClientMessages::Aim {
+        point: lobby.me.aim,
+    })
+    .unwrap();
+    client.send_message(DefaultChannel::Unreliable, message);
+}
+
+/// Sends the ability requests of this peer, the host answers with the activation or the refusal.
+fn client_send_abilities(
+    mut request_events: EventReader<AbilityRequest>,
+    me_query: Query<(), With<Me>>,
+    targets: AbilityTargets,
+    mut client: ResMut<RenetClient>,
+    time: Res<Time>,
+) {
+    for request in request_events.read() {
+        if !me_query.contains(request.caster) {
+            continue;
+        }
+        let Some(target) = targets.to_network(&request.target) else {
+            continue;
+        };
+        let message = bincode::serialize(&ClientMessages::ActivateAbility {
+            slot: request.slot,
+            target,
+            seq: request.seq,
+            time: time.elapsed_seconds_f64(),
+        })
+        .unwrap();
+        client.send_message(DefaultChannel::ReliableUnordered, message);
+    }
+}
+
+/// Spawns the hidden actors the host revealed, despawns the ones it concealed.
+fn client_reveal_hidden(
+    mut commands: Commands,
+    revealed_actors: Res<RevealedActors>,
+    hidden_query: Query<(Entity, &LinkId), With<HiddenToMortals>>,
+) {
+    let mut spawned = HashSet::new();
+    for (entity, link_id) in hidden_query.iter() {
+        if revealed_actors.0.contains_key(link_id) {
+            spawned.insert(link_id);
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for (id, actor) in revealed_actors.0.iter() {
+        if !spawned.contains(id) {
+            commands.spawn_revealed(actor.clone());
+        }
+    }
+}
+
 fn setup(mut commands: Commands) {
     // me
     // let a = Vec3::new(0., 10., 0.);
@@ -88,6 +170,7 @@ fn setup(mut commands: Commands) {
     // commands.spawn_tied_camera(entity);
     commands.init_resource::<Lobby>();
     commands.init_resource::<OwnId>();
+    commands.init_resource::<RevealedActors>();
     commands.init_resource::<TransportDataResource>();
 }
 
@@ -121,6 +204,15 @@ pub fn client_sync_players(
     //mut next_state_map: ResMut<NextState<MapState>>,
     lincked_obj_query: Query<(Entity, &LinkId)>,
     mut unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut load_level_event: EventWriter<LoadLevelEvent>,
+    mut checkpoint_event: EventWriter<CheckpointActivated>,
+    mut respawned_event: EventWriter<EntityRespawned>,
+    current_level: Res<CurrentLevel>,
+    mut abilities_query: Query<&mut Abilities>,
+    ability_targets: AbilityTargets,
+    mut ability_activated_event: EventWriter<AbilityActivated>,
+    mut ability_rejected_event: EventWriter<AbilityRejected>,
+    mut revealed_actors: ResMut<RevealedActors>,
 ) {
     // player existence manager
     while let Some(message) = client.receive_message(DefaultChannel::ReliableOrdered) {
@@ -134,17 +226,25 @@ pub fn client_sync_players(
                     *own_id = OwnId(Some(id));
                 }
             }
-            ServerMessages::ChangeMap { /*map_state*/ } => {
+            ServerMessages::ChangeMap {
+                level_code,
+                content_hash,
+            } => {
                 //next_state_map.set(map_state);
                 unload_actors_event.send(UnloadActorsEvent);
+                if current_level.0 != level_code {
+                    load_level_event
+                        .send(LoadLevelEvent::new(level_code).with_content_hash(content_hash));
+                }
             }
             ServerMessages::PlayerConnected {
                 id: player_id,
                 color,
                 username,
+                class,
             } => {
                 let player_entity = commands
-                    .spawn_character_shell(player_id, color, Vec3::ZERO)
+                    .spawn_character_shell(player_id, color, Vec3::ZERO, class)
                     .id();
                 if let PlayerId::Client(id) = player_id {
                     if Some(id) == own_id.0 {
@@ -158,9 +258,10 @@ pub fn client_sync_players(
                     log::info!("Host {} ({:?}).", username, player_id);
                 }
 
-                lobby
-                    .players
-                    .insert(player_id, PlayerData::new(player_entity, color, username));
+                lobby.players.insert(
+                    player_id,
+                    PlayerData::new(player_entity, color, username, class),
+                );
             }
             ServerMessages::PlayerDisconnected { id } => {
                 let name = "noname";
@@ -177,7 +278,132 @@ pub fn client_sync_players(
                     }
                 }
             }
-            ServerMessages::ProjectileSpawn { id: _, color: _ } => todo!(),
+            ServerMessages::ProjectileSpawn {
+                id,
+                color,
+                projectile,
+                position,
+                velocity,
+            } => {
+                // the host decides what it hits
+                commands.spawn_projectile(id, projectile, position, velocity, color, None);
+            }
+            ServerMessages::CheckpointActivated { id, checkpoint } => {
+                for (entity, link_id) in lincked_obj_query.iter() {
+                    if link_id == &checkpoint {
+                        checkpoint_event.send(CheckpointActivated {
+                            player: id,
+                            checkpoint: entity,
+                        });
+                    }
+                }
+            }
+            ServerMessages::PlayerRespawn {
+                id,
+                from,
+                position,
+                rotation,
+            } => {
+                if let Some(player_data) = lobby.players.get(&id) {
+                    let entity = player_data.entity();
+                    let to = Transform::from_translation(position).with_rotation(rotation);
+                    commands.entity(entity).insert(to);
+                    respawned_event.send(EntityRespawned {
+                        entity,
+                        from: Transform::from_translation(from),
+                        to,
+                        reason: DespawnReason::Forced,
+                    });
+                }
+            }
+            ServerMessages::ActorRespawn {
+                id,
+                from,
+                position,
+                rotation,
+            } => {
+                for (entity, link_id) in lincked_obj_query.iter() {
+                    if link_id == &id {
+                        let to = Transform::from_translation(position).with_rotation(rotation);
+                        commands.entity(entity).try_insert(to);
+                        respawned_event.send(EntityRespawned {
+                            entity,
+                            from: Transform::from_translation(from),
+                            to,
+                            reason: DespawnReason::Forced,
+                        });
+                    }
+                }
+            }
+            ServerMessages::Reveal { actors } => revealed_actors
+                .0
+                .extend(actors.into_iter().map(|actor| (actor.id.clone(), actor))),
+            ServerMessages::Conceal { ids } => {
+                for id in ids.iter() {
+                    revealed_actors.0.remove(id);
+                }
+            }
+            ServerMessages::PlayerHealth { id, current, max } => {
+                if let Some(player_data) = lobby.players.get(&id) {
+                    commands
+                        .entity(player_data.entity())
+                        .try_insert(Health { current, max });
+                }
+            }
+            ServerMessages::ActorHealth { id, current, max } => {
+                for (entity, link_id) in lincked_obj_query.iter() {
+                    if link_id == &id {
+                        commands.entity(entity).try_insert(Health { current, max });
+                    }
+                }
+            }
+            ServerMessages::AbilityActivated {
+                id,
+                slot,
+                target,
+                seq,
+            } => {
+                let Some(player_data) = lobby.players.get(&id) else {
+                    continue;
+                };
+                let caster = player_data.entity();
+                let Ok(mut abilities) = abilities_query.get_mut(caster) else {
+                    continue;
+                };
+                // this peer already activated it ahead of the host
+                if own_id.0.is_some_and(|own| id == PlayerId::Client(own)) {
+                    abilities.confirm(seq);
+                    continue;
+                }
+                let (Some(state), Some(target)) =
+                    (abilities.get(slot), ability_targets.from_network(&target))
+                else {
+                    continue;
+                };
+                ability_activated_event.send(AbilityActivated {
+                    caster,
+                    slot,
+                    ability: state.id.clone(),
+                    target,
+                    seq,
+                    time: None,
+                    predicted: false,
+                });
+            }
+            ServerMessages::AbilityRejected { slot, seq, error } => {
+                let Some(player_data) = own_id
+                    .0
+                    .and_then(|own| lobby.players.get(&PlayerId::Client(own)))
+                else {
+                    continue;
+                };
+                ability_rejected_event.send(AbilityRejected {
+                    caster: player_data.entity(),
+                    slot,
+                    seq,
+                    error,
+                });
+            }
         }
     }
 
@@ -1,34 +1,77 @@
+use std::collections::{HashMap, HashSet};
 use std::net::UdpSocket;
 use std::time::SystemTime;
 
-use crate::actor::character::{spawn_character, spawn_tied_camera, TiedCamera};
-use crate::actor::UnloadActorsEvent;
-use crate::component::{DespawnReason, Respawn};
-use crate::core::{KnownLevel};
+use crate::actor::ability::{
+    AbilityActivated, AbilityRejected, AbilityRequest, AbilityTarget, AbilityTargets, NetworkTarget,
+};
+use crate::actor::character::{spawn_character, spawn_tied_camera, SelectedClass, TiedCamera};
+use crate::actor::{HiddenActor, HiddenActors, ProjectileDefinition, TrueSight, UnloadActorsEvent};
+use crate::component::{DespawnReason, EntityRespawned, Health, Respawn, SpawnPoints, SpawnTag};
+use crate::core::{CoreGameState, KnownLevel};
+use crate::level::{level_content_hash, CheckpointActivated, CurrentLevel};
 use crate::lobby::{LobbyState, PlayerData, PlayerId, ServerMessages, Username};
 use crate::world::{LinkId, Me, SpawnProperty};
 use bevy::app::{App, Plugin, Update};
 use bevy::ecs::entity::Entity;
 use bevy::ecs::event::{Event, EventReader, EventWriter};
-use bevy::ecs::query::With;
+use bevy::ecs::query::{Changed, Has, With};
 use bevy::ecs::schedule::{Condition, NextState, OnExit};
-use bevy::ecs::system::{Query, Res, ResMut};
+use bevy::ecs::system::{Query, Res, ResMut, Resource};
 use bevy::hierarchy::DespawnRecursiveExt;
 
-use bevy::prelude::{in_state, Color, Commands, IntoSystemConfigs, OnEnter};
+use bevy::prelude::{in_state, Color, Commands, IntoSystemConfigs, OnEnter, Vec3};
+use bevy::time::Time;
+use bevy::transform::components::GlobalTransform;
 use bevy_renet::transport::NetcodeServerPlugin;
 use bevy_renet::RenetServerPlugin;
 use renet::transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig};
-use renet::{ConnectionConfig, DefaultChannel, RenetServer, ServerEvent};
+use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetServer, ServerEvent};
 
 use super::{
-    ChangeMapLobbyEvent, Character, HostResource, LevelCode, Lobby, MapLoaderState, TransportDataResource, PROTOCOL_ID,
+    ChangeMapLobbyEvent, Character, ClientMessages, HostResource, LevelCode, Lobby, MapLoaderState, TransportDataResource, PROTOCOL_ID,
 };
 
 #[derive(Debug, Event)]
 pub struct DespawnActorEvent(pub LinkId);
+/// Sent when the host shoots a projectile, the clients spawn their own copy.
 #[derive(Debug, Event)]
-pub struct SpawnProjectileEvent(pub LinkId, pub Color);
+pub struct SpawnProjectileEvent {
+    pub id: LinkId,
+    pub color: Color,
+    pub projectile: ProjectileDefinition,
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+/// Hidden actors the host told every client about, see
+/// [`HiddenToMortals`](crate::actor::HiddenToMortals).
+#[derive(Debug, Default, Resource)]
+pub struct HiddenReplication {
+    /// Every hidden actor of the level.
+    hidden: HashSet<LinkId>,
+    revealed: HashMap<ClientId, HashSet<LinkId>>,
+}
+
+impl HiddenReplication {
+    /// The client may hear about the actor.
+    pub fn sees(&self, client_id: ClientId, id: &LinkId) -> bool {
+        !self.hidden.contains(id)
+            || self
+                .revealed
+                .get(&client_id)
+                .is_some_and(|revealed| revealed.contains(id))
+    }
+
+    /// Sends a message about an actor to the clients that see it.
+    pub fn send_about(&self, server: &mut RenetServer, id: &LinkId, message: Vec<u8>) {
+        for client_id in server.clients_id() {
+            if self.sees(client_id, id) {
+                server.send_message(client_id, DefaultChannel::ReliableOrdered, message.clone());
+            }
+        }
+    }
+}
 
 pub struct HostLobbyPlugins;
 
@@ -36,11 +79,21 @@ impl Plugin for HostLobbyPlugins {
     fn build(&self, app: &mut App) {
         app.add_event::<DespawnActorEvent>()
             .add_event::<SpawnProjectileEvent>()
+            .init_resource::<HiddenReplication>()
             .add_plugins((RenetServerPlugin, NetcodeServerPlugin))
             .add_systems(OnEnter(LobbyState::Host), setup)
             .add_systems(
                 Update,
-                (send_change_map, spawn_projectile, despawn_actor)
+                (
+                    send_change_map,
+                    spawn_projectile,
+                    despawn_actor,
+                    send_checkpoint,
+                    send_respawn,
+                    send_revealed,
+                    send_health,
+                    send_abilities,
+                )
                     .run_if(in_state(LobbyState::Host)),
             )
             .add_systems(
@@ -50,8 +103,17 @@ impl Plugin for HostLobbyPlugins {
             .add_systems(OnExit(LobbyState::Host), teardown)
             .add_systems(
                 Update,
-                load_processing
-                    .run_if(in_state(LobbyState::Host).and_then(in_state(MapLoaderState::No))),
+                (
+                    load_processing
+                        .run_if(in_state(LobbyState::Host).and_then(in_state(MapLoaderState::No))),
+                    refresh_respawn_points
+                        .run_if(in_state(LobbyState::Host).and_then(in_state(MapLoaderState::Yes))),
+                ),
+            )
+            // a downloaded level is left long after the map change
+            .add_systems(
+                OnExit(CoreGameState::InGame),
+                reset_map_loader.run_if(in_state(LobbyState::Host)),
             );
     }
 }
@@ -60,10 +122,13 @@ pub fn spawn_projectile(
     mut event_reader: EventReader<SpawnProjectileEvent>,
     mut server: ResMut<RenetServer>,
 ) {
-    for SpawnProjectileEvent(link_id, color) in event_reader.read() {
+    for event in event_reader.read() {
         let message = bincode::serialize(&ServerMessages::ProjectileSpawn {
-            id: link_id.clone(),
-            color: *color,
+            id: event.id.clone(),
+            color: event.color,
+            projectile: event.projectile.clone(),
+            position: event.position,
+            velocity: event.velocity,
         })
         .unwrap();
         server.broadcast_message(DefaultChannel::ReliableOrdered, message);
@@ -72,6 +137,7 @@ pub fn spawn_projectile(
 
 pub fn despawn_actor(
     mut event_reader: EventReader<DespawnActorEvent>,
+    replication: Res<HiddenReplication>,
     mut server: ResMut<RenetServer>,
 ) {
     for DespawnActorEvent(link_id) in event_reader.read() {
@@ -79,7 +145,193 @@ pub fn despawn_actor(
             id: link_id.clone(),
         })
         .unwrap();
-        server.broadcast_message(DefaultChannel::ReliableOrdered, message);
+        replication.send_about(&mut server, link_id, message);
+    }
+}
+
+pub fn send_checkpoint(
+    mut event_reader: EventReader<CheckpointActivated>,
+    link_query: Query<&LinkId>,
+    replication: Res<HiddenReplication>,
+    mut server: ResMut<RenetServer>,
+) {
+    for CheckpointActivated { player, checkpoint } in event_reader.read() {
+        let Ok(link_id) = link_query.get(*checkpoint) else {
+            continue;
+        };
+        let message = bincode::serialize(&ServerMessages::CheckpointActivated {
+            id: *player,
+            checkpoint: link_id.clone(),
+        })
+        .unwrap();
+        replication.send_about(&mut server, link_id, message);
+    }
+}
+
+pub fn send_respawn(
+    mut event_reader: EventReader<EntityRespawned>,
+    character_query: Query<&Character>,
+    link_query: Query<&LinkId>,
+    replication: Res<HiddenReplication>,
+    mut server: ResMut<RenetServer>,
+) {
+    for EntityRespawned {
+        entity, from, to, ..
+    } in event_reader.read()
+    {
+        let message = if let Ok(character) = character_query.get(*entity) {
+            ServerMessages::PlayerRespawn {
+                id: character.id,
+                from: from.translation,
+                position: to.translation,
+                rotation: to.rotation,
+            }
+        } else if let Ok(link_id) = link_query.get(*entity) {
+            ServerMessages::ActorRespawn {
+                id: link_id.clone(),
+                from: from.translation,
+                position: to.translation,
+                rotation: to.rotation,
+            }
+        } else {
+            continue;
+        };
+        let message = bincode::serialize(&message).unwrap();
+        // hidden actors are only told about to the clients that see them
+        match link_query.get(*entity) {
+            Ok(link_id) => replication.send_about(&mut server, link_id, message),
+            Err(_) => server.broadcast_message(DefaultChannel::ReliableOrdered, message),
+        }
+    }
+}
+
+pub fn send_health(
+    health_query: Query<(Entity, &Health), Changed<Health>>,
+    character_query: Query<&Character>,
+    link_query: Query<&LinkId>,
+    replication: Res<HiddenReplication>,
+    mut server: ResMut<RenetServer>,
+) {
+    for (entity, health) in health_query.iter() {
+        let message = if let Ok(character) = character_query.get(entity) {
+            ServerMessages::PlayerHealth {
+                id: character.id,
+                current: health.current,
+                max: health.max,
+            }
+        } else if let Ok(link_id) = link_query.get(entity) {
+            ServerMessages::ActorHealth {
+                id: link_id.clone(),
+                current: health.current,
+                max: health.max,
+            }
+        } else {
+            continue;
+        };
+        let message = bincode::serialize(&message).unwrap();
+        match link_query.get(entity) {
+            Ok(link_id) => replication.send_about(&mut server, link_id, message),
+            Err(_) => server.broadcast_message(DefaultChannel::ReliableOrdered, message),
+        }
+    }
+}
+
+/// Tells every client which hidden actors its character sees, the others are never mentioned.
+pub fn send_revealed(
+    hidden_actors: HiddenActors,
+    sight_query: Query<(&Character, Has<TrueSight>)>,
+    mut replication: ResMut<HiddenReplication>,
+    mut server: ResMut<RenetServer>,
+) {
+    let replication = &mut *replication;
+    replication.hidden = hidden_actors.ids().cloned().collect();
+    let clients = server.clients_id();
+    replication
+        .revealed
+        .retain(|client_id, _| clients.contains(client_id));
+
+    for (character, true_sight) in sight_query.iter() {
+        let PlayerId::Client(client_id) = character.id else {
+            continue;
+        };
+        let seen = if true_sight {
+            replication.hidden.clone()
+        } else {
+            HashSet::new()
+        };
+        let known = replication.revealed.entry(client_id).or_default();
+        let revealed: Vec<HiddenActor> = seen
+            .difference(known)
+            .filter_map(|id| hidden_actors.describe(id))
+            .collect();
+        let concealed: Vec<LinkId> = known.difference(&seen).cloned().collect();
+        *known = seen;
+
+        if !revealed.is_empty() {
+            let message = bincode::serialize(&ServerMessages::Reveal { actors: revealed }).unwrap();
+            server.send_message(client_id, DefaultChannel::ReliableOrdered, message);
+        }
+        if !concealed.is_empty() {
+            let message = bincode::serialize(&ServerMessages::Conceal { ids: concealed }).unwrap();
+            server.send_message(client_id, DefaultChannel::ReliableOrdered, message);
+        }
+    }
+}
+
+pub fn send_abilities(
+    mut activated_events: EventReader<AbilityActivated>,
+    mut rejected_events: EventReader<AbilityRejected>,
+    character_query: Query<&Character>,
+    position_query: Query<&GlobalTransform>,
+    targets: AbilityTargets,
+    replication: Res<HiddenReplication>,
+    mut server: ResMut<RenetServer>,
+) {
+    for activated in activated_events.read() {
+        let Ok(character) = character_query.get(activated.caster) else {
+            continue;
+        };
+        let Some(target) = targets.to_network(&activated.target) else {
+            continue;
+        };
+        for client_id in server.clients_id() {
+            // a hidden target is only a point to the clients that do not see it
+            let target = match (&target, &activated.target) {
+                (NetworkTarget::Link(id), AbilityTarget::Entity(entity))
+                    if !replication.sees(client_id, id) =>
+                {
+                    let Ok(transform) = position_query.get(*entity) else {
+                        continue;
+                    };
+                    NetworkTarget::Point(transform.translation())
+                }
+                (target, _) => target.clone(),
+            };
+            let message = bincode::serialize(&ServerMessages::AbilityActivated {
+                id: character.id,
+                slot: activated.slot,
+                target,
+                seq: activated.seq,
+            })
+            .unwrap();
+            server.send_message(client_id, DefaultChannel::ReliableOrdered, message);
+        }
+    }
+
+    for rejected in rejected_events.read() {
+        let Ok(Character {
+            id: PlayerId::Client(client_id),
+        }) = character_query.get(rejected.caster)
+        else {
+            continue;
+        };
+        let message = bincode::serialize(&ServerMessages::AbilityRejected {
+            slot: rejected.slot,
+            seq: rejected.seq,
+            error: rejected.error,
+        })
+        .unwrap();
+        server.send_message(*client_id, DefaultChannel::ReliableOrdered, message);
     }
 }
 
@@ -121,24 +373,32 @@ fn setup(
     change_map_event.send(ChangeMapLobbyEvent(LevelCode::Known(KnownLevel::Hub)));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn load_processing(
     mut commands: Commands,
     spawn_point: Res<SpawnProperty>,
+    mut spawn_points: SpawnPoints,
     mut lobby_res: ResMut<Lobby>,
     host_resource: Res<HostResource>,
     query: Query<(), With<Me>>,
     mut character_respawn_query: Query<&mut Respawn, With<Character>>,
     mut next_state_map: ResMut<NextState<MapLoaderState>>,
+    selected_class: Res<SelectedClass>,
 ) {
     log::info!("LoadProcessing: {:#?}", spawn_point);
     if !spawn_point.is_empty() {
         if query.get_single().is_err() {
             // spawn host character
+            let Some(spawn) =
+                spawn_points.select(&spawn_point.clone().with_tag(SpawnTag::Start), &[])
+            else {
+                return;
+            };
             lobby_res.players_seq += 1;
             let color = generate_player_color(lobby_res.players_seq as u32);
 
             let player_entity = commands
-                .spawn_character(PlayerId::HostOrSingle, color, spawn_point.random_point())
+                .spawn_character(PlayerId::HostOrSingle, color, spawn, **selected_class)
                 .insert(Me)
                 .id();
             commands.spawn_tied_camera(player_entity);
@@ -147,11 +407,12 @@ pub fn load_processing(
                 player_entity,
                 color,
                 host_resource.username.clone().unwrap(),
+                **selected_class,
             );
         }
 
         for mut respawn in character_respawn_query.iter_mut() {
-            respawn.replase_spawn_point(spawn_point.clone());
+            respawn.replase_spawn_point(spawn_point.clone().with_tag(SpawnTag::Respawn));
             respawn.insert_reason(DespawnReason::Forced);
         }
 
@@ -159,16 +420,37 @@ pub fn load_processing(
     }
 }
 
+/// Keeps the respawn points of the characters up with the spawn points the level registers.
+fn refresh_respawn_points(
+    spawn_point: Res<SpawnProperty>,
+    mut character_respawn_query: Query<&mut Respawn, With<Character>>,
+) {
+    if !spawn_point.is_changed() || spawn_point.is_empty() {
+        return;
+    }
+    for mut respawn in character_respawn_query.iter_mut() {
+        respawn.replase_spawn_point(spawn_point.clone().with_tag(SpawnTag::Respawn));
+    }
+}
+
+fn reset_map_loader(mut next_state_map: ResMut<NextState<MapLoaderState>>) {
+    next_state_map.set(MapLoaderState::No);
+}
+
 pub fn send_change_map(
     mut change_map_event: EventReader<ChangeMapLobbyEvent>,
     mut server: ResMut<RenetServer>,
-    // mut next_state_map: ResMut<NextState<MapState>>,
+    mut next_state_map: ResMut<NextState<MapLoaderState>>,
     mut unload_actors_event: EventWriter<UnloadActorsEvent>,
 ) {
-    for ChangeMapLobbyEvent(_state) in change_map_event.read() {
-        // next_state_map.set(*state);
-        let message =
-            bincode::serialize(&ServerMessages::ChangeMap { /*map_state: *state*/ }).unwrap();
+    for ChangeMapLobbyEvent(level_code) in change_map_event.read() {
+        // the characters are placed again once the new level has spawn points
+        next_state_map.set(MapLoaderState::No);
+        let message = bincode::serialize(&ServerMessages::ChangeMap {
+            level_code: level_code.clone(),
+            content_hash: level_content_hash(level_code),
+        })
+        .unwrap();
         server.broadcast_message(DefaultChannel::ReliableOrdered, message);
 
         unload_actors_event.send(UnloadActorsEvent);
@@ -207,6 +489,11 @@ pub fn server_update_system(
     mut server: ResMut<RenetServer>,
     transport: Res<NetcodeServerTransport>,
     spawn_point: Res<SpawnProperty>,
+    mut spawn_points: SpawnPoints,
+    mut request_events: EventWriter<AbilityRequest>,
+    targets: AbilityTargets,
+    time: Res<Time>,
+    current_level: Res<CurrentLevel>,
     //map_state: ResMut<State<MapState>>,
 
     //mut input_query: Query<&mut PlayerInputs>,
@@ -224,16 +511,31 @@ pub fn server_update_system(
                 .unwrap();
                 server.send_message(*client_id, DefaultChannel::ReliableOrdered, message);
 
+                // late joiners load the level of the host, a dungeon from its seed
+                let message = bincode::serialize(&ServerMessages::ChangeMap {
+                    level_code: current_level.0.clone(),
+                    content_hash: level_content_hash(&current_level.0),
+                })
+                .unwrap();
+                server.send_message(*client_id, DefaultChannel::ReliableOrdered, message);
+
                 lobby.players_seq += 1;
                 let color = generate_player_color(lobby.players_seq as u32);
+                let spawn = spawn_points
+                    .select(&spawn_point.clone().with_tag(SpawnTag::Start), &[])
+                    .unwrap_or_default();
+
+                let data = transport.user_data(*client_id).unwrap();
+                let username = match Username::from_user_data(&data) {
+                    Ok(name) => name,
+                    Err(_) => "@corapted@".to_string(),
+                };
+                // let username = "noname".to_string();
+                let class = Username::class_from_user_data(&data).unwrap_or_default();
 
                 // Spawn player cube
                 let player_entity = commands
-                    .spawn_character(
-                        PlayerId::Client(*client_id),
-                        color,
-                        spawn_point.random_point(),
-                    )
+                    .spawn_character(PlayerId::Client(*client_id), color, spawn, class)
                     .id();
 
                 // We could send an InitState with all the players id and positions for the multiplayer
@@ -243,27 +545,22 @@ pub fn server_update_system(
                         id: *player_id,
                         color: player_data.color,
                         username: player_data.username.clone(),
+                        class: player_data.class,
                     })
                     .unwrap();
                     server.send_message(*client_id, DefaultChannel::ReliableOrdered, message);
                 }
 
-                let data = transport.user_data(*client_id).unwrap();
-                let username = match Username::from_user_data(&data) {
-                    Ok(name) => name,
-                    Err(_) => "@corapted@".to_string(),
-                };
-                // let username = "noname".to_string();
-
                 lobby.players.insert(
                     PlayerId::Client(*client_id),
-                    PlayerData::new(player_entity, color, username.clone()),
+                    PlayerData::new(player_entity, color, username.clone(), class),
                 );
 
                 let message = bincode::serialize(&ServerMessages::PlayerConnected {
                     id: PlayerId::Client(*client_id),
                     color,
                     username,
+                    class,
                 })
                 .unwrap();
                 server.broadcast_message(DefaultChannel::ReliableOrdered, message);
@@ -302,6 +599,53 @@ pub fn server_update_system(
                 log::error!("Player not found");
             }
         }
+
+        while let Some(message) =
+            server.receive_message(client_id, DefaultChannel::ReliableUnordered)
+        {
+            let Some(player_data) = lobby.players.get_mut(&PlayerId::Client(client_id)) else {
+                log::error!("Player not found");
+                continue;
+            };
+            match bincode::deserialize(&message) {
+                Ok(ClientMessages::ActivateAbility {
+                    slot,
+                    target,
+                    seq,
+                    time: client_time,
+                }) => {
+                    let rtt = server.network_info(client_id).map_or(0., |info| info.rtt);
+                    player_data
+                        .clock
+                        .sample(client_time, time.elapsed_seconds_f64(), rtt);
+                    let Some(target) = targets.from_network(&target) else {
+                        log::warn!("Unknown ability target from {}: {:?}", client_id, target);
+                        continue;
+                    };
+                    request_events.send(AbilityRequest {
+                        caster: player_data.entity(),
+                        slot,
+                        target,
+                        seq,
+                        time: player_data.clock.host_time(client_time),
+                    });
+                }
+                Ok(message) => log::warn!("Unexpected message from {}: {:?}", client_id, message),
+                Err(err) => log::error!("Malformed message from {}: {}", client_id, err),
+            }
+        }
+
+        while let Some(message) = server.receive_message(client_id, DefaultChannel::Unreliable) {
+            let Some(player_data) = lobby.players.get_mut(&PlayerId::Client(client_id)) else {
+                log::error!("Player not found");
+                continue;
+            };
+            match bincode::deserialize(&message) {
+                Ok(ClientMessages::Aim { point }) => player_data.aim = point,
+                Ok(message) => log::warn!("Unexpected message from {}: {:?}", client_id, message),
+                Err(err) => log::error!("Malformed message from {}: {}", client_id, err),
+            }
+        }
     }
 }
 
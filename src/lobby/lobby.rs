@@ -1,3 +1,6 @@
+use crate::actor::ability::{AbilityError, AbilitySlot, NetworkTarget};
+use crate::actor::character::CharacterClass;
+use crate::actor::{HiddenActor, ProjectileDefinition};
 use crate::core::{CoreAction, KnownLevel};
 use crate::world::LinkId;
 use bevy::app::{App, Plugin};
@@ -61,9 +64,11 @@ pub enum ServerMessages {
     ///
     /// # Fields
     ///
-    /// * `map_state` - The new state of the map.
+    /// * `level_code` - The level to load.
+    /// * `content_hash` - Hash of the level content, a peer with other content refuses the level.
     ChangeMap {
-        //map_state: MapState,
+        level_code: LevelCode,
+        content_hash: Option<String>,
     },
     /// Indicates that a player has connected to the server.
     ///
@@ -72,10 +77,12 @@ pub enum ServerMessages {
     /// * `id` - Unique identifier for the player.
     /// * `color` - The color assigned to the player.
     /// * `username` - The player's chosen username.
+    /// * `class` - The class the player plays.
     PlayerConnected {
         id: PlayerId,
         color: Color,
         username: String,
+        class: CharacterClass,
     },
     /// Indicates that a player has disconnected from the server.
     ///
@@ -85,13 +92,122 @@ pub enum ServerMessages {
     PlayerDisconnected {
         id: PlayerId,
     },
+    /// Indicates that the host shot a projectile, the clients simulate their own copy.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - Link of the projectile, the host despawns it by it.
+    /// * `color` - The color of the shooter.
+    /// * `projectile` - How it flies and looks.
+    /// * `position` - Where it was shot from.
+    /// * `velocity` - Its initial velocity.
     ProjectileSpawn {
         id: LinkId,
         color: Color,
+        projectile: ProjectileDefinition,
+        position: Vec3,
+        velocity: Vec3,
     },
     ActorDespawn {
         id: LinkId,
     },
+    /// Indicates that a player has activated a checkpoint of the current level.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - Unique identifier for the player.
+    /// * `checkpoint` - The activated checkpoint.
+    CheckpointActivated {
+        id: PlayerId,
+        checkpoint: LinkId,
+    },
+    /// Indicates that the character of a player has respawned.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - Unique identifier for the player.
+    /// * `from` - Where the character was.
+    /// * `position` - Where the character respawned.
+    /// * `rotation` - Facing of the respawned character.
+    PlayerRespawn {
+        id: PlayerId,
+        from: Vec3,
+        position: Vec3,
+        rotation: Quat,
+    },
+    /// Indicates that an actor has respawned, see [`ServerMessages::PlayerRespawn`].
+    ActorRespawn {
+        id: LinkId,
+        from: Vec3,
+        position: Vec3,
+        rotation: Quat,
+    },
+    /// Indicates that the health of a player changed.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - Unique identifier for the player.
+    /// * `current` - Health left, the character is dead at zero.
+    /// * `max` - Health the character respawns with.
+    PlayerHealth {
+        id: PlayerId,
+        current: f32,
+        max: f32,
+    },
+    /// Indicates that the health of an actor changed, see [`ServerMessages::PlayerHealth`].
+    ActorHealth {
+        id: LinkId,
+        current: f32,
+        max: f32,
+    },
+    /// Indicates that the cast of an ability ended.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - Unique identifier for the player who cast it.
+    /// * `slot` - The slot of the ability.
+    /// * `target` - What the ability is aimed at.
+    /// * `seq` - Sequence number of the activation on the client who asked for it.
+    AbilityActivated {
+        id: PlayerId,
+        slot: AbilitySlot,
+        target: NetworkTarget,
+        seq: u32,
+    },
+    /// Sent to the client whose activation was refused.
+    AbilityRejected {
+        slot: AbilitySlot,
+        seq: u32,
+        error: AbilityError,
+    },
+    /// Sent to a client whose character starts to see hidden actors,
+    /// see [`HiddenToMortals`](crate::actor::HiddenToMortals).
+    Reveal {
+        actors: Vec<HiddenActor>,
+    },
+    /// Sent to a client whose character no longer sees hidden actors.
+    Conceal {
+        ids: Vec<LinkId>,
+    },
+}
+
+/// Messages a client sends to the host.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ClientMessages {
+    /// Point the player aims at, see [`CursorWorld`](crate::world::CursorWorld).
+    ///
+    /// # Fields
+    ///
+    /// * `point` - The aimed point, `None` when the cursor is out of the game view.
+    Aim { point: Option<Vec3> },
+    /// Asks to activate an ability, see [`AbilityRequest`](crate::actor::ability::AbilityRequest).
+    ActivateAbility {
+        slot: AbilitySlot,
+        target: NetworkTarget,
+        seq: u32,
+        /// Seconds since the start of the client when it was asked, see [`PeerClock`].
+        time: f64,
+    },
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
@@ -110,18 +226,23 @@ impl Default for Username {
     }
 }
 
+/// Byte of the netcode user data with the class of the client, the username ends before it.
+const CLASS_BYTE: usize = NETCODE_USER_DATA_BYTES - 1;
+
 impl Username {
     pub fn to_netcode_data(
         &self,
+        class: CharacterClass,
     ) -> Result<[u8; NETCODE_USER_DATA_BYTES], Box<dyn std::error::Error>> {
         let mut data = [0u8; NETCODE_USER_DATA_BYTES];
-        if self.0.len() > NETCODE_USER_DATA_BYTES - 8 {
+        if self.0.len() > CLASS_BYTE - 8 {
             let err = Err(From::from("Your username to long"));
             log::error!("{:?}", err);
             return err;
         }
         data[0..8].copy_from_slice(&(self.0.len() as u64).to_le_bytes());
         data[8..self.0.len() + 8].copy_from_slice(self.0.as_bytes());
+        data[CLASS_BYTE] = class.to_byte();
 
         Ok(data)
     }
@@ -132,12 +253,19 @@ impl Username {
         let mut buffer = [0u8; 8];
         buffer.copy_from_slice(&user_data[0..8]);
         let mut len = u64::from_le_bytes(buffer) as usize;
-        len = len.min(NETCODE_USER_DATA_BYTES - 8);
+        len = len.min(CLASS_BYTE - 8);
         let data = user_data[8..len + 8].to_vec();
         let username = String::from_utf8(data)?;
 
         Ok(username)
     }
+
+    /// Class the client chose, see [`Username::to_netcode_data`].
+    pub fn class_from_user_data(
+        user_data: &[u8; NETCODE_USER_DATA_BYTES],
+    ) -> Option<CharacterClass> {
+        CharacterClass::from_byte(user_data[CLASS_BYTE])
+    }
 }
 
 #[derive(Debug, Default, Resource)]
@@ -160,6 +288,26 @@ pub struct Lobby {
     pub players_seq: usize,
 }
 
+impl Lobby {
+    /// Inputs of a character, `me` tells if it is the character of this peer.
+    pub fn inputs_of(&self, id: &PlayerId, me: bool) -> Option<&PlayerActions<CoreAction>> {
+        if me {
+            Some(&self.me.inputs)
+        } else {
+            self.players.get(id).map(|player| &player.inputs)
+        }
+    }
+
+    /// Aimed point of a character, `me` tells if it is the character of this peer.
+    pub fn aim_of(&self, id: &PlayerId, me: bool) -> Option<Vec3> {
+        if me {
+            self.me.aim
+        } else {
+            self.players.get(id).and_then(|player| player.aim)
+        }
+    }
+}
+
 impl InputsContainer<CoreAction> for Lobby {
     fn iter_inputs<'a>(&'a self) -> Box<dyn Iterator<Item = &'a PlayerActions<CoreAction>> + 'a> {
         todo!()
@@ -197,16 +345,28 @@ pub struct PlayerData {
     entity: Option<Entity>,
     pub color: Color,
     pub username: String,
+    pub class: CharacterClass,
     pub inputs: PlayerActions<CoreAction>,
+    /// Point in the world the player aims at.
+    pub aim: Option<Vec3>,
+    pub clock: PeerClock,
 }
 
 impl PlayerData {
-    pub fn new(entity: Entity, color: Color, username: String) -> PlayerData {
+    pub fn new(
+        entity: Entity,
+        color: Color,
+        username: String,
+        class: CharacterClass,
+    ) -> PlayerData {
         PlayerData {
             entity: Some(entity),
             color,
             username,
+            class,
             inputs: PlayerActions::<CoreAction>::default(),
+            aim: None,
+            clock: PeerClock::default(),
         }
     }
 
@@ -224,11 +384,45 @@ impl Default for PlayerData {
             entity: None,
             color: Color::RED,
             username: "noname".into(),
+            class: CharacterClass::default(),
             inputs: PlayerActions::<CoreAction>::default(),
+            aim: None,
+            clock: PeerClock::default(),
         }
     }
 }
 
+/// Clock of a client seen from the host, the host rewinds the hits of the client with it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PeerClock {
+    /// Smallest gap seen between the host clock and the timestamps of the client.
+    offset: Option<f64>,
+    /// Seconds a message of the client takes, half of the round trip sampled with the offset.
+    trip: f64,
+}
+
+impl PeerClock {
+    /// Longest rewind, slower clients hit what they saw that long ago.
+    pub const MAX_LAG: f32 = 0.3;
+
+    /// Updates the estimate with a timestamp of the client the host got at `now`.
+    ///
+    /// The smallest gap is the difference of the clocks plus the fastest trip,
+    /// taken as half of the round trip `rtt` of the same sample.
+    pub fn sample(&mut self, client_time: f64, now: f64, rtt: f64) {
+        let gap = now - client_time;
+        if self.offset.map_or(true, |offset| gap < offset) {
+            self.offset = Some(gap);
+            self.trip = rtt / 2.;
+        }
+    }
+
+    /// Host time of a timestamp of the client, `None` before the first sample.
+    pub fn host_time(&self, client_time: f64) -> Option<f64> {
+        self.offset.map(|offset| client_time + offset - self.trip)
+    }
+}
+
 #[derive(Debug, Component)]
 pub struct Character {
     pub id: PlayerId,
@@ -258,10 +452,31 @@ pub struct TransportDataResource {
     pub data: TransportData,
 }
 
+/// How the camera of a player looks at the character.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum ViewMode {
+    /// Third person camera turning around the character.
+    Orbit,
+    /// Camera high above the character, the main view of the game.
+    #[default]
+    TopDown,
+}
+
+impl ViewMode {
+    pub fn toggle(&mut self) -> Self {
+        match self {
+            ViewMode::Orbit => *self = ViewMode::TopDown,
+            ViewMode::TopDown => *self = ViewMode::Orbit,
+        }
+        *self
+    }
+}
+
 #[derive(Debug, Component, Default, Serialize, Deserialize, Clone, Copy, Reflect)]
 pub struct PlayerView {
     pub direction: Quat,
     pub distance: f32,
+    pub mode: ViewMode,
 }
 
 impl PlayerView {
@@ -269,12 +484,13 @@ impl PlayerView {
         Self {
             direction,
             distance,
+            mode: ViewMode::default(),
         }
     }
 }
 
 // TODO: to core.rs
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Reflect)]
 pub enum LevelCode {
     Url(String),
     Path(String),
@@ -296,3 +512,23 @@ impl Plugin for LobbyPlugins {
             .add_plugins((HostLobbyPlugins, SingleLobbyPlugins, ClientLobbyPlugins));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_keeps_the_trip_of_the_smallest_gap() {
+        let mut clock = PeerClock::default();
+        assert_eq!(clock.host_time(1.), None);
+
+        clock.sample(10., 15., 0.2);
+        // a slower message does not move the estimate
+        clock.sample(11., 16.5, 1.);
+        assert!((clock.host_time(12.).unwrap() - 16.9).abs() < 1e-9);
+
+        clock.sample(12., 16.95, 0.1);
+        assert!((clock.host_time(12.).unwrap() - 16.9).abs() < 1e-9);
+        assert!((clock.trip - 0.05).abs() < 1e-9);
+    }
+}
@@ -1,11 +1,11 @@
-use crate::component::{DespawnReason, Respawn};
+use crate::component::{DespawnReason, Respawn, SpawnPoints, SpawnTag};
 use crate::core::CoreGameState;
 use crate::lobby::host::generate_player_color;
 use crate::lobby::LobbyState;
 use crate::world::Me;
 use crate::{
     actor::{
-        character::{spawn_character, spawn_tied_camera, TiedCamera},
+        character::{spawn_character, spawn_tied_camera, SelectedClass, TiedCamera},
         UnloadActorsEvent,
     },
     core::KnownLevel,
@@ -58,7 +58,9 @@ pub fn init_lobby(
 pub fn load_processing(
     mut commands: Commands,
     spawn_point: Res<SpawnProperty>,
+    mut spawn_points: SpawnPoints,
     mut query: Query<&mut Respawn, With<Me>>,
+    selected_class: Res<SelectedClass>,
 ) {
     info!("LoadProcessing: {:#?}", spawn_point);
     if !spawn_point.is_empty() {
@@ -67,16 +69,22 @@ pub fn load_processing(
                 // spawn character fitst time
                 let random_i32 = rand::random::<i32>();
                 let color = generate_player_color(random_i32 as u32);
+                let Some(spawn) =
+                    spawn_points.select(&spawn_point.clone().with_tag(SpawnTag::Start), &[])
+                else {
+                    log::error!("No spawn point on level");
+                    return;
+                };
 
                 let player_entity = commands
-                    .spawn_character(PlayerId::HostOrSingle, color, spawn_point.random_point())
+                    .spawn_character(PlayerId::HostOrSingle, color, spawn, **selected_class)
                     .insert(Me)
                     .id();
                 commands.spawn_tied_camera(player_entity);
             }
             Ok(mut respawn) => {
                 // respawn character
-                respawn.replase_spawn_point(spawn_point.clone());
+                respawn.replase_spawn_point(spawn_point.clone().with_tag(SpawnTag::Respawn));
                 respawn.insert_reason(DespawnReason::Forced);
             }
         }
@@ -6,7 +6,7 @@ use bevy::winit::WinitWindows;
 use bevy_egui::EguiPlugin;
 use bevy_rapier3d::plugin::{NoUserData, RapierPhysicsPlugin};
 use jeraido::core::CorePlugins;
-use jeraido::ASSET_DIR;
+use jeraido::{LevelPackagePlugins, ASSET_DIR};
 use winit::window::Icon;
 #[cfg(all(debug_assertions, feature = "devtools"))]
 use jeraido::DEBUG;
@@ -66,6 +66,9 @@ fn main() {
         ))
     }
 
+    // asset sources are registered before the `AssetPlugin`
+    app.add_plugins(LevelPackagePlugins);
+
     #[cfg(not(feature = "devtools"))]
     default_build(&mut app, asset_plugin, log_plugin);
 
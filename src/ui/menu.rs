@@ -1,4 +1,5 @@
-use crate::core::{LoadLevelEvent, CoreGameState};
+use crate::actor::character::{CharacterClass, CharacterClasses, SelectedClass};
+use crate::core::{LoadLevelEvent, CoreGameState, KnownLevel};
 use crate::lobby::{ClientResource, HostResource, LevelCode, LobbyState};
 use crate::settings::{ApplySettings, ExemptSettings, Settings};
 use crate::ui::{rich_text, TRANSPARENT};
@@ -8,6 +9,7 @@ use bevy::prelude::*;
 use bevy::window::Window;
 use bevy_egui::egui::Align2;
 use bevy_egui::{egui, EguiContexts};
+use strum::IntoEnumIterator;
 
 use super::{MouseGrabState, ViewportRect};
 
@@ -77,6 +79,8 @@ fn menu(
     mut windows: Query<&Window>,
     mut next_state_lobby: ResMut<NextState<LobbyState>>,
     mut load_level_event: EventWriter<LoadLevelEvent>,
+    mut selected_class: ResMut<SelectedClass>,
+    classes: Res<CharacterClasses>,
 ) {
     let ctx = context.ctx_mut();
 
@@ -101,6 +105,7 @@ fn menu(
         .resizable(false)
         .movable(false)
         .show(ctx, |ui| {
+            class_selector(ui, &mut selected_class, &classes, &font);
             if ui
                 .button(rich_text("Start".to_string(), Module(&MODULE), &font))
                 .clicked()
@@ -111,6 +116,17 @@ fn menu(
                     LevelCode::Path("Level2".into()),
                 ));
             }
+            if ui
+                .button(rich_text("Dungeon".to_string(), Module(&MODULE), &font))
+                .clicked()
+            {
+                next_state_lobby.set(LobbyState::Single);
+                load_level_event.send(LoadLevelEvent::new(LevelCode::Known(
+                    KnownLevel::Dungeon {
+                        seed: rand::random(),
+                    },
+                )));
+            }
             if ui
                 .button(rich_text("Multiplayer".to_string(), Module(&MODULE), &font))
                 .clicked()
@@ -143,6 +159,8 @@ fn multiplayer_window(
     ui_frame_rect: ResMut<ViewportRect>,
     mut client_resource: ResMut<ClientResource>,
     mut nex_state_mouse_grab: ResMut<NextState<MouseGrabState>>,
+    mut selected_class: ResMut<SelectedClass>,
+    classes: Res<CharacterClasses>,
 ) {
     // let window = windows.single_mut();
     // let window_size = egui::vec2(window.width(), window.height());
@@ -169,6 +187,7 @@ fn multiplayer_window(
         .resizable(false)
         .movable(false)
         .show(ctx, |ui| {
+            class_selector(ui, &mut selected_class, &classes, &font);
             match state.multiplayer_state {
                 MultiplayerState::Create => {
                     ui.horizontal(|ui| {
@@ -242,6 +261,21 @@ fn multiplayer_window(
         });
 }
 
+/// Row of the playable classes, the selected one is played in every lobby.
+fn class_selector(
+    ui: &mut egui::Ui,
+    selected: &mut SelectedClass,
+    classes: &CharacterClasses,
+    font: &egui::FontId,
+) {
+    ui.horizontal(|ui| {
+        for class in CharacterClass::iter() {
+            let name = classes.get(class).name.clone();
+            ui.selectable_value(&mut selected.0, class, rich_text(name, Module(&MODULE), font));
+        }
+    });
+}
+
 fn settings_window(
     mut next_state_menu_window: ResMut<NextState<WindowState>>,
     mut context: EguiContexts,
@@ -0,0 +1,156 @@
+//! Point cloud gesture recognizer after $P (Vatavu, Anthony, Wobbrock, 2012).
+//!
+//! Strokes are resampled to a fixed number of points, scaled into a unit box and
+//! centered, then matched by pairing every point with the closest free one of the
+//! template. The order and direction of the strokes do not matter, the rotation does.
+
+use bevy::math::Vec2;
+
+/// Points every cloud is resampled to.
+pub const CLOUD_SIZE: usize = 32;
+
+/// Normalized gesture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointCloud(Vec<Vec2>);
+
+impl PointCloud {
+    /// `None` when the strokes have no length to resample.
+    pub fn new(strokes: &[Vec<Vec2>]) -> Option<Self> {
+        let points = resample(strokes, CLOUD_SIZE)?;
+        Some(Self(center(&scale(&points))))
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.0
+    }
+
+    /// Distance to another cloud, 0 for the same gesture.
+    pub fn distance(&self, other: &PointCloud) -> f32 {
+        let n = self.0.len().min(other.0.len());
+        if n == 0 {
+            return f32::INFINITY;
+        }
+        let step = ((n as f32).sqrt() as usize).max(1);
+        (0..n)
+            .step_by(step)
+            .map(|start| {
+                cloud_distance(&self.0, &other.0, start)
+                    .min(cloud_distance(&other.0, &self.0, start))
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Similarity in `0..=1`, 1 for the same gesture.
+    pub fn score(&self, other: &PointCloud) -> f32 {
+        let distance = self.distance(other);
+        if distance > 1. {
+            1. / distance
+        } else {
+            1.
+        }
+    }
+}
+
+/// Scales the strokes into a unit box around the origin, keeping the proportions.
+pub fn normalize_strokes(strokes: &[Vec<Vec2>]) -> Vec<Vec<Vec2>> {
+    let points: Vec<Vec2> = strokes.iter().flatten().copied().collect();
+    let Some((min, max)) = bounds(&points) else {
+        return Vec::new();
+    };
+    let size = (max - min).max_element().max(f32::EPSILON);
+    let middle = (min + max) / 2.;
+    strokes
+        .iter()
+        .map(|stroke| {
+            stroke
+                .iter()
+                .map(|point| (*point - middle) / size)
+                .collect()
+        })
+        .collect()
+}
+
+/// Greedy matching starting at `start`, the first pairs weigh the most.
+fn cloud_distance(points: &[Vec2], template: &[Vec2], start: usize) -> f32 {
+    let n = points.len().min(template.len());
+    let mut matched = vec![false; n];
+    let mut sum = 0.;
+    for offset in 0..n {
+        let point = points[(start + offset) % n];
+        let Some((index, distance)) = template[..n]
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !matched[*index])
+            .map(|(index, other)| (index, point.distance(*other)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+        else {
+            break;
+        };
+        matched[index] = true;
+        let weight = 1. - offset as f32 / n as f32;
+        sum += weight * distance;
+    }
+    sum
+}
+
+/// Spreads `n` points evenly along the strokes, never between two of them.
+fn resample(strokes: &[Vec<Vec2>], n: usize) -> Option<Vec<Vec2>> {
+    let length: f32 = strokes
+        .iter()
+        .flat_map(|stroke| stroke.windows(2))
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum();
+    if length <= f32::EPSILON || n < 2 {
+        return None;
+    }
+    let interval = length / (n - 1) as f32;
+
+    let mut points = Vec::with_capacity(n);
+    let mut walked = 0.;
+    for stroke in strokes.iter().filter(|stroke| !stroke.is_empty()) {
+        let mut previous = stroke[0];
+        if points.is_empty() {
+            points.push(previous);
+        }
+        for &next in &stroke[1..] {
+            let mut segment = previous.distance(next);
+            while walked + segment >= interval && segment > 0. {
+                let point = previous.lerp(next, (interval - walked) / segment);
+                points.push(point);
+                segment -= previous.distance(point);
+                previous = point;
+                walked = 0.;
+            }
+            walked += segment;
+            previous = next;
+        }
+    }
+    // rounding may leave the last point out
+    if let Some(last) = strokes.iter().rev().find_map(|stroke| stroke.last()) {
+        while points.len() < n {
+            points.push(*last);
+        }
+    }
+    points.truncate(n);
+    Some(points)
+}
+
+fn scale(points: &[Vec2]) -> Vec<Vec2> {
+    let Some((min, max)) = bounds(points) else {
+        return Vec::new();
+    };
+    let size = (max - min).max_element().max(f32::EPSILON);
+    points.iter().map(|point| (*point - min) / size).collect()
+}
+
+fn center(points: &[Vec2]) -> Vec<Vec2> {
+    let centroid = points.iter().sum::<Vec2>() / points.len().max(1) as f32;
+    points.iter().map(|point| *point - centroid).collect()
+}
+
+fn bounds(points: &[Vec2]) -> Option<(Vec2, Vec2)> {
+    let first = *points.first()?;
+    Some(points.iter().fold((first, first), |(min, max), point| {
+        (min.min(*point), max.max(*point))
+    }))
+}
@@ -3,6 +3,7 @@
 mod util;
 
 pub mod extend_commands;
+pub mod gesture;
 pub mod i18n;
 
 
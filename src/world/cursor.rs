@@ -0,0 +1,109 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::EguiSettings;
+use bevy_rapier3d::prelude::*;
+
+use crate::{lobby::Lobby, ui::ViewportRect};
+
+use super::{MainCamera, Me};
+
+/// Farthest the cursor ray reaches.
+const MAX_CURSOR_DISTANCE: f32 = 500.;
+
+/// What the cursor of this peer points at in the world.
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct CursorWorld {
+    /// Ray from the camera through the cursor, `None` outside the game view.
+    pub ray: Option<Ray3d>,
+    /// First point the ray hits, on the height of the character if it hits nothing.
+    pub point: Option<Vec3>,
+    pub normal: Option<Vec3>,
+    /// Collider under the cursor.
+    pub entity: Option<Entity>,
+}
+
+pub struct CursorPlugins;
+
+impl Plugin for CursorPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorWorld>()
+            .add_systems(PreUpdate, (pick_cursor_world, aim_at_cursor).chain());
+    }
+}
+
+fn pick_cursor_world(
+    mut cursor: ResMut<CursorWorld>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    me_query: Query<(Entity, &GlobalTransform), With<Me>>,
+    viewport_rect: Res<ViewportRect>,
+    egui_settings: Option<Res<EguiSettings>>,
+    rapier_context: Res<RapierContext>,
+) {
+    *cursor = CursorWorld::default();
+
+    let Some(position) = window_query
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+
+    // the editor shrinks the game view to a part of the window
+    let scale = egui_settings.map_or(1., |settings| settings.scale_factor);
+    let min = Vec2::new(viewport_rect.min.x, viewport_rect.min.y) * scale;
+    let size = Vec2::new(viewport_rect.width(), viewport_rect.height()) * scale;
+    let relative = (position - min) / size;
+    if !(0. ..=1.).contains(&relative.x) || !(0. ..=1.).contains(&relative.y) {
+        return;
+    }
+    let ndc = Vec2::new(relative.x * 2. - 1., 1. - relative.y * 2.);
+    let (Some(near), Some(far)) = (
+        camera.ndc_to_world(camera_transform, ndc.extend(1.)),
+        camera.ndc_to_world(camera_transform, ndc.extend(f32::EPSILON)),
+    ) else {
+        return;
+    };
+    let Some(direction) = (far - near).try_normalize() else {
+        return;
+    };
+    let ray = Ray3d::new(near, direction);
+    cursor.ray = Some(ray);
+
+    let me = me_query.get_single().ok();
+    let mut filter = QueryFilter::new().exclude_sensors();
+    if let Some((entity, _)) = me {
+        filter = filter.exclude_collider(entity);
+    }
+    if let Some((entity, hit)) = rapier_context.cast_ray_and_get_normal(
+        ray.origin,
+        *ray.direction,
+        MAX_CURSOR_DISTANCE,
+        true,
+        filter,
+    ) {
+        cursor.point = Some(hit.point);
+        cursor.normal = Some(hit.normal);
+        cursor.entity = Some(entity);
+        return;
+    }
+
+    // looking into the void, aim on the height of the character
+    let height = me.map_or(0., |(_, transform)| transform.translation().y);
+    cursor.point = ray
+        .intersect_plane(Vec3::Y * height, Plane3d::new(Vec3::Y))
+        .map(|distance| ray.get_point(distance));
+    cursor.normal = cursor.point.map(|_| Vec3::Y);
+}
+
+/// Stores the cursor point as the aim of this peer.
+fn aim_at_cursor(cursor: Res<CursorWorld>, lobby: Option<ResMut<Lobby>>) {
+    if let Some(mut lobby) = lobby {
+        if lobby.me.aim != cursor.point {
+            lobby.me.aim = cursor.point;
+        }
+    }
+}
@@ -1,9 +1,11 @@
 #![allow(clippy::module_inception)]
 
 mod camera;
+mod cursor;
 mod spawn_point;
 mod world;
 
 pub use camera::*;
+pub use cursor::*;
 pub use spawn_point::*;
 pub use world::*;
@@ -1,39 +1,100 @@
 use bevy::{
-    ecs::system::Resource,
+    ecs::{entity::Entity, system::Resource},
     math::Vec3,
-    prelude::{Deref, DerefMut},
     reflect::Reflect,
+    transform::components::Transform,
 };
 use bevy_inspector_egui::{inspector_options::ReflectInspectorOptions, InspectorOptions};
-use rand::Rng;
 
-#[derive(Debug, Clone, Resource, InspectorOptions, Deref, DerefMut, Default, Reflect)]
+use crate::component::SpawnTag;
+
+/// A place an actor may spawn at.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum SpawnLocation {
+    Fixed(Transform),
+    /// A [`SpawnPoint`](crate::component::SpawnPoint) entity, followed when it moves.
+    Point(Entity),
+}
+
+/// How a location is picked when a [`SpawnProperty`] has several.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum SpawnStrategy {
+    #[default]
+    Random,
+    /// The spawn points take turns.
+    RoundRobin,
+    /// The location farthest from the closest enemy.
+    FarthestFromEnemies,
+    /// The location nobody spawned at for the longest time.
+    LeastRecentlyUsed,
+}
+
+/// Locations an actor spawns at, picked by [`SpawnPoints`](crate::component::SpawnPoints).
+#[derive(Debug, Clone, Resource, InspectorOptions, Default, Reflect)]
 #[reflect(InspectorOptions)]
-pub struct SpawnProperty(Vec<Vec3>);
+pub struct SpawnProperty {
+    locations: Vec<SpawnLocation>,
+    /// Locations with another tag are skipped unless none has this one.
+    tag: Option<SpawnTag>,
+    strategy: SpawnStrategy,
+}
 
 impl SpawnProperty {
     pub fn new<T: IntoVec3Vec>(spawn_points: T) -> Self {
-        Self(spawn_points.into_vec3_vec())
+        Self {
+            locations: spawn_points
+                .into_vec3_vec()
+                .into_iter()
+                .map(|point| SpawnLocation::Fixed(Transform::from_translation(point)))
+                .collect(),
+            ..Self::empty()
+        }
+    }
+
+    pub fn from_transform(transform: Transform) -> Self {
+        Self {
+            locations: vec![SpawnLocation::Fixed(transform)],
+            ..Self::empty()
+        }
     }
 
     #[allow(dead_code)]
     pub fn empty() -> Self {
-        Self(Vec::new())
+        Self {
+            locations: Vec::new(),
+            tag: None,
+            strategy: SpawnStrategy::default(),
+        }
+    }
+
+    pub fn with_tag(mut self, tag: SpawnTag) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: SpawnStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn push(&mut self, location: SpawnLocation) {
+        self.locations.push(location);
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.locations.is_empty()
     }
 
-    #[allow(dead_code)]
-    pub fn points(&self) -> &[Vec3] {
-        &self.0
+    pub fn locations(&self) -> &[SpawnLocation] {
+        &self.locations
+    }
+
+    pub fn tag(&self) -> Option<&SpawnTag> {
+        self.tag.as_ref()
     }
 
-    pub fn random_point(&self) -> Vec3 {
-        let mut rng = rand::thread_rng();
-        let index = rng.gen_range(0..self.0.len());
-        self.0[index]
+    pub fn strategy(&self) -> SpawnStrategy {
+        self.strategy
     }
 }
 
@@ -1,23 +1,60 @@
 use crate::actor::ActorPlugins;
 use crate::component::ComponentPlugins;
 use crate::level::MapPlugins;
-use crate::lobby::{LobbyPlugins};
+use crate::lobby::{LobbyPlugins, PlayerId};
 use crate::settings::SettingsPlugins;
 use crate::sound::SoundPlugins;
 use crate::ui::UiPlugins;
+use crate::world::CursorPlugins;
 use bevy::prelude::*;
+use bevy_rapier3d::prelude::{CollisionGroups, Group};
 use serde::{Deserialize, Serialize};
 
 
 
-/// Enum representing collision layers for physics interactions.
-// TODO: #[derive(PhysicsLayer)]
-//pub enum CollisionLayer {
-//    /// Actors with this layer cannot collide with each other.
-//    ActorNoclip,
-//    /// The default collision layer.
-//    Default,
-//}
+/// Collision groups for physics interactions.
+///
+/// Level geometry is a member of every group, so it collides with both kinds of actors.
+pub struct CollisionLayer;
+
+impl CollisionLayer {
+    /// Actors collide with each other.
+    pub const ACTOR: Group = Group::GROUP_1;
+    /// Actors in this group pass through other actors.
+    pub const ACTOR_NOCLIP: Group = Group::GROUP_2;
+    /// Projectiles hit everything but actors in noclip.
+    pub const PROJECTILE: Group = Group::GROUP_3;
+    /// Melee hitboxes only touch actors.
+    pub const HITBOX: Group = Group::GROUP_4;
+
+    pub fn actor() -> CollisionGroups {
+        CollisionGroups::new(Self::ACTOR, Group::ALL)
+    }
+
+    pub fn actor_noclip() -> CollisionGroups {
+        CollisionGroups::new(
+            Self::ACTOR_NOCLIP,
+            Group::ALL.difference(Self::ACTOR | Self::ACTOR_NOCLIP),
+        )
+    }
+
+    pub fn projectile() -> CollisionGroups {
+        CollisionGroups::new(Self::PROJECTILE, Group::ALL.difference(Self::ACTOR_NOCLIP))
+    }
+
+    pub fn hitbox() -> CollisionGroups {
+        CollisionGroups::new(Self::HITBOX, Self::ACTOR)
+    }
+
+    /// Groups of scene queries that pass through actors and only see the level.
+    pub fn scenery() -> CollisionGroups {
+        CollisionGroups::new(
+            Group::ALL,
+            Group::ALL
+                .difference(Self::ACTOR | Self::ACTOR_NOCLIP | Self::PROJECTILE | Self::HITBOX),
+        )
+    }
+}
 
 /// A component representing a promised GLTF scene.
 ///
@@ -47,19 +84,22 @@ pub struct PromisedScene;
 pub enum LinkId {
     Scene(String),
     Projectile(usize),
+    /// Node of a scene by the path of the scene and the names down to the node.
+    Node(String),
+    /// Rune inscribed by a player, by the sequence number of the activation.
+    Rune(PlayerId, u32),
 }
 
 #[derive(Resource, Default, Reflect, Debug, Clone, Copy, PartialEq, Eq, Deref, DerefMut)]
 pub struct ProjectileIdSeq(usize);
 
-// TODO:
-//impl ProjectileIdSeq {
-//    /// Returns the next projectile ID. A new ID is generated each time this method is called.
-//    pub fn shift(&mut self) -> LinkId {
-//        self.0 += 1;
-//        LinkId::Projectile(self.0)
-//    }
-//}
+impl ProjectileIdSeq {
+    /// Returns the next projectile ID. A new ID is generated each time this method is called.
+    pub fn shift(&mut self) -> LinkId {
+        self.0 += 1;
+        LinkId::Projectile(self.0)
+    }
+}
 
 pub struct WorldPlugins;
 
@@ -75,6 +115,7 @@ impl Plugin for WorldPlugins {
                 LobbyPlugins,
                 ActorPlugins,
                 ComponentPlugins,
+                CursorPlugins,
             ));
     }
 }